@@ -0,0 +1,130 @@
+//! Batched order operations, letting resolvers and monitors act on several
+//! orders in one transaction
+use crate::*;
+
+/// Parameters of a native order for `create_htlc`, `create_htlc_batch` and
+/// `announce_htlc`. `amount` is the NEAR the order takes from the attached
+/// deposit, safety deposit included.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CreateHtlcArgs {
+    pub order_hash: Base64VecU8,
+    pub src_maker: String,
+    pub src_chain: String,
+    pub src_token: String,
+    pub src_amount: U128,
+    pub dst_recipient: AccountId,
+    pub dst_token: String,
+    pub hash_lock: Base64VecU8,
+    pub timelock: Timestamp,
+    #[serde(default)]
+    pub complete_by: Option<Timestamp>,
+    #[serde(default)]
+    pub refund_bridge: Option<AccountId>,
+    #[serde(default)]
+    pub auto_complete_secret: Option<Base64VecU8>,
+    #[serde(default)]
+    pub src_timelock: Option<Timestamp>,
+    #[serde(default)]
+    pub hash_algorithm: Option<HashAlgorithm>,
+    #[serde(default)]
+    pub safety_deposit: Option<U128>,
+    #[serde(default)]
+    pub public_window_start: Option<Timestamp>,
+    #[serde(default)]
+    pub timelocks: Option<Timelocks>,
+    #[serde(default)]
+    pub merkle_root: Option<Base64VecU8>,
+    #[serde(default)]
+    pub memo: Option<String>,
+    #[serde(default)]
+    pub refund_to: Option<AccountId>,
+    #[serde(default)]
+    pub relayer_fee: Option<U128>,
+    #[serde(default)]
+    pub allow_recipient_override: Option<bool>,
+    #[serde(default)]
+    pub src_block_height: Option<u64>,
+    #[serde(default)]
+    pub alt_hash_locks: Option<Vec<Base64VecU8>>,
+    #[serde(default)]
+    pub push_on_complete: Option<bool>,
+    #[serde(default)]
+    pub auction: Option<AuctionConfig>,
+    #[serde(default)]
+    pub parts: Option<u32>,
+    pub amount: U128,
+}
+
+#[near_bindgen]
+impl NEAREscrow {
+    /// Create several native NEAR orders, splitting the attached deposit
+    /// by their `amount`s. Every order is validated before any is stored,
+    /// so one invalid entry rejects the whole batch.
+    #[payable]
+    pub fn create_htlc_batch(&mut self, orders: Vec<CreateHtlcArgs>) {
+        assert!(!orders.is_empty(), "Empty batch");
+        let total = orders
+            .iter()
+            .try_fold(0, |total: Balance, order| total.checked_add(order.amount.0))
+            .expect("Batch amount overflow");
+        assert_eq!(
+            total,
+            env::attached_deposit(),
+            "Batch amounts must equal attached deposit"
+        );
+
+        let swap_orders: Vec<SwapOrder> = orders
+            .into_iter()
+            .map(|args| self.native_order(args))
+            .collect();
+        for (i, swap_order) in swap_orders.iter().enumerate() {
+            self.assert_valid_order(swap_order);
+            assert!(
+                swap_orders[..i]
+                    .iter()
+                    .all(|other| other.order_hash != swap_order.order_hash),
+                "Duplicate order in batch"
+            );
+            assert!(
+                !swap_orders[..i]
+                    .iter()
+                    .flat_map(SwapOrder::hash_locks)
+                    .any(|other| swap_order.hash_locks().any(|hash_lock| hash_lock == other)),
+                "Duplicate hash lock in batch"
+            );
+        }
+
+        for swap_order in swap_orders {
+            self.store_order(swap_order);
+        }
+    }
+
+    /// Refund every listed order that `refund_htlc` would refund, each to its
+    /// own resolver. Orders that can't be refunded yet are skipped with a log
+    /// line instead of failing the batch. Returns the refunded hashes.
+    pub fn refund_htlc_batch(&mut self, order_hashes: Vec<Base64VecU8>) -> Vec<Base64VecU8> {
+        let mut refunded = Vec::new();
+        for order_hash in order_hashes {
+            let executor = env::predecessor_account_id();
+            let skip_reason = match self.swap_orders.get(&order_hash) {
+                None => Some("Order not found"),
+                Some(order) => self
+                    .refund_blocker(&order, &executor)
+                    .map(|(_, message)| message),
+            };
+            if let Some(reason) = skip_reason {
+                env::log_str(&format!(
+                    "Skipping refund of htlc:{}: {}",
+                    hex::encode(&order_hash.0),
+                    reason
+                ));
+                continue;
+            }
+
+            self.refund_htlc(order_hash.clone());
+            refunded.push(order_hash);
+        }
+        refunded
+    }
+}
@@ -0,0 +1,83 @@
+//! Stable error codes for order creation, completion and refund failures.
+//! Each panic message starts with the code, e.g.
+//! `"E_UNSUPPORTED_CHAIN: Unsupported source chain"`, so clients can match
+//! on the prefix while the rest stays human-readable.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorCode {
+    Paused,
+    ResolverNotAllowed,
+    UnsupportedChain,
+    InvalidToken,
+    InvalidAmount,
+    InvalidAuction,
+    InvalidTimelock,
+    OrderExists,
+    InvalidHashLock,
+    OrderHashMismatch,
+    InvalidRecipient,
+    InvalidMemo,
+    RateOutOfBounds,
+    RateLimited,
+    InsufficientStorage,
+    OrderNotFound,
+    OrderNotActive,
+    OrderExpired,
+    OrderNotExpired,
+    NotFinal,
+    InvalidSecret,
+    Unauthorized,
+    PartialFillRequired,
+    PartialFillUnsupported,
+    InvalidLeafIndex,
+    PayoutBelowMinimum,
+    DeadlinePassed,
+    NotEnoughGas,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::Paused => "E_PAUSED",
+            ErrorCode::ResolverNotAllowed => "E_RESOLVER_NOT_ALLOWED",
+            ErrorCode::UnsupportedChain => "E_UNSUPPORTED_CHAIN",
+            ErrorCode::InvalidToken => "E_INVALID_TOKEN",
+            ErrorCode::InvalidAmount => "E_INVALID_AMOUNT",
+            ErrorCode::InvalidAuction => "E_INVALID_AUCTION",
+            ErrorCode::InvalidTimelock => "E_INVALID_TIMELOCK",
+            ErrorCode::OrderExists => "E_ORDER_EXISTS",
+            ErrorCode::InvalidHashLock => "E_INVALID_HASH_LOCK",
+            ErrorCode::OrderHashMismatch => "E_ORDER_HASH_MISMATCH",
+            ErrorCode::InvalidRecipient => "E_INVALID_RECIPIENT",
+            ErrorCode::InvalidMemo => "E_INVALID_MEMO",
+            ErrorCode::RateOutOfBounds => "E_RATE_OUT_OF_BOUNDS",
+            ErrorCode::RateLimited => "E_RATE_LIMITED",
+            ErrorCode::InsufficientStorage => "E_INSUFFICIENT_STORAGE",
+            ErrorCode::OrderNotFound => "E_ORDER_NOT_FOUND",
+            ErrorCode::OrderNotActive => "E_ORDER_NOT_ACTIVE",
+            ErrorCode::OrderExpired => "E_ORDER_EXPIRED",
+            ErrorCode::OrderNotExpired => "E_ORDER_NOT_EXPIRED",
+            ErrorCode::NotFinal => "E_NOT_FINAL",
+            ErrorCode::InvalidSecret => "E_INVALID_SECRET",
+            ErrorCode::Unauthorized => "E_UNAUTHORIZED",
+            ErrorCode::PartialFillRequired => "E_PARTIAL_FILL_REQUIRED",
+            ErrorCode::PartialFillUnsupported => "E_PARTIAL_FILL_UNSUPPORTED",
+            ErrorCode::InvalidLeafIndex => "E_INVALID_LEAF_INDEX",
+            ErrorCode::PayoutBelowMinimum => "E_PAYOUT_BELOW_MINIMUM",
+            ErrorCode::DeadlinePassed => "E_DEADLINE_PASSED",
+            ErrorCode::NotEnoughGas => "E_NOT_ENOUGH_GAS",
+        }
+    }
+}
+
+/// Panic with `code` followed by `message`
+pub(crate) fn fail(code: ErrorCode, message: &str) -> ! {
+    panic!("{}: {}", code.as_str(), message)
+}
+
+/// Fail with `code` and `message` unless `condition` holds
+pub(crate) fn require(condition: bool, code: ErrorCode, message: &str) {
+    if !condition {
+        fail(code, message)
+    }
+}
@@ -0,0 +1,223 @@
+//! NEP-297 events emitted on HTLC lifecycle transitions, logged as
+//! `EVENT_JSON:{"standard":"htlc","version":"1.0.0","event":...,"data":[...]}`
+use crate::*;
+
+pub const EVENT_STANDARD: &str = "htlc";
+pub const EVENT_STANDARD_VERSION: &str = "1.0.0";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcCreatedData {
+    pub order_hash: Base64VecU8,
+    pub resolver: AccountId,
+    pub dst_recipient: AccountId,
+    pub dst_token: String,
+    pub dst_amount: U128,
+    pub hash_lock: Base64VecU8,
+    pub timelock: Timestamp,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcCompletedData {
+    pub order_hash: Base64VecU8,
+    pub secret: Base64VecU8,
+    pub dst_recipient: AccountId,
+    pub amount: U128,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcRefundedData {
+    pub order_hash: Base64VecU8,
+    pub resolver: AccountId,
+    pub amount: U128,
+}
+
+/// Resolver paid to push out an active order's completion deadline
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcCompletionExtendedData {
+    pub order_hash: Base64VecU8,
+    pub old_complete_by: Timestamp,
+    pub new_complete_by: Timestamp,
+    pub fee: U128,
+}
+
+/// Completion payout failed and the order went back to `Active`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcCompletionFailedData {
+    pub order_hash: Base64VecU8,
+    pub amount: U128,
+}
+
+/// Order passed its timelock without settling and awaits refund
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcExpiredData {
+    pub order_hash: Base64VecU8,
+    pub timelock: Timestamp,
+}
+
+/// Resolver pushed out an active order's timelock
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcTimelockExtendedData {
+    pub order_hash: Base64VecU8,
+    pub old_timelock: Timestamp,
+    pub new_timelock: Timestamp,
+}
+
+/// Resolver handed an active order over to another resolver
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcResolverReassignedData {
+    pub order_hash: Base64VecU8,
+    pub old_resolver: AccountId,
+    pub new_resolver: AccountId,
+}
+
+/// Another account added NEAR to an active order
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcContributedData {
+    pub order_hash: Base64VecU8,
+    pub contributor: AccountId,
+    pub amount: U128,
+}
+
+/// Resolver added NEAR to an active order's deposit
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcToppedUpData {
+    pub order_hash: Base64VecU8,
+    pub amount: U128,
+    pub dst_amount: U128,
+}
+
+/// Settled order deleted by `purge_order`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcPurgedData {
+    pub order_hash: Base64VecU8,
+}
+
+/// Order recorded by `announce_htlc`, awaiting its deposit
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcAnnouncedData {
+    pub order_hash: Base64VecU8,
+    pub resolver: AccountId,
+    pub dst_recipient: AccountId,
+    pub dst_amount: U128,
+    pub hash_lock: Base64VecU8,
+    pub timelock: Timestamp,
+}
+
+/// Announced order withdrawn by its resolver before funding
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcCancelledData {
+    pub order_hash: Base64VecU8,
+}
+
+/// Recipient pulled funds credited by pull-mode completions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcClaimedData {
+    pub account_id: AccountId,
+    pub token: String,
+    pub amount: U128,
+}
+
+/// Stuck order rescued by the owner long after its timelock
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcForceRefundedData {
+    pub order_hash: Base64VecU8,
+    pub rescue_to: AccountId,
+    pub amount: U128,
+}
+
+/// Order frozen by the owner pending investigation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcDisputedData {
+    pub order_hash: Base64VecU8,
+}
+
+/// Disputed order released back to `Active` or refunded by the owner
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HtlcDisputeResolvedData {
+    pub order_hash: Base64VecU8,
+    pub allow_complete: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum HtlcEvent {
+    HtlcCreated(Vec<HtlcCreatedData>),
+    HtlcCompleted(Vec<HtlcCompletedData>),
+    HtlcRefunded(Vec<HtlcRefundedData>),
+    HtlcCompletionExtended(Vec<HtlcCompletionExtendedData>),
+    HtlcCompletionFailed(Vec<HtlcCompletionFailedData>),
+    HtlcExpired(Vec<HtlcExpiredData>),
+    HtlcTimelockExtended(Vec<HtlcTimelockExtendedData>),
+    HtlcResolverReassigned(Vec<HtlcResolverReassignedData>),
+    HtlcContributed(Vec<HtlcContributedData>),
+    HtlcAnnounced(Vec<HtlcAnnouncedData>),
+    HtlcCancelled(Vec<HtlcCancelledData>),
+    HtlcDisputed(Vec<HtlcDisputedData>),
+    HtlcDisputeResolved(Vec<HtlcDisputeResolvedData>),
+    HtlcForceRefunded(Vec<HtlcForceRefundedData>),
+    HtlcClaimed(Vec<HtlcClaimedData>),
+    HtlcToppedUp(Vec<HtlcToppedUpData>),
+    HtlcPurged(Vec<HtlcPurgedData>),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventLog {
+    pub standard: String,
+    pub version: String,
+    #[serde(flatten)]
+    pub event: HtlcEvent,
+}
+
+impl HtlcEvent {
+    /// Log the event. Contract methods emit through `NEAREscrow::emit_event`
+    /// so the event checkpoint stays current.
+    pub fn emit(self) {
+        let log = EventLog {
+            standard: EVENT_STANDARD.to_string(),
+            version: EVENT_STANDARD_VERSION.to_string(),
+            event: self,
+        };
+        env::log_str(&format!(
+            "EVENT_JSON:{}",
+            serde_json::to_string(&log).expect("Failed to serialize event")
+        ));
+    }
+}
+
+#[near_bindgen]
+impl NEAREscrow {
+    /// Block height and sequence number of the latest event, letting
+    /// indexers check they have seen every event before resuming
+    pub fn get_event_checkpoint(&self) -> (BlockHeight, u64) {
+        (self.last_event_block, self.last_event_index)
+    }
+}
+
+impl NEAREscrow {
+    /// Emit an event, advancing the event checkpoint
+    pub(crate) fn emit_event(&mut self, event: HtlcEvent) {
+        self.last_event_block = env::block_height();
+        self.last_event_index += 1;
+        event.emit();
+    }
+}
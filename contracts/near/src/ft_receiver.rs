@@ -0,0 +1,125 @@
+use crate::*;
+use near_sdk::PromiseOrValue;
+
+/// Order parameters carried in the `msg` of `ft_transfer_call` (and of
+/// `nft_transfer_call` for NFT orders)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtHtlcMessage {
+    pub order_hash: Base64VecU8,
+    pub src_maker: String,
+    pub src_chain: String,
+    #[serde(default)]
+    pub src_token: Option<String>,
+    #[serde(default)]
+    pub src_amount: Option<U128>,
+    pub dst_recipient: AccountId,
+    pub hash_lock: Base64VecU8,
+    pub timelock: Timestamp,
+    #[serde(default)]
+    pub complete_by: Option<Timestamp>,
+    #[serde(default)]
+    pub refund_bridge: Option<AccountId>,
+    #[serde(default)]
+    pub auto_complete_secret: Option<Base64VecU8>,
+    #[serde(default)]
+    pub src_timelock: Option<Timestamp>,
+    #[serde(default)]
+    pub hash_algorithm: Option<HashAlgorithm>,
+    #[serde(default)]
+    pub memo: Option<String>,
+    #[serde(default)]
+    pub refund_to: Option<AccountId>,
+    #[serde(default)]
+    pub relayer_fee: Option<U128>,
+    #[serde(default)]
+    pub allow_recipient_override: Option<bool>,
+    #[serde(default)]
+    pub src_block_height: Option<u64>,
+    #[serde(default)]
+    pub alt_hash_locks: Option<Vec<Base64VecU8>>,
+    #[serde(default)]
+    pub auto_register_recipient: Option<bool>,
+    #[serde(default)]
+    pub push_on_complete: Option<bool>,
+    #[serde(default)]
+    pub auction: Option<AuctionConfig>,
+}
+
+#[near_bindgen]
+impl NEAREscrow {
+    /// NEP-141 receiver: lock transferred tokens into a new HTLC.
+    /// The calling token contract becomes the order's `dst_token` and
+    /// `sender_id` its resolver. Returns the unused amount to the sender.
+    pub fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let message: FtHtlcMessage = match serde_json::from_str(&msg) {
+            Ok(message) => message,
+            Err(_) => {
+                env::log_str("Malformed HTLC message, returning tokens");
+                return PromiseOrValue::Value(amount);
+            }
+        };
+
+        // A token contract named like the "NEAR" sentinel is too easily
+        // mistaken for native NEAR
+        if TokenId::is_native_name(env::predecessor_account_id().as_str()) {
+            env::log_str("Ambiguous token account, returning tokens");
+            return PromiseOrValue::Value(amount);
+        }
+
+        if !self.is_chain_supported(message.src_chain.clone()) {
+            env::log_str("Unsupported source chain, returning tokens");
+            return PromiseOrValue::Value(amount);
+        }
+
+        self.internal_create_htlc(SwapOrder {
+            order_hash: message.order_hash,
+            src_maker: message.src_maker,
+            src_chain: message.src_chain,
+            src_token: message.src_token.unwrap_or_default(),
+            src_amount: message.src_amount.unwrap_or(U128(0)),
+            dst_recipient: message.dst_recipient,
+            dst_token: env::predecessor_account_id().to_string(),
+            dst_amount: amount,
+            hash_lock: message.hash_lock,
+            hash_algorithm: message.hash_algorithm.unwrap_or_default(),
+            timelock: message.timelock,
+            complete_by: message.complete_by,
+            state: HTLCState::Active,
+            created_at: env::block_timestamp(),
+            resolver: sender_id,
+            refund_bridge: message.refund_bridge,
+            auto_complete_secret: message.auto_complete_secret,
+            src_timelock: message.src_timelock,
+            revealed_secret: None,
+            safety_deposit: U128(0),
+            public_window_start: None,
+            timelocks: None,
+            merkle_root: None,
+            parts_filled: 0,
+            filled_amount: U128(0),
+            storage_bytes: 0,
+            order_id: 0,
+            dst_asset: DstAsset::Ft,
+            memo: message.memo,
+            refund_to: message.refund_to,
+            relayer_fee: message.relayer_fee.unwrap_or(U128(0)),
+            allow_recipient_override: message.allow_recipient_override.unwrap_or(false),
+            src_block_height: message.src_block_height.unwrap_or(0),
+            completion_attempts: 0,
+            alt_hash_locks: message.alt_hash_locks.unwrap_or_default(),
+            matched_hash_lock: None,
+            auto_register_recipient: message.auto_register_recipient.unwrap_or(false),
+            push_on_complete: message.push_on_complete.unwrap_or(true),
+            auction: message.auction,
+            parts: 0,
+        });
+
+        PromiseOrValue::Value(U128(0))
+    }
+}
@@ -1,14 +1,229 @@
+// `near_bindgen` doesn't forward a method's `allow` attributes to the
+// `NEAREscrowExt` wrappers it generates, so this can't be set per method
+#![allow(clippy::too_many_arguments)]
+
+use blake2::digest::consts::U32;
+use blake2::Blake2b;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap, UnorderedMap};
+use near_sdk::collections::{LookupMap, TreeMap, UnorderedMap, UnorderedSet, Vector};
 use near_sdk::json_types::{Base64VecU8, U128};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
-    env, near_bindgen, AccountId, Balance, CryptoHash, Gas, PanicOnDefault, Promise, Timestamp,
+    env, ext_contract, near_bindgen, AccountId, Balance, BlockHeight, Gas, PanicOnDefault, Promise,
+    PromiseOrValue, PromiseResult, StorageUsage, Timestamp,
 };
 use sha2::{Digest, Sha256};
+use std::ops::Bound;
+
+mod batch;
+mod errors;
+mod events;
+mod ft_receiver;
+mod metadata;
+mod migration;
+mod nft_receiver;
+mod partial_fill;
+mod storage;
 
-/// Gas for cross-contract calls
+pub use batch::CreateHtlcArgs;
+pub use errors::ErrorCode;
+use errors::{fail, require};
+pub use events::*;
+pub use ft_receiver::FtHtlcMessage;
+pub use metadata::{ContractSourceMetadata, Standard, CONTRACT_SOURCE_LINK};
+pub use migration::{OldNEAREscrow, OldSwapOrder};
+use partial_fill::PartialFill;
+pub use storage::{StorageBalance, StorageBalanceBounds, STORAGE_REGISTRATION_BYTES};
+
+/// Default gas for token transfer calls (`ft_transfer`, `nft_transfer`)
 const GAS_FOR_FT_TRANSFER: Gas = Gas(10_000_000_000_000);
+/// Default gas for resolving a payout in `on_transfer_complete`
+const GAS_FOR_RESOLVE_TRANSFER: Gas = Gas(10_000_000_000_000);
+/// Gas for the best-effort refund notification to a bridge contract
+const GAS_FOR_REFUND_MESSAGE: Gas = Gas(10_000_000_000_000);
+
+/// Longest order memo accepted, in bytes
+const MAX_MEMO_LENGTH: usize = 256;
+
+/// Largest share of an expired order's deposit paid to a third-party
+/// refund executor, in basis points
+const MAX_CLEANUP_REWARD_BPS: u16 = 1_000;
+
+/// Most order hashes accepted by `get_swap_orders`, and most orders
+/// returned by `get_orders_by_id_range` and `get_orders_after_id`
+const MAX_ORDERS_PER_QUERY: usize = 50;
+
+/// Most accounts besides the resolver that can fund one order
+const MAX_CONTRIBUTORS: usize = 10;
+
+/// Time past an order's timelock before the owner may rescue its funds
+/// with `owner_force_refund` (30 days in nanoseconds)
+const FORCE_REFUND_DELAY: Timestamp = 2_592_000_000_000_000;
+
+/// Most hash locks an order accepts, `hash_lock` included
+const MAX_HASH_LOCKS: usize = 4;
+
+/// Number of orders `get_largest_active_orders` returns by default
+const LARGEST_ORDERS_CAPACITY: usize = 10;
+
+/// NEP-141 fungible token contract
+#[ext_contract(ext_ft)]
+pub trait FungibleToken {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+    fn storage_deposit(&mut self, account_id: Option<AccountId>, registration_only: Option<bool>);
+}
+
+/// NEP-171 non-fungible token contract
+#[ext_contract(ext_nft)]
+pub trait NonFungibleToken {
+    fn nft_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: String,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    );
+}
+
+/// Bridge contract relaying refunds back to the source chain
+#[ext_contract(ext_refund_bridge)]
+pub trait RefundBridge {
+    fn send_refund_message(&mut self, order_hash: Base64VecU8, src_chain: String);
+}
+
+/// Hash function used to derive a hash lock from its secret
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum HashAlgorithm {
+    /// SHA-256, the default for NEAR-native swaps
+    #[default]
+    Sha256,
+    /// Keccak-256, used by EVM source chains
+    Keccak256,
+    /// BLAKE2b with a 256-bit output, used by Zcash and Substrate chains
+    Blake2b256,
+    /// RIPEMD-160 of SHA-256, as in Bitcoin script's `OP_HASH160`
+    Hash160,
+}
+
+impl HashAlgorithm {
+    pub fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+            HashAlgorithm::Keccak256 => env::keccak256(data),
+            HashAlgorithm::Blake2b256 => Blake2b::<U32>::digest(data).to_vec(),
+            HashAlgorithm::Hash160 => env::ripemd160_array(&Sha256::digest(data)).to_vec(),
+        }
+    }
+
+    /// Length in bytes of the digests this algorithm produces
+    pub fn digest_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 | HashAlgorithm::Keccak256 | HashAlgorithm::Blake2b256 => 32,
+            HashAlgorithm::Hash160 => 20,
+        }
+    }
+}
+
+/// Kind of asset an order locks. For native NEAR and NEP-141 orders
+/// `dst_token` and `dst_amount` identify the funds; an NFT order locks a
+/// single token, with `dst_token` its contract and `dst_amount` 1.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum DstAsset {
+    Native,
+    Ft,
+    Nft {
+        contract: AccountId,
+        token_id: String,
+    },
+}
+
+/// Token a balance is held in, parsed from a token argument once instead
+/// of comparing names. Balances are keyed by `key`: "NEAR" for native
+/// NEAR, otherwise the token contract (an NFT order's contract included).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum TokenId {
+    Native,
+    Ft(AccountId),
+}
+
+impl TokenId {
+    /// Whether `token` names native NEAR: the "NEAR" sentinel, in any casing
+    pub fn is_native_name(token: &str) -> bool {
+        token.eq_ignore_ascii_case("NEAR")
+    }
+
+    /// Parse a token argument, the sentinel as native NEAR and anything
+    /// else as a token contract account
+    pub fn parse(token: &str) -> Self {
+        if Self::is_native_name(token) {
+            TokenId::Native
+        } else {
+            TokenId::Ft(token.parse().expect("Invalid token account"))
+        }
+    }
+
+    /// Key the token's balances are stored under
+    pub fn key(&self) -> String {
+        match self {
+            TokenId::Native => "NEAR".to_string(),
+            TokenId::Ft(contract) => contract.to_string(),
+        }
+    }
+}
+
+/// Stage offsets (nanoseconds after `created_at`) for staged settlement.
+/// Completion opens after `finality_lock`, is resolver-only until
+/// `public_withdraw`, and gives way to refunds after `cancellation`. Those
+/// are resolver-only until `public_cancellation`, if set, in place of the
+/// contract's refund grace period.
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, Copy, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Timelocks {
+    pub finality_lock: Timestamp,
+    pub exclusive_withdraw: Timestamp,
+    pub public_withdraw: Timestamp,
+    pub cancellation: Timestamp,
+    #[serde(default)]
+    pub public_cancellation: Option<Timestamp>,
+}
+
+/// Dutch auction on an order's amount: the least `dst_amount` accepted
+/// falls linearly from `start_amount` at `start_time` to `floor_amount`
+/// `duration` nanoseconds later
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, Copy, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuctionConfig {
+    pub start_amount: U128,
+    pub floor_amount: U128,
+    pub start_time: Timestamp,
+    pub duration: u64,
+}
+
+impl AuctionConfig {
+    /// Least amount accepted at `timestamp`
+    pub fn price_at(&self, timestamp: Timestamp) -> Balance {
+        let elapsed = timestamp.saturating_sub(self.start_time).min(self.duration);
+        let decay = self.start_amount.0 - self.floor_amount.0;
+        self.start_amount.0 - decay * elapsed as u128 / self.duration as u128
+    }
+}
+
+/// Stages reported by `get_current_stage`
+pub const STAGE_FINALITY_LOCK: u8 = 0;
+pub const STAGE_FINALIZED: u8 = 1;
+pub const STAGE_EXCLUSIVE_WITHDRAW: u8 = 2;
+pub const STAGE_PUBLIC_WITHDRAW: u8 = 3;
+pub const STAGE_CANCELLATION: u8 = 4;
+pub const STAGE_PUBLIC_CANCELLATION: u8 = 5;
 
 /// HTLC states
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -18,6 +233,188 @@ pub enum HTLCState {
     Completed,
     Refunded,
     Expired,
+    /// Recorded by `announce_htlc`, not yet funded
+    Announced,
+    /// Frozen by the owner with `dispute_order`
+    Disputed,
+}
+
+/// Action a keeper should take on an order
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum KeeperAction {
+    /// Timelock passed, funds can be returned to the resolver
+    Refund,
+    /// Auto-complete window is open and a secret was pre-deposited
+    AutoComplete,
+}
+
+/// Order paired with the keeper action it needs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct KeeperTask {
+    pub order_hash: Base64VecU8,
+    pub action: KeeperAction,
+    pub timelock: Timestamp,
+}
+
+/// Active order with the time left before its timelock
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ActiveOrderInfo {
+    pub order: SwapOrder,
+    /// Nanoseconds until the timelock, 0 once it has passed
+    pub remaining_ns: u64,
+}
+
+/// Page of active orders returned by `get_active_orders_detailed`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ActiveOrdersPage {
+    pub orders: Vec<ActiveOrderInfo>,
+    /// Number of active orders across all pages
+    pub total_active: u64,
+}
+
+/// Track record of a resolver returned by `get_resolver_stats`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ResolverStats {
+    pub orders_created: u64,
+    pub completed: u64,
+    pub refunded: u64,
+    /// Amount paid out by the resolver's completed orders, in raw units
+    /// summed across tokens
+    pub total_volume: U128,
+}
+
+impl Default for ResolverStats {
+    fn default() -> Self {
+        Self {
+            orders_created: 0,
+            completed: 0,
+            refunded: 0,
+            total_volume: U128(0),
+        }
+    }
+}
+
+/// Reference exchange rate of a token pair: `src_units` of the source
+/// token are worth `dst_units` of the destination token
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReferenceRate {
+    pub src_units: U128,
+    pub dst_units: U128,
+}
+
+/// Display metadata of a fungible token, for rendering raw amounts
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenInfo {
+    pub decimals: u8,
+    pub symbol: String,
+}
+
+/// Native NEAR the contract holds against what it owes, from `get_solvency`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SolvencyReport {
+    pub account_balance: U128,
+    /// NEAR deposits and safety deposits of active orders
+    pub total_locked_native: U128,
+    pub collected_fees_native: U128,
+    /// NEAR credited to recipients by pull-mode completions
+    pub claimable_native: U128,
+    /// NEAR covering the contract's storage and unused storage balances
+    pub storage_reserve: U128,
+    /// Whether the balance covers the locked NEAR, fees, claims and storage
+    /// reserve
+    pub solvent: bool,
+}
+
+/// Number of stored orders in each state, returned by `get_order_counts`.
+/// Orders past their timelock count as active until `expire_htlc` marks
+/// them, and purged or cancelled orders are no longer counted.
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, Default, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OrderCounts {
+    pub active_count: u64,
+    pub completed_count: u64,
+    pub refunded_count: u64,
+    pub expired_count: u64,
+    pub announced_count: u64,
+    pub disputed_count: u64,
+}
+
+impl OrderCounts {
+    fn count_mut(&mut self, state: &HTLCState) -> &mut u64 {
+        match state {
+            HTLCState::Active => &mut self.active_count,
+            HTLCState::Completed => &mut self.completed_count,
+            HTLCState::Refunded => &mut self.refunded_count,
+            HTLCState::Expired => &mut self.expired_count,
+            HTLCState::Announced => &mut self.announced_count,
+            HTLCState::Disputed => &mut self.disputed_count,
+        }
+    }
+
+    pub(crate) fn add(&mut self, state: &HTLCState) {
+        *self.count_mut(state) += 1;
+    }
+
+    pub(crate) fn remove(&mut self, state: &HTLCState) {
+        let count = self.count_mut(state);
+        *count = count.saturating_sub(1);
+    }
+}
+
+/// Aggregate order counters returned by `get_stats`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractStats {
+    pub total_orders_created: u64,
+    pub total_completed: u64,
+    pub total_refunded: u64,
+    pub active_orders: u64,
+}
+
+/// Owner-tunable settings returned by `get_config`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractConfig {
+    pub owner: AccountId,
+    pub min_timelock: Timestamp,
+    pub max_timelock: Timestamp,
+    pub timelock_safety_margin: Timestamp,
+    pub fee_bps: u16,
+    pub paused: bool,
+    pub min_order_amount: U128,
+    pub max_order_amount: U128,
+    pub min_deposit: U128,
+    pub min_secret_length: u32,
+    pub completion_extension_fee: U128,
+    pub max_completion_attempts: u8,
+    pub max_creates_per_block: u32,
+    pub storage_reserve: U128,
+    pub resolver_allowlist_enabled: bool,
+    pub verify_order_hashes: bool,
+    pub reject_self_dealing: bool,
+}
+
+/// Settings for a source chain
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ChainConfig {
+    /// Numeric chain id (e.g. the EIP-155 id for EVM chains)
+    pub chain_id: u64,
+    /// Confirmations an order's source transaction needs, by the reported
+    /// chain height, before the order can be completed
+    pub min_confirmations: u8,
+    /// Whether new orders from this chain are accepted
+    pub enabled: bool,
 }
 
 /// Cross-chain swap order structure
@@ -42,14 +439,98 @@ pub struct SwapOrder {
     pub dst_amount: U128,
     /// Hash lock for HTLC
     pub hash_lock: Base64VecU8,
+    /// Algorithm the hash lock was computed with
+    pub hash_algorithm: HashAlgorithm,
     /// Timelock timestamp (nanoseconds)
     pub timelock: Timestamp,
+    /// Time after which the order can no longer be completed, if earlier than
+    /// the timelock; the resolver may push it out with `purchase_completion_extension`
+    pub complete_by: Option<Timestamp>,
     /// Current state
     pub state: HTLCState,
     /// Block timestamp when created
     pub created_at: Timestamp,
     /// Resolver account who deposited funds
     pub resolver: AccountId,
+    /// Bridge contract notified on refund so the source chain can cancel too
+    pub refund_bridge: Option<AccountId>,
+    /// Secret pre-deposited by the resolver so a keeper can complete near
+    /// expiry. Left out of order views, though like all contract state it
+    /// can still be read from the chain, so only a secret the resolver is
+    /// ready to reveal should be deposited.
+    #[serde(skip)]
+    pub auto_complete_secret: Option<Base64VecU8>,
+    /// Timelock of the matching HTLC on the source chain (nanoseconds)
+    pub src_timelock: Option<Timestamp>,
+    /// Secret revealed on completion, kept so relayers can claim on the source chain
+    pub revealed_secret: Option<Base64VecU8>,
+    /// NEAR posted by the resolver on top of the principal, paid to whoever
+    /// executes the completion
+    pub safety_deposit: U128,
+    /// When anyone (not just the resolver) may complete the order
+    pub public_window_start: Option<Timestamp>,
+    /// Stage offsets for staged settlement, if the order uses them
+    pub timelocks: Option<Timelocks>,
+    /// Root of the Merkle tree of per-part secret hashes, for orders filled
+    /// in parts through `complete_htlc_partial`
+    pub merkle_root: Option<Base64VecU8>,
+    /// Number of parts filled so far
+    pub parts_filled: u32,
+    /// Cumulative amount released by partial fills
+    pub filled_amount: U128,
+    /// Storage bytes charged to the resolver and not yet credited back
+    pub storage_bytes: u64,
+    /// Sequential id assigned on creation, for cursor-based syncing
+    pub order_id: u64,
+    /// Asset locked by the order
+    pub dst_asset: DstAsset,
+    /// Free-form reference set by the creator (e.g. the source-chain tx hash)
+    pub memo: Option<String>,
+    /// Account refunds go to instead of the resolver
+    pub refund_to: Option<AccountId>,
+    /// Taken from the payout for whoever submits `complete_htlc` on the
+    /// recipient's behalf
+    pub relayer_fee: U128,
+    /// Whether the resolver may pay the order out to another account with
+    /// `complete_htlc_to`
+    pub allow_recipient_override: bool,
+    /// Source-chain block holding the maker's lock transaction
+    pub src_block_height: u64,
+    /// Completion payouts that failed and were rolled back
+    pub completion_attempts: u8,
+    /// Further hash locks the secret may match instead of `hash_lock`
+    pub alt_hash_locks: Vec<Base64VecU8>,
+    /// Which hash lock the revealed secret matched: 0 for `hash_lock`,
+    /// `i + 1` for `alt_hash_locks[i]`
+    pub matched_hash_lock: Option<u8>,
+    /// Register the recipient with the token contract before paying out a
+    /// token order, see `storage_subsidy`
+    pub auto_register_recipient: bool,
+    /// Pay the recipient on completion; otherwise credit its `claimable`
+    /// balance for it to `claim`
+    pub push_on_complete: bool,
+    /// Dutch auction the amount must keep up with, if any
+    pub auction: Option<AuctionConfig>,
+    /// Number of equal slices a Merkle order is filled in, one per leaf; zero
+    /// for other orders
+    pub parts: u32,
+}
+
+impl SwapOrder {
+    /// Token the order's deposit is held in, by its asset kind
+    fn token_id(&self) -> TokenId {
+        match self.dst_asset {
+            DstAsset::Native => TokenId::Native,
+            DstAsset::Ft | DstAsset::Nft { .. } => {
+                TokenId::Ft(self.dst_token.parse().expect("Invalid token account"))
+            }
+        }
+    }
+
+    /// Every hash lock the order opens with, `hash_lock` first
+    fn hash_locks(&self) -> impl Iterator<Item = &Base64VecU8> {
+        std::iter::once(&self.hash_lock).chain(&self.alt_hash_locks)
+    }
 }
 
 /// NEAR Escrow Contract for Cross-Chain Swaps
@@ -58,16 +539,156 @@ pub struct SwapOrder {
 pub struct NEAREscrow {
     /// Contract owner
     pub owner: AccountId,
+    /// Account proposed as the next owner, pending its acceptance
+    pub pending_owner: Option<AccountId>,
     /// Active swap orders indexed by order hash
     pub swap_orders: UnorderedMap<Base64VecU8, SwapOrder>,
     /// Deposits for each order (order_hash -> amount)
     pub deposits: LookupMap<Base64VecU8, Balance>,
-    /// Supported source chains
-    pub supported_chains: LookupMap<String, bool>,
+    /// NEAR a resolver pays into the protocol fees to push out an order's
+    /// `complete_by`
+    pub completion_extension_fee: Balance,
+    /// Source chain settings; orders are only accepted from enabled chains
+    pub supported_chains: UnorderedMap<String, ChainConfig>,
     /// Minimum timelock duration (nanoseconds)
     pub min_timelock: Timestamp,
     /// Maximum timelock duration (nanoseconds)  
     pub max_timelock: Timestamp,
+    /// Hashes of orders currently in the `Active` state, so listings scale with
+    /// the page size rather than total history. Added after the initial
+    /// release: existing deployments must backfill it from `swap_orders` when
+    /// migrating state.
+    pub active_order_hashes: UnorderedSet<Base64VecU8>,
+    /// Active order hashes keyed by (deposit, order id), so the largest can
+    /// be listed without scanning every active order
+    pub active_orders_by_amount: TreeMap<(Balance, u64), Base64VecU8>,
+    /// Key of each active order in `active_orders_by_amount`
+    pub active_order_amounts: LookupMap<Base64VecU8, (Balance, u64)>,
+    /// Window before the timelock in which keepers may auto-complete (nanoseconds)
+    pub auto_complete_window: Timestamp,
+    /// Keeper reward for auto-completion, in basis points of the order amount
+    pub auto_complete_reward_bps: u16,
+    /// Minimum gap between the NEAR timelock and the source timelock (nanoseconds)
+    pub timelock_safety_margin: Timestamp,
+    /// Time after the timelock during which only the resolver may refund (nanoseconds)
+    pub refund_grace_ns: Timestamp,
+    /// Reward for refunding an order after its grace period on the resolver's
+    /// behalf, in basis points of the order amount
+    pub cleanup_reward_bps: u16,
+    /// Circuit breaker blocking new orders; settlement stays available
+    pub paused: bool,
+    /// Protocol fee taken from completed payouts, in basis points
+    pub fee_bps: u16,
+    /// Protocol fees collected per token ("NEAR" or token contract), withdrawable by the owner
+    pub collected_fees: LookupMap<String, Balance>,
+    /// Native NEAR held for active orders (principal and safety deposits),
+    /// which `emergency_withdraw` can't touch
+    pub total_locked: Balance,
+    /// Amount held for active orders per token ("NEAR" or token contract)
+    pub locked_by_token: LookupMap<String, Balance>,
+    /// NEP-145 storage balances prepaid by resolvers
+    pub storage_balances: LookupMap<AccountId, StorageBalance>,
+    /// Sum of unused storage balances, also kept out of `emergency_withdraw`
+    pub total_storage_available: Balance,
+    /// Order hashes per source-chain maker, in creation order
+    pub maker_orders: LookupMap<String, Vector<Base64VecU8>>,
+    /// Order hashes per NEAR recipient, in creation order
+    pub recipient_orders: LookupMap<AccountId, Vector<Base64VecU8>>,
+    /// Order hashes per source chain, in creation order
+    pub chain_orders: LookupMap<String, Vector<Base64VecU8>>,
+    /// Number of orders ever created
+    pub total_orders_created: u64,
+    /// Number of orders completed (a failed payout undoes its count)
+    pub total_completed: u64,
+    /// Number of orders refunded
+    pub total_refunded: u64,
+    /// Amount paid out by completions per destination token
+    pub total_volume: LookupMap<String, Balance>,
+    /// Smallest NEAR deposit accepted for an order
+    pub min_order_amount: Balance,
+    /// Largest NEAR deposit accepted for an order
+    pub max_order_amount: Balance,
+    /// Whether only allowlisted resolvers may create orders
+    pub resolver_allowlist_enabled: bool,
+    /// Resolvers allowed to create orders while the allowlist is enabled
+    pub resolvers: LookupMap<AccountId, bool>,
+    /// Per-chain (min, max) timelock durations overriding the global limits
+    pub chain_timelocks: LookupMap<String, (Timestamp, Timestamp)>,
+    /// Whether new orders must use the hash given by `compute_order_hash`
+    pub verify_order_hashes: bool,
+    /// Whether orders paying out to their own resolver are rejected
+    pub reject_self_dealing: bool,
+    /// Id the next created order gets
+    pub next_order_id: u64,
+    /// Order hashes by order id, ordered so id queries skip purged ids
+    pub order_ids: TreeMap<u64, Base64VecU8>,
+    /// Number of active orders per hash lock. New orders need an unused
+    /// hash lock, so revealing one order's secret can't unlock another
+    /// active order; orders migrated from before this check may share one.
+    pub active_hashlocks: LookupMap<Base64VecU8, u32>,
+    /// Largest deviation of an order's rate from its pair's reference rate,
+    /// in basis points
+    pub max_rate_deviation_bps: u16,
+    /// Reference rates per (src_token, dst_token); pairs without one aren't checked
+    pub reference_rates: LookupMap<(String, String), ReferenceRate>,
+    /// Length of the windows `volume_cap` applies to (nanoseconds)
+    pub volume_window_ns: Timestamp,
+    /// Most native NEAR that new orders may lock within one window
+    pub volume_cap: Balance,
+    /// Start of the current volume window
+    pub window_start: Timestamp,
+    /// Native NEAR locked by orders created in the current window
+    pub window_volume: Balance,
+    /// Accounts refused as resolvers of new orders or recipients of payouts
+    pub blacklist: LookupMap<AccountId, bool>,
+    /// Gas for token transfer calls (`ft_transfer`, `nft_transfer`)
+    pub gas_for_ft_transfer: Gas,
+    /// Gas for the callbacks resolving payouts
+    pub gas_for_resolve: Gas,
+    /// Order counts and volume per resolver
+    pub resolver_stats: LookupMap<AccountId, ResolverStats>,
+    /// Every order hash ever created, kept after orders are settled or
+    /// purged so a hash can never be reused
+    pub used_order_hashes: UnorderedSet<Base64VecU8>,
+    /// Latest reported block height per source chain
+    pub chain_heights: LookupMap<String, u64>,
+    /// Account allowed to report chain heights besides the owner
+    pub height_oracle: Option<AccountId>,
+    /// Smallest NEAR attached per native order, safety deposit included,
+    /// whatever the order amount limits. Token orders attach no NEAR.
+    pub min_deposit: Balance,
+    /// Block height of the latest emitted event
+    pub last_event_block: BlockHeight,
+    /// Sequence number of the latest emitted event, counting from 1
+    pub last_event_index: u64,
+    /// NEAR added to orders by accounts other than their resolver, refunded
+    /// to each contributor. The rest of `deposits` belongs to the resolver.
+    pub contributions: LookupMap<Base64VecU8, Vec<(AccountId, Balance)>>,
+    /// Shortest secret accepted at completion, in bytes
+    pub min_secret_length: u32,
+    /// Display metadata per token contract, registered by the owner
+    pub token_registry: LookupMap<String, TokenInfo>,
+    /// Failed completion payouts after which the resolver may refund an
+    /// order before its timelock
+    pub max_completion_attempts: u8,
+    /// Most orders one resolver may create within a single block
+    pub max_creates_per_block: u32,
+    /// Per resolver, the block of its latest order and how many orders it
+    /// created in that block
+    pub resolver_creates: LookupMap<AccountId, (BlockHeight, u32)>,
+    /// NEAR attached to the token contract's `storage_deposit` when
+    /// registering a recipient, taken from the resolver's storage balance
+    pub storage_subsidy: Balance,
+    /// Stored orders per state
+    pub order_counts: OrderCounts,
+    /// Payouts of pull-mode completions awaiting `claim`, per recipient
+    /// and token ("NEAR" or token contract)
+    pub claimable: LookupMap<(AccountId, String), Balance>,
+    /// Native NEAR owed through `claimable`, kept out of `emergency_withdraw`
+    pub total_claimable: Balance,
+    /// NEAR kept on the account beyond its storage staking, which no owner
+    /// withdrawal or rescue may spend
+    pub storage_reserve: Balance,
 }
 
 #[near_bindgen]
@@ -77,11 +698,72 @@ impl NEAREscrow {
     pub fn new(owner: AccountId) -> Self {
         Self {
             owner,
+            pending_owner: None,
             swap_orders: UnorderedMap::new(b"s".to_vec()),
             deposits: LookupMap::new(b"d".to_vec()),
-            supported_chains: LookupMap::new(b"c".to_vec()),
+            completion_extension_fee: 0,
+            supported_chains: UnorderedMap::new(b"h".to_vec()),
             min_timelock: 3_600_000_000_000, // 1 hour in nanoseconds
             max_timelock: 86_400_000_000_000, // 24 hours in nanoseconds
+            active_order_hashes: UnorderedSet::new(b"a".to_vec()),
+            active_orders_by_amount: TreeMap::new(b"j".to_vec()),
+            active_order_amounts: LookupMap::new(b"J".to_vec()),
+            auto_complete_window: 600_000_000_000, // 10 minutes in nanoseconds
+            auto_complete_reward_bps: 10,          // 0.1%
+            timelock_safety_margin: 3_600_000_000_000, // 1 hour in nanoseconds
+            refund_grace_ns: 0,
+            cleanup_reward_bps: 0,
+            paused: false,
+            fee_bps: 0,
+            collected_fees: LookupMap::new(b"f".to_vec()),
+            total_locked: 0,
+            locked_by_token: LookupMap::new(b"g".to_vec()),
+            storage_balances: LookupMap::new(b"b".to_vec()),
+            total_storage_available: 0,
+            maker_orders: LookupMap::new(b"m".to_vec()),
+            recipient_orders: LookupMap::new(b"r".to_vec()),
+            chain_orders: LookupMap::new(b"q".to_vec()),
+            total_orders_created: 0,
+            total_completed: 0,
+            total_refunded: 0,
+            total_volume: LookupMap::new(b"v".to_vec()),
+            min_order_amount: 10_000_000_000_000_000_000_000, // 0.01 NEAR
+            max_order_amount: Balance::MAX,
+            resolver_allowlist_enabled: false,
+            resolvers: LookupMap::new(b"l".to_vec()),
+            chain_timelocks: LookupMap::new(b"t".to_vec()),
+            verify_order_hashes: false,
+            reject_self_dealing: false,
+            next_order_id: 0,
+            order_ids: TreeMap::new(b"i".to_vec()),
+            active_hashlocks: LookupMap::new(b"k".to_vec()),
+            max_rate_deviation_bps: 500, // 5%
+            reference_rates: LookupMap::new(b"x".to_vec()),
+            volume_window_ns: 86_400_000_000_000, // 24 hours in nanoseconds
+            volume_cap: Balance::MAX,
+            window_start: 0,
+            window_volume: 0,
+            blacklist: LookupMap::new(b"n".to_vec()),
+            gas_for_ft_transfer: GAS_FOR_FT_TRANSFER,
+            gas_for_resolve: GAS_FOR_RESOLVE_TRANSFER,
+            resolver_stats: LookupMap::new(b"p".to_vec()),
+            used_order_hashes: UnorderedSet::new(b"u".to_vec()),
+            chain_heights: LookupMap::new(b"e".to_vec()),
+            height_oracle: None,
+            min_deposit: 10_000_000_000_000_000_000_000, // 0.01 NEAR, ~1 kB of storage
+            last_event_block: 0,
+            last_event_index: 0,
+            contributions: LookupMap::new(b"w".to_vec()),
+            min_secret_length: 32,
+            token_registry: LookupMap::new(b"o".to_vec()),
+            max_completion_attempts: 3,
+            max_creates_per_block: u32::MAX,
+            resolver_creates: LookupMap::new(b"y".to_vec()),
+            storage_subsidy: 1_250_000_000_000_000_000_000, // 0.00125 NEAR
+            order_counts: OrderCounts::default(),
+            claimable: LookupMap::new(b"z".to_vec()),
+            total_claimable: 0,
+            storage_reserve: 0,
         }
     }
 
@@ -90,272 +772,8185 @@ impl NEAREscrow {
     pub fn new_with_chains(owner: AccountId, supported_chains: Vec<String>) -> Self {
         let mut contract = Self::new(owner);
         for chain in supported_chains {
-            contract.supported_chains.insert(&chain, &true);
+            contract.supported_chains.insert(
+                &chain,
+                &ChainConfig {
+                    chain_id: 0,
+                    min_confirmations: 0,
+                    enabled: true,
+                },
+            );
         }
         contract
     }
 
-    /// Create a new HTLC for incoming swap from source chain
+    /// Create a new HTLC for incoming swap from source chain, locking the
+    /// attached NEAR, which must equal `order.amount`. With staged
+    /// `timelocks` the order's timelock is its cancellation stage and its
+    /// public window the public withdrawal stage, so the `timelock` field is
+    /// ignored.
     #[payable]
-    pub fn create_htlc(
-        &mut self,
-        order_hash: Base64VecU8,
-        src_maker: String,
-        src_chain: String,
-        src_token: String,
-        src_amount: U128,
-        dst_recipient: AccountId,
-        dst_token: String,
-        hash_lock: Base64VecU8,
-        timelock: Timestamp,
-    ) {
-        // Validate parameters
-        assert!(
-            self.supported_chains.get(&src_chain).unwrap_or(false),
-            "Unsupported source chain"
+    pub fn create_htlc(&mut self, order: CreateHtlcArgs) {
+        assert_eq!(
+            order.amount.0,
+            env::attached_deposit(),
+            "Amount must equal attached deposit"
         );
-        assert!(
-            timelock > env::block_timestamp() + self.min_timelock,
-            "Timelock too short"
+        let swap_order = self.native_order(order);
+        self.internal_create_htlc(swap_order);
+    }
+
+    /// Record a native order without its deposit, to be funded later with
+    /// `fund_htlc`. `amount` is the NEAR the funding must attach, safety
+    /// deposit included. The order is validated now and can't be completed
+    /// or refunded until funded.
+    pub fn announce_htlc(&mut self, order: CreateHtlcArgs) {
+        let mut swap_order = self.native_order(order);
+        swap_order.state = HTLCState::Announced;
+        self.internal_create_htlc(swap_order);
+    }
+
+    /// Fund an announced order with its announced amount, making it
+    /// `Active` (resolver only)
+    #[payable]
+    pub fn fund_htlc(&mut self, order_hash: Base64VecU8) {
+        let swap_order = self
+            .swap_orders
+            .get(&order_hash)
+            .unwrap_or_else(|| fail(ErrorCode::OrderNotFound, "Order not found"));
+
+        require(
+            env::predecessor_account_id() == swap_order.resolver,
+            ErrorCode::Unauthorized,
+            "Only resolver can fund",
         );
-        assert!(
-            timelock < env::block_timestamp() + self.max_timelock,
-            "Timelock too long"
+        require(
+            swap_order.state == HTLCState::Announced,
+            ErrorCode::OrderNotActive,
+            "Order not announced",
         );
-        assert!(
-            !self.swap_orders.get(&order_hash).is_some(),
-            "Order already exists"
+        require(!self.paused, ErrorCode::Paused, "Contract is paused");
+        require(
+            env::block_timestamp() <= swap_order.timelock,
+            ErrorCode::OrderExpired,
+            "HTLC expired",
+        );
+        require(
+            env::attached_deposit() == swap_order.dst_amount.0 + swap_order.safety_deposit.0,
+            ErrorCode::InvalidAmount,
+            "Deposit must match announced amount",
+        );
+        require(
+            swap_order
+                .hash_locks()
+                .all(|hash_lock| !self.active_hashlocks.contains_key(hash_lock)),
+            ErrorCode::InvalidHashLock,
+            "Hash lock already in use",
         );
-        assert!(hash_lock.0.len() == 32, "Invalid hash lock length");
 
-        let deposit_amount = env::attached_deposit();
-        assert!(deposit_amount > 0, "Must attach deposit");
+        self.fund_order(swap_order);
+    }
 
-        // Create swap order
-        let swap_order = SwapOrder {
-            order_hash: order_hash.clone(),
-            src_maker,
-            src_chain,
-            src_token,
-            src_amount,
-            dst_recipient,
-            dst_token,
-            dst_amount: U128(deposit_amount),
-            hash_lock,
-            timelock,
-            state: HTLCState::Active,
-            created_at: env::block_timestamp(),
-            resolver: env::predecessor_account_id(),
-        };
+    /// Withdraw an announced order before it is funded (resolver only),
+    /// crediting back its storage and dropping it from the lookup indexes.
+    /// Its hash stays used.
+    pub fn cancel_announced(&mut self, order_hash: Base64VecU8) {
+        let swap_order = self
+            .swap_orders
+            .get(&order_hash)
+            .expect("Order not found");
+
+        assert_eq!(
+            env::predecessor_account_id(),
+            swap_order.resolver,
+            "Only resolver can cancel"
+        );
+        assert_eq!(
+            swap_order.state,
+            HTLCState::Announced,
+            "Order not announced"
+        );
+
+        self.swap_orders.remove(&order_hash);
+        self.order_counts.remove(&swap_order.state);
+        self.order_ids.remove(&swap_order.order_id);
+        self.unindex_order(&swap_order);
+        self.release_storage(&swap_order.resolver, swap_order.storage_bytes);
+
+        self.emit_event(HtlcEvent::HtlcCancelled(vec![HtlcCancelledData {
+            order_hash,
+        }]));
+    }
+
+    /// Add the attached NEAR to an active native order's deposit (resolver only)
+    #[payable]
+    pub fn top_up_htlc(&mut self, order_hash: Base64VecU8) {
+        let mut swap_order = self
+            .swap_orders
+            .get(&order_hash)
+            .expect("Order not found");
+
+        assert_eq!(
+            env::predecessor_account_id(),
+            swap_order.resolver,
+            "Only resolver can top up"
+        );
+        assert_eq!(swap_order.state, HTLCState::Active, "Order not active");
+        assert_eq!(
+            swap_order.dst_asset,
+            DstAsset::Native,
+            "Only native NEAR orders can be topped up"
+        );
+        let amount = env::attached_deposit();
+        assert!(amount > 0, "Must attach deposit");
 
-        // Store order and deposit
+        let deposit = self.deposits.get(&order_hash).expect("Deposit not found") + amount;
+        self.assert_order_amount(deposit);
+        swap_order.dst_amount = U128(swap_order.dst_amount.0 + amount);
         self.swap_orders.insert(&order_hash, &swap_order);
-        self.deposits.insert(&order_hash, &deposit_amount);
+        self.deposits.insert(&order_hash, &deposit);
+        self.lock(&swap_order.token_id(), amount);
+        self.track_largest_order(&order_hash, deposit);
 
-        env::log_str(&format!(
-            "HTLC created: order_hash={:?}, amount={}, timelock={}",
-            order_hash, deposit_amount, timelock
-        ));
+        self.emit_event(HtlcEvent::HtlcToppedUp(vec![HtlcToppedUpData {
+            order_hash,
+            amount: U128(amount),
+            dst_amount: swap_order.dst_amount,
+        }]));
     }
 
-    /// Complete the HTLC by revealing the secret
-    pub fn complete_htlc(&mut self, order_hash: Base64VecU8, secret: Base64VecU8) {
+    /// Add the attached NEAR to an unexpired native order on behalf of the
+    /// caller, who gets exactly that amount back if the order is refunded.
+    /// The recipient is paid the total on completion.
+    #[payable]
+    pub fn contribute_to_htlc(&mut self, order_hash: Base64VecU8) {
         let mut swap_order = self
             .swap_orders
             .get(&order_hash)
             .expect("Order not found");
 
+        let contributor = env::predecessor_account_id();
+        assert_ne!(
+            contributor, swap_order.resolver,
+            "Resolver should use top_up_htlc"
+        );
         assert_eq!(swap_order.state, HTLCState::Active, "Order not active");
         assert!(
             env::block_timestamp() <= swap_order.timelock,
             "HTLC expired"
         );
-
-        // Verify secret matches hash lock
-        let secret_hash = Sha256::digest(&secret.0);
         assert_eq!(
-            secret_hash.as_slice(),
-            swap_order.hash_lock.0.as_slice(),
-            "Invalid secret"
+            swap_order.dst_asset,
+            DstAsset::Native,
+            "Only native NEAR orders take contributions"
         );
+        assert!(
+            swap_order.merkle_root.is_none(),
+            "Partially fillable orders don't take contributions"
+        );
+        let amount = env::attached_deposit();
+        assert!(amount >= self.min_deposit, "Deposit below minimum");
 
-        // Update state
-        swap_order.state = HTLCState::Completed;
-        self.swap_orders.insert(&order_hash, &swap_order);
-
-        // Get deposit amount
-        let amount = self.deposits.get(&order_hash).expect("Deposit not found");
-        self.deposits.remove(&order_hash);
-
-        // Transfer to recipient
-        if swap_order.dst_token == "NEAR" {
-            // Native NEAR transfer
-            Promise::new(swap_order.dst_recipient.clone()).transfer(amount);
-        } else {
-            // FT transfer (would need to call FT contract)
-            // For now, just transfer NEAR - in production would call FT contract
-            Promise::new(swap_order.dst_recipient.clone()).transfer(amount);
+        let mut contributions = self.contributions.get(&order_hash).unwrap_or_default();
+        match contributions
+            .iter_mut()
+            .find(|(account_id, _)| *account_id == contributor)
+        {
+            Some((_, contributed)) => *contributed += amount,
+            None => {
+                assert!(
+                    contributions.len() < MAX_CONTRIBUTORS,
+                    "Too many contributors"
+                );
+                contributions.push((contributor.clone(), amount));
+            }
         }
 
-        env::log_str(&format!(
-            "HTLC completed: order_hash={:?}, secret={:?}, amount={}",
-            order_hash, secret, amount
-        ));
+        let deposit = self.deposits.get(&order_hash).expect("Deposit not found") + amount;
+        self.assert_order_amount(deposit);
+        swap_order.dst_amount = U128(swap_order.dst_amount.0 + amount);
+        self.swap_orders.insert(&order_hash, &swap_order);
+        self.deposits.insert(&order_hash, &deposit);
+        self.contributions.insert(&order_hash, &contributions);
+        self.lock(&swap_order.token_id(), amount);
+        self.track_largest_order(&order_hash, deposit);
+
+        self.emit_event(HtlcEvent::HtlcContributed(vec![HtlcContributedData {
+            order_hash,
+            contributor,
+            amount: U128(amount),
+        }]));
     }
 
-    /// Refund the HTLC after timelock expires
-    pub fn refund_htlc(&mut self, order_hash: Base64VecU8) {
+    /// Push out an active order's timelock (resolver only). The new timelock
+    /// must stay within the maximum duration from creation and, for orders
+    /// with a source timelock, the safety margin before it. Orders with
+    /// staged timelocks can't be extended.
+    pub fn extend_timelock(&mut self, order_hash: Base64VecU8, new_timelock: Timestamp) {
         let mut swap_order = self
             .swap_orders
             .get(&order_hash)
             .expect("Order not found");
 
+        assert_eq!(
+            env::predecessor_account_id(),
+            swap_order.resolver,
+            "Only resolver can extend timelock"
+        );
         assert_eq!(swap_order.state, HTLCState::Active, "Order not active");
         assert!(
-            env::block_timestamp() > swap_order.timelock,
-            "HTLC not expired"
+            env::block_timestamp() <= swap_order.timelock,
+            "HTLC expired"
+        );
+        assert!(
+            swap_order.timelocks.is_none(),
+            "Staged timelocks can't be extended"
+        );
+        assert!(
+            new_timelock > swap_order.timelock,
+            "New timelock must be later"
+        );
+        let (_, max_timelock) = self.get_chain_timelock_limits(swap_order.src_chain.clone());
+        assert!(
+            new_timelock < swap_order.created_at + max_timelock,
+            "Timelock too long"
         );
+        if let Some(src_timelock) = swap_order.src_timelock {
+            assert!(
+                new_timelock < src_timelock.saturating_sub(self.timelock_safety_margin),
+                "Timelock too close to source timelock"
+            );
+        }
 
-        // Update state
-        swap_order.state = HTLCState::Refunded;
+        let old_timelock = swap_order.timelock;
+        swap_order.timelock = new_timelock;
         self.swap_orders.insert(&order_hash, &swap_order);
 
-        // Get deposit amount
-        let amount = self.deposits.get(&order_hash).expect("Deposit not found");
-        self.deposits.remove(&order_hash);
+        self.emit_event(HtlcEvent::HtlcTimelockExtended(vec![
+            HtlcTimelockExtendedData {
+                order_hash,
+                old_timelock,
+                new_timelock,
+            },
+        ]));
+    }
 
-        // Refund to resolver
-        Promise::new(swap_order.resolver.clone()).transfer(amount);
+    /// Hand an active order over to another resolver (resolver only), who
+    /// then receives its refund and may top it up or extend it. The order's
+    /// storage charge moves to the new resolver.
+    pub fn reassign_resolver(&mut self, order_hash: Base64VecU8, new_resolver: AccountId) {
+        let mut swap_order = self
+            .swap_orders
+            .get(&order_hash)
+            .expect("Order not found");
 
-        env::log_str(&format!(
-            "HTLC refunded: order_hash={:?}, amount={}",
-            order_hash, amount
-        ));
-    }
+        assert_eq!(
+            env::predecessor_account_id(),
+            swap_order.resolver,
+            "Only resolver can reassign"
+        );
+        assert_eq!(swap_order.state, HTLCState::Active, "Order not active");
+        assert!(
+            self.is_resolver_allowed(new_resolver.clone()),
+            "Resolver not allowlisted"
+        );
+        assert!(
+            !self.blacklist.contains_key(&new_resolver),
+            "Resolver is blacklisted"
+        );
 
-    /// Get swap order details
-    pub fn get_swap_order(&self, order_hash: Base64VecU8) -> Option<SwapOrder> {
-        self.swap_orders.get(&order_hash)
+        let old_resolver = swap_order.resolver.clone();
+        self.transfer_storage_charge(&old_resolver, &new_resolver, swap_order.storage_bytes);
+        swap_order.resolver = new_resolver.clone();
+        self.swap_orders.insert(&order_hash, &swap_order);
+
+        self.emit_event(HtlcEvent::HtlcResolverReassigned(vec![
+            HtlcResolverReassignedData {
+                order_hash,
+                old_resolver,
+                new_resolver,
+            },
+        ]));
     }
 
-    /// Check if HTLC is active
-    pub fn is_htlc_active(&self, order_hash: Base64VecU8) -> bool {
+    /// Complete the HTLC by revealing the secret. The order is restored to
+    /// `Active` by `on_transfer_complete` if the payout fails.
+    /// Orders with a public window can only be completed by their resolver
+    /// until the window opens. A caller other than the recipient earns the
+    /// order's relayer fee out of the payout. Orders created without
+    /// `push_on_complete` credit the recipient's `claimable` balance instead
+    /// of paying it.
+    ///
+    /// A recipient can guard its own completion with `min_payout`, the least
+    /// it accepts net of fees, and a `deadline` after which it fails.
+    pub fn complete_htlc(
+        &mut self,
+        order_hash: Base64VecU8,
+        secret: Base64VecU8,
+        min_payout: Option<U128>,
+        deadline: Option<Timestamp>,
+    ) -> PromiseOrValue<bool> {
         if let Some(order) = self.swap_orders.get(&order_hash) {
-            order.state == HTLCState::Active && env::block_timestamp() <= order.timelock
-        } else {
-            false
+            Self::assert_exclusive_access(&order);
+        }
+        if let Some(deadline) = deadline {
+            require(
+                env::block_timestamp() <= deadline,
+                ErrorCode::DeadlinePassed,
+                "Completion deadline passed",
+            );
         }
-    }
 
-    /// Get all active orders (for monitoring)
-    pub fn get_active_orders(&self, from_index: Option<u32>, limit: Option<u32>) -> Vec<SwapOrder> {
-        let start = from_index.unwrap_or(0) as usize;
-        let limit = limit.unwrap_or(10) as usize;
+        self.internal_complete_and_pay(order_hash, secret, None, min_payout, 0, None)
+    }
 
+    /// Complete the HTLC like `complete_htlc` but pay `recipient` instead of
+    /// `dst_recipient` (resolver only), for orders created with
+    /// `allow_recipient_override`. No relayer fee is taken.
+    pub fn complete_htlc_to(
+        &mut self,
+        order_hash: Base64VecU8,
+        secret: Base64VecU8,
+        recipient: AccountId,
+    ) -> PromiseOrValue<bool> {
+        let swap_order = self
+            .swap_orders
+            .get(&order_hash)
+            .expect("Order not found");
+
+        assert_eq!(
+            env::predecessor_account_id(),
+            swap_order.resolver,
+            "Only resolver can override the recipient"
+        );
+        assert!(
+            swap_order.allow_recipient_override,
+            "Recipient override not allowed"
+        );
+        assert!(
+            !self.blacklist.contains_key(&recipient),
+            "Recipient is blacklisted"
+        );
+
+        self.internal_complete_and_pay(order_hash, secret, Some(recipient), None, 0, None)
+    }
+
+    /// Complete the HTLC on behalf of the recipient once its public window
+    /// has opened, earning the resolver's safety deposit
+    pub fn public_withdraw_htlc(
+        &mut self,
+        order_hash: Base64VecU8,
+        secret: Base64VecU8,
+    ) -> PromiseOrValue<bool> {
+        let swap_order = self
+            .swap_orders
+            .get(&order_hash)
+            .expect("Order not found");
+        let public_window_start = swap_order
+            .public_window_start
+            .expect("Order has no public window");
+        assert!(
+            env::block_timestamp() >= public_window_start,
+            "Public window not open"
+        );
+
+        self.internal_complete_and_pay(order_hash, secret, None, None, 0, None)
+    }
+
+    /// Push out an active order's `complete_by` (resolver only), attaching
+    /// `completion_extension_fee`, which is collected as a protocol fee. The
+    /// new deadline can't pass the timelock, so the refund path is unchanged.
+    #[payable]
+    pub fn purchase_completion_extension(
+        &mut self,
+        order_hash: Base64VecU8,
+        new_complete_by: Timestamp,
+    ) {
+        let mut swap_order = self
+            .swap_orders
+            .get(&order_hash)
+            .expect("Order not found");
+
+        assert_eq!(swap_order.state, HTLCState::Active, "Order not active");
+        assert_eq!(
+            env::predecessor_account_id(),
+            swap_order.resolver,
+            "Only resolver can extend the completion deadline"
+        );
+        let old_complete_by = swap_order
+            .complete_by
+            .expect("Order has no completion deadline");
+        assert!(
+            new_complete_by > old_complete_by,
+            "New completion deadline must be later"
+        );
+        assert!(
+            new_complete_by <= swap_order.timelock,
+            "Completion deadline past timelock"
+        );
+        assert_eq!(
+            env::attached_deposit(),
+            self.completion_extension_fee,
+            "Attach exactly the extension fee"
+        );
+
+        let fee = self.completion_extension_fee;
+        if fee > 0 {
+            let token = TokenId::Native.key();
+            let collected = self.collected_fees.get(&token).unwrap_or(0);
+            self.collected_fees.insert(&token, &(collected + fee));
+        }
+        swap_order.complete_by = Some(new_complete_by);
+        self.swap_orders.insert(&order_hash, &swap_order);
+
+        self.emit_event(HtlcEvent::HtlcCompletionExtended(vec![
+            HtlcCompletionExtendedData {
+                order_hash,
+                old_complete_by,
+                new_complete_by,
+                fee: U128(fee),
+            },
+        ]));
+    }
+
+    /// Complete the HTLC near its completion deadline using the secret
+    /// pre-deposited by the resolver. Callable by any keeper, who earns
+    /// `auto_complete_reward_bps` of the amount, on top of any relayer fee,
+    /// once the payout succeeds. Otherwise settles like `complete_htlc`,
+    /// pull mode and the resolver's exclusive window included.
+    pub fn auto_complete(&mut self, order_hash: Base64VecU8) -> PromiseOrValue<bool> {
+        let swap_order = self
+            .swap_orders
+            .get(&order_hash)
+            .expect("Order not found");
+        let secret = swap_order
+            .auto_complete_secret
+            .clone()
+            .expect("No auto-complete secret");
+        assert!(
+            env::block_timestamp() + self.auto_complete_window
+                >= Self::completion_deadline(&swap_order),
+            "Auto-complete window not open"
+        );
+        Self::assert_exclusive_access(&swap_order);
+
+        let reward_bps = self.auto_complete_reward_bps;
+        self.internal_complete_and_pay(order_hash, secret, None, None, reward_bps, None)
+    }
+
+    /// Callback for `complete_htlc` and `auto_complete`: pay the executor
+    /// its fee (relayer fee plus any keeper reward) and the safety deposit,
+    /// or restore the order and its deposit if the payout failed so it can
+    /// be retried or refunded. A failed payout also takes back the protocol
+    /// `fee` accrued for it.
+    #[private]
+    pub fn on_transfer_complete(
+        &mut self,
+        order_hash: Base64VecU8,
+        amount: U128,
+        executor: AccountId,
+        executor_fee: U128,
+        fee: U128,
+    ) -> bool {
+        let succeeded = Self::transfer_succeeded();
+        if !succeeded {
+            self.restore_failed_completion(&order_hash, amount.0, fee.0);
+            return false;
+        }
+
+        if executor_fee.0 > 0 {
+            let swap_order = self
+                .swap_orders
+                .get(&order_hash)
+                .expect("Order not found");
+            self.pay_executor_fee(&swap_order, executor.clone(), executor_fee.0);
+        }
+        self.pay_safety_deposit(&order_hash, executor);
+        true
+    }
+
+    /// Pull `token` credited to the caller by pull-mode completions.
+    /// `on_claim_complete` restores the balance if the transfer fails.
+    pub fn claim(&mut self, token: String) -> Promise {
+        self.assert_gas_for_payout(self.gas_for_resolve);
+        let account_id = env::predecessor_account_id();
+        let token_id = TokenId::parse(&token);
+        let token = token_id.key();
+        let key = (account_id.clone(), token.clone());
+        let amount = self.claimable.remove(&key).expect("Nothing to claim");
+        if token_id == TokenId::Native {
+            self.total_claimable -= amount;
+        }
+
+        self.emit_event(HtlcEvent::HtlcClaimed(vec![HtlcClaimedData {
+            account_id: account_id.clone(),
+            token: token.clone(),
+            amount: U128(amount),
+        }]));
+        self.send_token(&token_id, account_id.clone(), amount, None)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(self.gas_for_resolve)
+                    .on_claim_complete(account_id, token, U128(amount)),
+            )
+    }
+
+    /// Callback for `claim` and executor fee payouts: credit the amount back
+    /// if the transfer failed
+    #[private]
+    pub fn on_claim_complete(
+        &mut self,
+        account_id: AccountId,
+        token: String,
+        amount: U128,
+    ) -> bool {
+        let succeeded = Self::transfer_succeeded();
+        if !succeeded {
+            self.credit_claimable(&account_id, &TokenId::parse(&token), amount.0);
+        }
+        succeeded
+    }
+
+    pub fn get_claimable(&self, account_id: AccountId, token: String) -> U128 {
+        U128(self.claimable.get(&(account_id, token)).unwrap_or(0))
+    }
+
+    /// Mark an active order past its timelock as `Expired`, leaving its
+    /// funds in place for `refund_htlc`. Callable by anyone. Expired orders
+    /// leave the active indexes and are listed by `get_orders_by_state`.
+    pub fn expire_htlc(&mut self, order_hash: Base64VecU8) {
+        let mut swap_order = self
+            .swap_orders
+            .get(&order_hash)
+            .expect("Order not found");
+
+        assert_eq!(swap_order.state, HTLCState::Active, "Order not active");
+        assert!(
+            env::block_timestamp() > swap_order.timelock,
+            "HTLC not expired"
+        );
+
+        self.set_state(&mut swap_order, HTLCState::Expired);
+        self.swap_orders.insert(&order_hash, &swap_order);
+        self.deactivate_order(&order_hash);
+
+        self.emit_event(HtlcEvent::HtlcExpired(vec![HtlcExpiredData {
+            order_hash,
+            timelock: swap_order.timelock,
+        }]));
+    }
+
+    /// Refund the HTLC after timelock expires, from `Active` or `Expired`.
+    /// Storage freed by settling the order is credited back to the resolver.
+    /// A partially filled order refunds only its unfilled remainder, as each
+    /// fill is taken out of the deposit. Contributors get back what they
+    /// added. Until the refund grace period has passed (or the order's
+    /// `public_cancellation` stage has begun) only the resolver may refund;
+    /// anyone refunding after it earns `cleanup_reward_bps` of the amount
+    /// along with the safety deposit. Once more than
+    /// `max_completion_attempts` payouts have failed the resolver may refund
+    /// before the timelock.
+    pub fn refund_htlc(&mut self, order_hash: Base64VecU8) {
+        let initial_storage = env::storage_usage();
+        let swap_order = self
+            .swap_orders
+            .get(&order_hash)
+            .unwrap_or_else(|| fail(ErrorCode::OrderNotFound, "Order not found"));
+
+        let executor = env::predecessor_account_id();
+        if let Some((code, message)) = self.refund_blocker(&swap_order, &executor) {
+            fail(code, message);
+        }
+        let cleanup = executor != swap_order.resolver;
+        self.internal_refund(order_hash, swap_order, initial_storage, executor, cleanup);
+    }
+
+    /// Freeze an active order (owner only): it can neither complete nor
+    /// refund until `resolve_dispute`. Its funds and hash locks stay
+    /// reserved, but the active order views and counts leave it out.
+    pub fn dispute_order(&mut self, order_hash: Base64VecU8) {
+        self.assert_owner();
+        let mut swap_order = self
+            .swap_orders
+            .get(&order_hash)
+            .expect("Order not found");
+        assert_eq!(swap_order.state, HTLCState::Active, "Order not active");
+
+        self.set_state(&mut swap_order, HTLCState::Disputed);
+        self.swap_orders.insert(&order_hash, &swap_order);
+
+        self.emit_event(HtlcEvent::HtlcDisputed(vec![HtlcDisputedData {
+            order_hash,
+        }]));
+    }
+
+    /// Settle a dispute (owner only): with `allow_complete` the order goes
+    /// back to `Active`, otherwise it is refunded right away, before its
+    /// timelock and without a cleanup reward.
+    pub fn resolve_dispute(&mut self, order_hash: Base64VecU8, allow_complete: bool) {
+        self.assert_owner();
+        let initial_storage = env::storage_usage();
+        let mut swap_order = self
+            .swap_orders
+            .get(&order_hash)
+            .expect("Order not found");
+        assert_eq!(
+            swap_order.state,
+            HTLCState::Disputed,
+            "Order not disputed"
+        );
+
+        self.emit_event(HtlcEvent::HtlcDisputeResolved(vec![
+            HtlcDisputeResolvedData {
+                order_hash: order_hash.clone(),
+                allow_complete,
+            },
+        ]));
+        if allow_complete {
+            self.set_state(&mut swap_order, HTLCState::Active);
+            self.swap_orders.insert(&order_hash, &swap_order);
+        } else {
+            let executor = env::predecessor_account_id();
+            self.internal_refund(order_hash, swap_order, initial_storage, executor, false);
+        }
+    }
+
+    /// Last-resort rescue of an order nobody refunded (owner only): once
+    /// `FORCE_REFUND_DELAY` has passed since its timelock, send its whole
+    /// deposit, contributions and safety deposit included, to `rescue_to`
+    /// and mark it `Refunded`.
+    pub fn owner_force_refund(&mut self, order_hash: Base64VecU8, rescue_to: AccountId) {
+        self.assert_owner();
+        let initial_storage = env::storage_usage();
+        let mut swap_order = self
+            .swap_orders
+            .get(&order_hash)
+            .expect("Order not found");
+
+        assert!(
+            matches!(swap_order.state, HTLCState::Active | HTLCState::Expired),
+            "Order not active"
+        );
+        assert!(
+            env::block_timestamp() > swap_order.timelock.saturating_add(FORCE_REFUND_DELAY),
+            "Force refund delay not passed"
+        );
+        self.assert_gas_for_payout(Gas(0));
+
+        self.set_state(&mut swap_order, HTLCState::Refunded);
+        self.deactivate_order(&order_hash);
+        let amount = self.deposits.get(&order_hash).expect("Deposit not found");
+        self.deposits.remove(&order_hash);
+        self.contributions.remove(&order_hash);
+        self.unlock(&swap_order.token_id(), amount);
+        if swap_order.safety_deposit.0 > 0 {
+            self.unlock(&TokenId::Native, swap_order.safety_deposit.0);
+        }
+        self.release_order_storage(&mut swap_order, initial_storage);
+        self.swap_orders.insert(&order_hash, &swap_order);
+        self.total_refunded += 1;
+        self.update_resolver_stats(&swap_order.resolver, |stats| stats.refunded += 1);
+
+        // The freed order NEAR must still be on the account
+        let native = if swap_order.dst_asset == DstAsset::Native {
+            amount
+        } else {
+            0
+        };
+        self.assert_available_balance(native + swap_order.safety_deposit.0);
+        self.transfer_token(&swap_order, rescue_to.clone(), amount);
+        if swap_order.safety_deposit.0 > 0 {
+            Promise::new(rescue_to.clone()).transfer(swap_order.safety_deposit.0);
+        }
+
+        self.emit_event(HtlcEvent::HtlcForceRefunded(vec![HtlcForceRefundedData {
+            order_hash,
+            rescue_to,
+            amount: U128(amount),
+        }]));
+    }
+
+    /// Delete a settled (completed or refunded) order, crediting its
+    /// remaining storage charge to the resolver's storage balance. Only the resolver or owner
+    /// may purge, and only once the timelock has passed so no completion
+    /// callback is still pending.
+    pub fn purge_order(&mut self, order_hash: Base64VecU8) {
+        self.internal_purge_order(&order_hash);
+    }
+
+    /// Purge several settled orders at once, see `purge_order`
+    pub fn purge_orders(&mut self, order_hashes: Vec<Base64VecU8>) {
+        for order_hash in order_hashes {
+            self.internal_purge_order(&order_hash);
+        }
+    }
+
+    /// Whether an order was ever created with `order_hash`, even if it has
+    /// since been purged
+    pub fn is_order_hash_used(&self, order_hash: Base64VecU8) -> bool {
+        self.used_order_hashes.contains(&order_hash)
+    }
+
+    /// NEAR added to an order by accounts other than its resolver
+    pub fn get_contributions(&self, order_hash: Base64VecU8) -> Vec<(AccountId, U128)> {
+        self.contributions
+            .get(&order_hash)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(contributor, amount)| (contributor, U128(amount)))
+            .collect()
+    }
+
+    /// Get swap order details
+    pub fn get_swap_order(&self, order_hash: Base64VecU8) -> Option<SwapOrder> {
+        self.swap_orders.get(&order_hash)
+    }
+
+    /// Borsh encoding of an order, the bytes kept in contract state under
+    /// `swap_orders` except that an auto-complete secret is left out, as in
+    /// `get_swap_order`. Fields are encoded in declaration order, so new
+    /// `SwapOrder` fields are only ever appended.
+    pub fn get_order_borsh(&self, order_hash: Base64VecU8) -> Option<Base64VecU8> {
+        self.swap_orders.get(&order_hash).map(|mut order| {
+            order.auto_complete_secret = None;
+            Base64VecU8(order.try_to_vec().expect("Failed to serialize order"))
+        })
+    }
+
+    /// Get several swap orders in the order of `order_hashes`, with `None`
+    /// for unknown hashes. At most `MAX_ORDERS_PER_QUERY` hashes per call.
+    pub fn get_swap_orders(&self, order_hashes: Vec<Base64VecU8>) -> Vec<Option<SwapOrder>> {
+        assert!(
+            order_hashes.len() <= MAX_ORDERS_PER_QUERY,
+            "Too many order hashes"
+        );
+        order_hashes
+            .iter()
+            .map(|order_hash| self.swap_orders.get(order_hash))
+            .collect()
+    }
+
+    /// Get the secret revealed when the order was completed
+    pub fn get_revealed_secret(&self, order_hash: Base64VecU8) -> Option<Base64VecU8> {
+        self.swap_orders
+            .get(&order_hash)
+            .and_then(|order| order.revealed_secret)
+    }
+
+    /// Get the settlement stage an order is in (see the `STAGE_*` constants).
+    /// Orders without staged timelocks move from exclusive (while a public
+    /// window is pending) to public withdrawal, then to cancellation.
+    pub fn get_current_stage(&self, order_hash: Base64VecU8) -> u8 {
+        let order = self
+            .swap_orders
+            .get(&order_hash)
+            .expect("Order not found");
+        let now = env::block_timestamp();
+
+        if now > order.timelock {
+            return match order
+                .timelocks
+                .and_then(|stages| stages.public_cancellation)
+            {
+                Some(public_cancellation) if now - order.created_at >= public_cancellation => {
+                    STAGE_PUBLIC_CANCELLATION
+                }
+                _ => STAGE_CANCELLATION,
+            };
+        }
+        match order.timelocks {
+            Some(stages) => {
+                let elapsed = now - order.created_at;
+                if elapsed >= stages.public_withdraw {
+                    STAGE_PUBLIC_WITHDRAW
+                } else if elapsed >= stages.exclusive_withdraw {
+                    STAGE_EXCLUSIVE_WITHDRAW
+                } else if elapsed >= stages.finality_lock {
+                    STAGE_FINALIZED
+                } else {
+                    STAGE_FINALITY_LOCK
+                }
+            }
+            None => match order.public_window_start {
+                Some(start) if now < start => STAGE_EXCLUSIVE_WITHDRAW,
+                _ => STAGE_PUBLIC_WITHDRAW,
+            },
+        }
+    }
+
+    /// Least amount an auction order currently accepts, `None` for orders
+    /// without an auction
+    pub fn get_auction_price(&self, order_hash: Base64VecU8) -> Option<U128> {
+        self.swap_orders
+            .get(&order_hash)
+            .and_then(|order| order.auction)
+            .map(|auction| U128(auction.price_at(env::block_timestamp())))
+    }
+
+    /// State of an order, reporting an `Active` order past its timelock as
+    /// `Expired` even before `expire_htlc` has marked it
+    pub fn get_order_state(&self, order_hash: Base64VecU8) -> Option<HTLCState> {
+        self.swap_orders.get(&order_hash).map(|order| {
+            if order.state == HTLCState::Active && env::block_timestamp() > order.timelock {
+                HTLCState::Expired
+            } else {
+                order.state
+            }
+        })
+    }
+
+    /// Check if HTLC is active
+    pub fn is_htlc_active(&self, order_hash: Base64VecU8) -> bool {
+        if let Some(order) = self.swap_orders.get(&order_hash) {
+            order.state == HTLCState::Active && env::block_timestamp() <= order.timelock
+        } else {
+            false
+        }
+    }
+
+    /// Page of active orders, as `get_active_orders`, with each order's
+    /// remaining time and the total active count
+    pub fn get_active_orders_detailed(
+        &self,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> ActiveOrdersPage {
+        let now = env::block_timestamp();
+        ActiveOrdersPage {
+            orders: self
+                .get_active_orders(from_index, limit)
+                .into_iter()
+                .map(|order| ActiveOrderInfo {
+                    remaining_ns: order.timelock.saturating_sub(now),
+                    order,
+                })
+                .collect(),
+            total_active: self.active_order_count(),
+        }
+    }
+
+    /// Up to `MAX_ORDERS_PER_QUERY` orders with ids in `[from_id, to_id)`,
+    /// in id order. Purged orders leave gaps.
+    pub fn get_orders_by_id_range(&self, from_id: u64, to_id: u64) -> Vec<SwapOrder> {
+        if from_id >= to_id {
+            return Vec::new();
+        }
+        self.order_ids
+            .range((Bound::Included(from_id), Bound::Excluded(to_id)))
+            .filter_map(|(_, order_hash)| self.swap_orders.get(&order_hash))
+            .take(MAX_ORDERS_PER_QUERY)
+            .collect()
+    }
+
+    /// Up to `limit` orders with ids after `after_id` (from the first order
+    /// if unset), in id order. Pass the last id of a page to get the next
+    /// one; each page reads orders by id instead of walking earlier pages.
+    /// `limit` is capped at `MAX_ORDERS_PER_QUERY`.
+    pub fn get_orders_after_id(&self, after_id: Option<u64>, limit: Option<u32>) -> Vec<SwapOrder> {
+        let limit = (limit.unwrap_or(10) as usize).min(MAX_ORDERS_PER_QUERY);
+        let start = after_id.map_or(Bound::Included(0), Bound::Excluded);
+        self.order_ids
+            .range((start, Bound::Included(u64::MAX)))
+            .filter_map(|(_, order_hash)| self.swap_orders.get(&order_hash))
+            .take(limit)
+            .collect()
+    }
+
+    /// Get all active orders (for monitoring). Disputed orders keep their
+    /// index position but are left out of the page.
+    pub fn get_active_orders(&self, from_index: Option<u32>, limit: Option<u32>) -> Vec<SwapOrder> {
+        let start = from_index.unwrap_or(0) as usize;
+        let limit = limit.unwrap_or(10) as usize;
+
+        // Page directly over the active index instead of scanning all orders
+        let active = self.active_order_hashes.as_vector();
+        let end = active.len().min((start + limit) as u64);
+        (start as u64..end)
+            .filter_map(|index| active.get(index))
+            .filter_map(|order_hash| self.swap_orders.get(&order_hash))
+            .filter(|order| order.state == HTLCState::Active)
+            .collect()
+    }
+
+    /// Get orders in a given state. Active orders are paged over the active
+    /// index; other states filter all orders before paginating.
+    pub fn get_orders_by_state(
+        &self,
+        state: HTLCState,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Vec<SwapOrder> {
+        if state == HTLCState::Active {
+            return self.get_active_orders(from_index, limit);
+        }
+
+        let start = from_index.unwrap_or(0) as usize;
+        let limit = limit.unwrap_or(10) as usize;
         self.swap_orders
             .values()
+            .filter(|order| order.state == state)
+            .skip(start)
+            .take(limit)
+            .collect()
+    }
+
+    /// Active orders whose timelock falls within the next `window_ns`
+    pub fn get_orders_expiring_within(
+        &self,
+        window_ns: u64,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Vec<SwapOrder> {
+        let start = from_index.unwrap_or(0) as usize;
+        let limit = limit.unwrap_or(10) as usize;
+        let now = env::block_timestamp();
+        let end = now.saturating_add(window_ns);
+
+        self.active_orders()
+            .filter(|order| order.timelock >= now && order.timelock <= end)
             .skip(start)
             .take(limit)
+            .collect()
+    }
+
+    /// Get orders needing keeper action, tagged with the action to take
+    pub fn get_keeper_worklist(
+        &self,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Vec<KeeperTask> {
+        let start = from_index.unwrap_or(0) as usize;
+        let limit = limit.unwrap_or(10) as usize;
+
+        self.active_orders()
+            .filter_map(|order| {
+                self.keeper_action(&order).map(|action| KeeperTask {
+                    order_hash: order.order_hash.clone(),
+                    action,
+                    timelock: order.timelock,
+                })
+            })
+            .skip(start)
+            .take(limit)
+            .collect()
+    }
+
+    /// Get orders created for a source-chain maker, oldest first. Purged
+    /// orders keep their index position but are left out of the page.
+    pub fn get_orders_by_maker(
+        &self,
+        src_maker: String,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Vec<SwapOrder> {
+        self.orders_from_index(self.maker_orders.get(&src_maker), from_index, limit)
+    }
+
+    /// Get orders paying out to a NEAR recipient, oldest first, like
+    /// `get_orders_by_maker`
+    pub fn get_orders_by_recipient(
+        &self,
+        recipient: AccountId,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Vec<SwapOrder> {
+        self.orders_from_index(self.recipient_orders.get(&recipient), from_index, limit)
+    }
+
+    /// Get orders coming from a source chain, oldest first, like
+    /// `get_orders_by_maker`
+    pub fn get_orders_by_chain(
+        &self,
+        src_chain: String,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Vec<SwapOrder> {
+        self.orders_from_index(self.chain_orders.get(&src_chain), from_index, limit)
+    }
+
+    /// Get the largest active orders by amount (for risk monitoring)
+    pub fn get_largest_active_orders(&self, limit: Option<u32>) -> Vec<SwapOrder> {
+        let limit = limit.unwrap_or(LARGEST_ORDERS_CAPACITY as u32) as usize;
+
+        self.active_orders_by_amount
+            .iter_rev()
+            .filter_map(|(_, order_hash)| self.swap_orders.get(&order_hash))
             .filter(|order| order.state == HTLCState::Active)
+            .take(limit)
             .collect()
     }
 
-    /// Verify hash lock matches secret
-    pub fn verify_secret(&self, secret: Base64VecU8, hash_lock: Base64VecU8) -> bool {
-        let secret_hash = Sha256::digest(&secret.0);
-        secret_hash.as_slice() == hash_lock.0.as_slice()
+    /// SHA-256 of the Borsh encoding of the order fields, in argument order.
+    /// Strings and the hash lock are length-prefixed, so the encoding is
+    /// unambiguous; source chains derive their order hash the same way.
+    pub fn compute_order_hash(
+        &self,
+        src_maker: String,
+        src_chain: String,
+        src_token: String,
+        src_amount: U128,
+        dst_recipient: AccountId,
+        dst_token: String,
+        dst_amount: U128,
+        hash_lock: Base64VecU8,
+        timelock: Timestamp,
+    ) -> Base64VecU8 {
+        let encoded = (
+            src_maker,
+            src_chain,
+            src_token,
+            src_amount.0,
+            dst_recipient,
+            dst_token,
+            dst_amount.0,
+            hash_lock.0,
+            timelock,
+        )
+            .try_to_vec()
+            .expect("Failed to serialize order");
+        Base64VecU8(env::sha256(&encoded))
+    }
+
+    /// Whether `secret` unlocks the given order under its hash algorithm;
+    /// false if the order doesn't exist
+    pub fn verify_secret_for_order(&self, order_hash: Base64VecU8, secret: Base64VecU8) -> bool {
+        self.swap_orders
+            .get(&order_hash)
+            .is_some_and(|order| Self::matching_hash_lock(&order, &secret).is_some())
+    }
+
+    /// Whether `complete_htlc` would currently accept `secret` for the order:
+    /// active, unexpired, past any finality lock, confirmed on the source
+    /// chain, with an allowed recipient and a long enough, matching secret.
+    /// Public window
+    /// access depends on the caller and isn't checked.
+    pub fn is_order_completable(&self, order_hash: Base64VecU8, secret: Base64VecU8) -> bool {
+        let swap_order = match self.swap_orders.get(&order_hash) {
+            Some(order) => order,
+            None => return false,
+        };
+        let now = env::block_timestamp();
+        swap_order.state == HTLCState::Active
+            && now <= Self::completion_deadline(&swap_order)
+            && swap_order.timelocks.is_none_or(|stages| {
+                now >= swap_order.created_at + stages.finality_lock
+            })
+            && swap_order.merkle_root.is_none()
+            && self.is_source_confirmed(&swap_order)
+            && !self.blacklist.contains_key(&swap_order.dst_recipient)
+            && secret.0.len() >= self.min_secret_length as usize
+            && Self::matching_hash_lock(&swap_order, &secret).is_some()
+    }
+
+    /// Verify hash lock matches secret (SHA-256 unless another algorithm is given)
+    pub fn verify_secret(
+        &self,
+        secret: Base64VecU8,
+        hash_lock: Base64VecU8,
+        hash_algorithm: Option<HashAlgorithm>,
+    ) -> bool {
+        let secret_hash = hash_algorithm.unwrap_or_default().digest(&secret.0);
+        secret_hash.as_slice() == hash_lock.0.as_slice()
+    }
+
+    // Owner functions
+
+    /// Add supported chain (owner only). Re-enables a disabled chain and
+    /// keeps its config; a new chain starts with a zeroed config.
+    pub fn add_supported_chain(&mut self, chain: String) {
+        self.assert_owner();
+        let config = self.supported_chains.get(&chain).unwrap_or(ChainConfig {
+            chain_id: 0,
+            min_confirmations: 0,
+            enabled: true,
+        });
+        self.supported_chains.insert(
+            &chain,
+            &ChainConfig {
+                enabled: true,
+                ..config
+            },
+        );
+    }
+
+    /// Remove supported chain (owner only). Its config is kept, disabled.
+    pub fn remove_supported_chain(&mut self, chain: String) {
+        self.assert_owner();
+        if let Some(config) = self.supported_chains.get(&chain) {
+            self.supported_chains.insert(
+                &chain,
+                &ChainConfig {
+                    enabled: false,
+                    ..config
+                },
+            );
+        }
+    }
+
+    /// Require new orders to use the hash given by `compute_order_hash` (owner only)
+    pub fn set_verify_order_hashes(&mut self, enabled: bool) {
+        self.assert_owner();
+        self.verify_order_hashes = enabled;
+    }
+
+    /// Reject orders whose recipient is their own resolver (owner only).
+    /// Off by default, as some flows legitimately use one account for both.
+    pub fn set_reject_self_dealing(&mut self, enabled: bool) {
+        self.assert_owner();
+        self.reject_self_dealing = enabled;
+    }
+
+    /// Set the account that may report chain heights besides the owner (owner only)
+    pub fn set_height_oracle(&mut self, height_oracle: Option<AccountId>) {
+        self.assert_owner();
+        self.height_oracle = height_oracle;
+    }
+
+    /// Report the latest block height of a source chain (owner or height
+    /// oracle). Heights only move forward.
+    pub fn update_chain_height(&mut self, chain: String, height: u64) {
+        let caller = env::predecessor_account_id();
+        assert!(
+            caller == self.owner || self.height_oracle.as_ref() == Some(&caller),
+            "Only owner or height oracle can update chain heights"
+        );
+        assert!(
+            height >= self.get_chain_height(chain.clone()),
+            "Chain height cannot decrease"
+        );
+        self.chain_heights.insert(&chain, &height);
+    }
+
+    /// Set the full config of a chain (owner only)
+    pub fn set_chain_config(&mut self, chain: String, config: ChainConfig) {
+        self.assert_owner();
+        self.supported_chains.insert(&chain, &config);
+    }
+
+    /// Update timelock limits (owner only)
+    pub fn update_timelock_limits(&mut self, min_timelock: Timestamp, max_timelock: Timestamp) {
+        self.assert_owner();
+        assert!(min_timelock < max_timelock, "Invalid timelock limits");
+        self.min_timelock = min_timelock;
+        self.max_timelock = max_timelock;
+    }
+
+    /// Set timelock limits for orders from one source chain (owner only)
+    pub fn set_chain_timelock_limits(
+        &mut self,
+        chain: String,
+        min_timelock: Timestamp,
+        max_timelock: Timestamp,
+    ) {
+        self.assert_owner();
+        assert!(min_timelock < max_timelock, "Invalid timelock limits");
+        self.chain_timelocks
+            .insert(&chain, &(min_timelock, max_timelock));
+    }
+
+    /// Update the required margin below the source-chain timelock (owner only)
+    pub fn update_timelock_safety_margin(&mut self, safety_margin: Timestamp) {
+        self.assert_owner();
+        self.timelock_safety_margin = safety_margin;
+    }
+
+    /// Update auto-completion window and keeper reward (owner only)
+    pub fn update_auto_complete_config(&mut self, window: Timestamp, reward_bps: u16) {
+        self.assert_owner();
+        assert!(reward_bps <= 10_000, "Invalid reward");
+        self.auto_complete_window = window;
+        self.auto_complete_reward_bps = reward_bps;
+    }
+
+    /// Update the refund grace period and the cleanup reward paid to others
+    /// refunding after it (owner only)
+    pub fn set_refund_grace_config(&mut self, grace_ns: Timestamp, cleanup_reward_bps: u16) {
+        self.assert_owner();
+        assert!(
+            cleanup_reward_bps <= MAX_CLEANUP_REWARD_BPS,
+            "Invalid cleanup reward"
+        );
+        self.refund_grace_ns = grace_ns;
+        self.cleanup_reward_bps = cleanup_reward_bps;
+    }
+
+    /// Update the accepted range of NEAR order deposits (owner only)
+    pub fn set_order_amount_limits(&mut self, min: U128, max: U128) {
+        self.assert_owner();
+        assert!(min.0 <= max.0, "Invalid order amount limits");
+        self.min_order_amount = min.0;
+        self.max_order_amount = max.0;
+    }
+
+    /// Update how many failed completion payouts unlock an early refund
+    /// (owner only)
+    pub fn set_max_completion_attempts(&mut self, max_completion_attempts: u8) {
+        self.assert_owner();
+        self.max_completion_attempts = max_completion_attempts;
+    }
+
+    /// Update the shortest secret accepted at completion (owner only)
+    pub fn set_min_secret_length(&mut self, min_secret_length: u32) {
+        self.assert_owner();
+        self.min_secret_length = min_secret_length;
+    }
+
+    /// Update the smallest NEAR deposit per native order (owner only)
+    pub fn set_min_deposit(&mut self, min_deposit: U128) {
+        self.assert_owner();
+        self.min_deposit = min_deposit.0;
+    }
+
+    /// Set how far order rates may stray from reference rates (owner only)
+    pub fn set_max_rate_deviation_bps(&mut self, max_rate_deviation_bps: u16) {
+        self.assert_owner();
+        assert!(max_rate_deviation_bps <= 10_000, "Invalid rate deviation");
+        self.max_rate_deviation_bps = max_rate_deviation_bps;
+    }
+
+    /// Register or update the display metadata of `token` (owner only)
+    pub fn register_token(&mut self, token: String, decimals: u8, symbol: String) {
+        self.assert_owner();
+        self.token_registry
+            .insert(&token, &TokenInfo { decimals, symbol });
+    }
+
+    /// Set the reference rate orders swapping `src_token` for `dst_token`
+    /// are checked against (owner only)
+    pub fn set_reference_rate(
+        &mut self,
+        src_token: String,
+        dst_token: String,
+        src_units: U128,
+        dst_units: U128,
+    ) {
+        self.assert_owner();
+        assert!(src_units.0 > 0 && dst_units.0 > 0, "Invalid reference rate");
+        self.reference_rates.insert(
+            &(src_token, dst_token),
+            &ReferenceRate {
+                src_units,
+                dst_units,
+            },
+        );
+    }
+
+    /// Stop checking orders of a token pair against a reference rate (owner only)
+    pub fn remove_reference_rate(&mut self, src_token: String, dst_token: String) {
+        self.assert_owner();
+        self.reference_rates.remove(&(src_token, dst_token));
+    }
+
+    /// Set the length of volume cap windows (owner only)
+    pub fn set_volume_window(&mut self, volume_window_ns: Timestamp) {
+        self.assert_owner();
+        assert!(volume_window_ns > 0, "Invalid volume window");
+        self.volume_window_ns = volume_window_ns;
+    }
+
+    /// Set the most native NEAR new orders may lock per window (owner only)
+    pub fn set_volume_cap(&mut self, volume_cap: U128) {
+        self.assert_owner();
+        self.volume_cap = volume_cap.0;
+    }
+
+    /// Set the NEAR attached when registering a token order's recipient
+    /// (owner only)
+    pub fn set_storage_subsidy(&mut self, storage_subsidy: U128) {
+        self.assert_owner();
+        self.storage_subsidy = storage_subsidy.0;
+    }
+
+    /// Set the NEAR kept on the account beyond its storage staking (owner
+    /// only)
+    pub fn set_storage_reserve(&mut self, storage_reserve: U128) {
+        self.assert_owner();
+        self.storage_reserve = storage_reserve.0;
+    }
+
+    /// Set the most orders one resolver may create per block (owner only)
+    pub fn set_max_creates_per_block(&mut self, max_creates_per_block: u32) {
+        self.assert_owner();
+        self.max_creates_per_block = max_creates_per_block;
+    }
+
+    /// Set the gas given to token transfers and payout callbacks (owner only)
+    pub fn set_gas_config(&mut self, gas_for_ft_transfer: Gas, gas_for_resolve: Gas) {
+        self.assert_owner();
+        assert!(
+            gas_for_ft_transfer.0 > 0 && gas_for_resolve.0 > 0,
+            "Invalid gas config"
+        );
+        self.gas_for_ft_transfer = gas_for_ft_transfer;
+        self.gas_for_resolve = gas_for_resolve;
+    }
+
+    /// Refuse an account as resolver or recipient (owner only). Its active
+    /// orders can still be refunded.
+    pub fn blacklist_account(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.blacklist.insert(&account_id, &true);
+    }
+
+    /// Lift an account's blacklisting (owner only)
+    pub fn unblacklist_account(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.blacklist.remove(&account_id);
+    }
+
+    /// Allow a resolver to create orders under the allowlist (owner only)
+    pub fn add_resolver(&mut self, resolver: AccountId) {
+        self.assert_owner();
+        self.resolvers.insert(&resolver, &true);
+    }
+
+    /// Remove a resolver from the allowlist (owner only)
+    pub fn remove_resolver(&mut self, resolver: AccountId) {
+        self.assert_owner();
+        self.resolvers.remove(&resolver);
+    }
+
+    /// Turn the resolver allowlist on or off; when off anyone may create
+    /// orders (owner only)
+    pub fn set_allowlist_enabled(&mut self, enabled: bool) {
+        self.assert_owner();
+        self.resolver_allowlist_enabled = enabled;
+    }
+
+    /// Stop new orders from being created (owner only). Existing orders can
+    /// still be completed and refunded.
+    pub fn pause(&mut self) {
+        self.assert_owner();
+        self.paused = true;
+    }
+
+    /// Resume order creation (owner only)
+    pub fn unpause(&mut self) {
+        self.assert_owner();
+        self.paused = false;
+    }
+
+    /// Set the protocol fee taken from completed payouts (owner only)
+    pub fn set_fee_bps(&mut self, fee_bps: u16) {
+        self.assert_owner();
+        assert!(fee_bps <= 10_000, "Invalid fee");
+        self.fee_bps = fee_bps;
+    }
+
+    /// Withdraw collected protocol fees of a token to `to`, such as a
+    /// treasury (owner only)
+    pub fn withdraw_fees(&mut self, token: String, amount: U128, to: AccountId) -> Promise {
+        self.assert_owner();
+        let token_id = TokenId::parse(&token);
+        let token = token_id.key();
+        let collected = self.collected_fees.get(&token).unwrap_or(0);
+        assert!(amount.0 <= collected, "Insufficient collected fees");
+        self.collected_fees.insert(&token, &(collected - amount.0));
+        if token_id == TokenId::Native {
+            self.assert_available_balance(amount.0);
+        }
+
+        self.send_token(&token_id, to, amount.0, None)
+    }
+
+    /// Emergency withdrawal of NEAR not locked in active orders or needed
+    /// for storage (owner only)
+    pub fn emergency_withdraw(&mut self, amount: U128) {
+        self.assert_owner();
+        self.assert_available_balance(amount.0);
+        Promise::new(self.owner.clone()).transfer(amount.0);
+    }
+
+    /// Set the fee for pushing out an order's completion deadline (owner only)
+    pub fn set_completion_extension_fee(&mut self, completion_extension_fee: U128) {
+        self.assert_owner();
+        self.completion_extension_fee = completion_extension_fee.0;
+    }
+
+    /// Propose a new owner, who takes over once it calls `accept_ownership`
+    /// (owner only). Replaces any earlier proposal.
+    pub fn propose_new_owner(&mut self, new_owner: AccountId) {
+        self.assert_owner();
+        self.pending_owner = Some(new_owner);
+    }
+
+    /// Become the owner (pending owner only)
+    pub fn accept_ownership(&mut self) {
+        assert_eq!(
+            Some(env::predecessor_account_id()),
+            self.pending_owner,
+            "Only pending owner can accept ownership"
+        );
+        self.owner = self.pending_owner.take().unwrap();
+    }
+
+    /// Withdraw a pending ownership proposal (owner only)
+    pub fn cancel_ownership_transfer(&mut self) {
+        self.assert_owner();
+        self.pending_owner = None;
+    }
+
+    // View functions
+
+    pub fn get_owner(&self) -> AccountId {
+        self.owner.clone()
+    }
+
+    pub fn get_pending_owner(&self) -> Option<AccountId> {
+        self.pending_owner.clone()
+    }
+
+    pub fn is_chain_supported(&self, chain: String) -> bool {
+        self.supported_chains
+            .get(&chain)
+            .is_some_and(|config| config.enabled)
+    }
+
+    /// Whether new orders from `src_chain` paying out `dst_token` are
+    /// currently accepted: the contract isn't paused, the chain is enabled
+    /// and a token other than native NEAR is in the token registry
+    pub fn is_corridor_enabled(&self, src_chain: String, dst_token: String) -> bool {
+        !self.paused
+            && self.is_chain_supported(src_chain)
+            && (TokenId::is_native_name(&dst_token) || self.token_registry.contains_key(&dst_token))
+    }
+
+    pub fn get_chain_config(&self, chain: String) -> Option<ChainConfig> {
+        self.supported_chains.get(&chain)
+    }
+
+    pub fn get_chain_height(&self, chain: String) -> u64 {
+        self.chain_heights.get(&chain).unwrap_or(0)
+    }
+
+    pub fn get_height_oracle(&self) -> Option<AccountId> {
+        self.height_oracle.clone()
+    }
+
+    /// Names of the chains orders are currently accepted from
+    pub fn list_supported_chains(&self) -> Vec<String> {
+        self.supported_chains
+            .iter()
+            .filter(|(_, config)| config.enabled)
+            .map(|(chain, _)| chain)
+            .collect()
+    }
+
+    /// Current settings in one call
+    pub fn get_config(&self) -> ContractConfig {
+        ContractConfig {
+            owner: self.owner.clone(),
+            min_timelock: self.min_timelock,
+            max_timelock: self.max_timelock,
+            timelock_safety_margin: self.timelock_safety_margin,
+            fee_bps: self.fee_bps,
+            paused: self.paused,
+            min_order_amount: U128(self.min_order_amount),
+            max_order_amount: U128(self.max_order_amount),
+            min_deposit: U128(self.min_deposit),
+            min_secret_length: self.min_secret_length,
+            completion_extension_fee: U128(self.completion_extension_fee),
+            max_completion_attempts: self.max_completion_attempts,
+            max_creates_per_block: self.max_creates_per_block,
+            storage_reserve: U128(self.storage_reserve),
+            resolver_allowlist_enabled: self.resolver_allowlist_enabled,
+            verify_order_hashes: self.verify_order_hashes,
+            reject_self_dealing: self.reject_self_dealing,
+        }
+    }
+
+    pub fn get_timelock_limits(&self) -> (Timestamp, Timestamp) {
+        (self.min_timelock, self.max_timelock)
+    }
+
+    pub fn get_completion_extension_fee(&self) -> U128 {
+        U128(self.completion_extension_fee)
+    }
+
+    /// Timelock limits applied to orders from `chain`, falling back to the
+    /// global limits
+    pub fn get_chain_timelock_limits(&self, chain: String) -> (Timestamp, Timestamp) {
+        self.chain_timelocks
+            .get(&chain)
+            .unwrap_or((self.min_timelock, self.max_timelock))
+    }
+
+    pub fn get_timelock_safety_margin(&self) -> Timestamp {
+        self.timelock_safety_margin
+    }
+
+    pub fn get_auto_complete_config(&self) -> (Timestamp, u16) {
+        (self.auto_complete_window, self.auto_complete_reward_bps)
+    }
+
+    pub fn get_refund_grace_config(&self) -> (Timestamp, u16) {
+        (self.refund_grace_ns, self.cleanup_reward_bps)
+    }
+
+    /// Whether `resolver` may currently create orders
+    pub fn is_resolver_allowed(&self, resolver: AccountId) -> bool {
+        !self.resolver_allowlist_enabled || self.resolvers.get(&resolver).unwrap_or(false)
+    }
+
+    /// Gas for token transfers and payout callbacks
+    pub fn get_gas_config(&self) -> (Gas, Gas) {
+        (self.gas_for_ft_transfer, self.gas_for_resolve)
+    }
+
+    pub fn get_resolver_stats(&self, account_id: AccountId) -> Option<ResolverStats> {
+        self.resolver_stats.get(&account_id)
+    }
+
+    pub fn is_blacklisted(&self, account_id: AccountId) -> bool {
+        self.blacklist.contains_key(&account_id)
+    }
+
+    pub fn get_order_amount_limits(&self) -> (U128, U128) {
+        (U128(self.min_order_amount), U128(self.max_order_amount))
+    }
+
+    pub fn get_min_deposit(&self) -> U128 {
+        U128(self.min_deposit)
+    }
+
+    pub fn get_min_secret_length(&self) -> u32 {
+        self.min_secret_length
+    }
+
+    pub fn get_max_completion_attempts(&self) -> u8 {
+        self.max_completion_attempts
+    }
+
+    pub fn get_storage_reserve(&self) -> U128 {
+        U128(self.storage_reserve)
+    }
+
+    pub fn get_max_creates_per_block(&self) -> u32 {
+        self.max_creates_per_block
+    }
+
+    pub fn get_storage_subsidy(&self) -> U128 {
+        U128(self.storage_subsidy)
+    }
+
+    /// Native NEAR locked by orders created in the current volume window
+    pub fn get_current_window_volume(&self) -> U128 {
+        if self.window_elapsed() {
+            U128(0)
+        } else {
+            U128(self.window_volume)
+        }
+    }
+
+    pub fn get_max_rate_deviation_bps(&self) -> u16 {
+        self.max_rate_deviation_bps
+    }
+
+    pub fn get_token_info(&self, token: String) -> Option<TokenInfo> {
+        self.token_registry.get(&token)
+    }
+
+    pub fn get_reference_rate(
+        &self,
+        src_token: String,
+        dst_token: String,
+    ) -> Option<ReferenceRate> {
+        self.reference_rates.get(&(src_token, dst_token))
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn get_fee_bps(&self) -> u16 {
+        self.fee_bps
+    }
+
+    pub fn get_collected_fees(&self, token: String) -> U128 {
+        U128(self.collected_fees.get(&token).unwrap_or(0))
+    }
+
+    pub fn get_total_locked(&self) -> U128 {
+        U128(self.total_locked)
+    }
+
+    /// Amount of `token` ("NEAR" or token contract) held for active orders.
+    /// For NEAR this includes safety deposits and can be compared against
+    /// the account balance minus storage.
+    pub fn get_locked_balance(&self, token: String) -> U128 {
+        U128(self.locked_by_token.get(&token).unwrap_or(0))
+    }
+
+    /// Whether the locked balance of `token` matches the deposits (and, for
+    /// NEAR, safety deposits) of the active orders. Holds whenever no payout
+    /// is awaiting its callback.
+    #[private]
+    pub fn check_locked_invariant(&self, token: String) -> bool {
+        let token = TokenId::parse(&token);
+        let expected: Balance = self
+            .active_order_hashes
+            .iter()
+            .filter_map(|order_hash| self.swap_orders.get(&order_hash))
+            .map(|swap_order| {
+                let mut amount = 0;
+                if swap_order.token_id() == token {
+                    amount += self.deposits.get(&swap_order.order_hash).unwrap_or(0);
+                }
+                if token == TokenId::Native {
+                    amount += swap_order.safety_deposit.0;
+                }
+                amount
+            })
+            .sum();
+        expected == self.locked_by_token.get(&token.key()).unwrap_or(0)
+    }
+
+    /// Whether the account balance covers every native NEAR liability
+    pub fn get_solvency(&self) -> SolvencyReport {
+        let account_balance = env::account_balance();
+        let collected_fees = self.collected_fees.get(&TokenId::Native.key()).unwrap_or(0);
+        let storage_reserve =
+            Self::storage_staking() + self.storage_reserve + self.total_storage_available;
+        let liabilities = self
+            .total_locked
+            .saturating_add(collected_fees)
+            .saturating_add(self.total_claimable)
+            .saturating_add(storage_reserve);
+        SolvencyReport {
+            account_balance: U128(account_balance),
+            total_locked_native: U128(self.total_locked),
+            collected_fees_native: U128(collected_fees),
+            claimable_native: U128(self.total_claimable),
+            storage_reserve: U128(storage_reserve),
+            solvent: account_balance >= liabilities,
+        }
+    }
+
+    pub fn get_order_counts(&self) -> OrderCounts {
+        self.order_counts.clone()
+    }
+
+    pub fn get_stats(&self) -> ContractStats {
+        ContractStats {
+            total_orders_created: self.total_orders_created,
+            total_completed: self.total_completed,
+            total_refunded: self.total_refunded,
+            active_orders: self.active_order_count(),
+        }
+    }
+
+    pub fn get_total_volume(&self, token: String) -> U128 {
+        U128(self.total_volume.get(&token).unwrap_or(0))
+    }
+
+    // Private functions
+
+    fn assert_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner,
+            "Only owner can call this method"
+        );
+    }
+
+    /// Last time an order can be completed: its `complete_by`, if set, or
+    /// its timelock
+    fn completion_deadline(swap_order: &SwapOrder) -> Timestamp {
+        swap_order.complete_by.unwrap_or(swap_order.timelock)
+    }
+
+    /// NEAR order deposits must stay within the configured range
+    fn assert_order_amount(&self, amount: Balance) {
+        require(
+            amount >= self.min_order_amount,
+            ErrorCode::InvalidAmount,
+            "Order amount below minimum",
+        );
+        require(
+            amount <= self.max_order_amount,
+            ErrorCode::InvalidAmount,
+            "Order amount above maximum",
+        );
+    }
+
+    fn window_elapsed(&self) -> bool {
+        env::block_timestamp() >= self.window_start.saturating_add(self.volume_window_ns)
+    }
+
+    /// Count a new native NEAR deposit against the current volume window,
+    /// starting a new window if the last one has elapsed
+    fn record_window_volume(&mut self, amount: Balance) {
+        if self.window_elapsed() {
+            self.window_start = env::block_timestamp();
+            self.window_volume = 0;
+        }
+        require(
+            amount <= self.volume_cap.saturating_sub(self.window_volume),
+            ErrorCode::RateLimited,
+            "Volume cap exceeded for current window",
+        );
+        self.window_volume += amount;
+    }
+
+    /// Count a new order against its resolver's limit for the current
+    /// block, starting a new count once the block height advances
+    fn record_resolver_create(&mut self, resolver: &AccountId) {
+        let block = env::block_height();
+        let creates = match self.resolver_creates.get(resolver) {
+            Some((last_block, creates)) if last_block == block => creates,
+            _ => 0,
+        };
+        require(
+            creates < self.max_creates_per_block,
+            ErrorCode::RateLimited,
+            "Too many orders created in this block",
+        );
+        self.resolver_creates
+            .insert(resolver, &(block, creates + 1));
+    }
+
+    fn assert_recipient_allowed(&self, swap_order: &SwapOrder) {
+        require(
+            !self.blacklist.contains_key(&swap_order.dst_recipient),
+            ErrorCode::InvalidRecipient,
+            "Recipient is blacklisted",
+        );
+    }
+
+    /// Check that an order's rate is within `max_rate_deviation_bps` of its
+    /// pair's reference rate, if one is set
+    fn assert_rate_within_bounds(&self, swap_order: &SwapOrder) {
+        let rate = match self
+            .reference_rates
+            .get(&(swap_order.src_token.clone(), swap_order.dst_token.clone()))
+        {
+            Some(rate) => rate,
+            None => return,
+        };
+
+        // Dividing first only when the product overflows keeps the error
+        // negligible, since the amount is then far larger than the divisor
+        let expected = swap_order
+            .src_amount
+            .0
+            .checked_mul(rate.dst_units.0)
+            .map(|product| product / rate.src_units.0)
+            .or_else(|| (swap_order.src_amount.0 / rate.src_units.0).checked_mul(rate.dst_units.0))
+            .unwrap_or_else(|| {
+                fail(
+                    ErrorCode::RateOutOfBounds,
+                    "Amount too large for rate check",
+                )
+            });
+        let tolerance = expected / 10_000 * self.max_rate_deviation_bps as u128;
+        require(
+            swap_order.dst_amount.0 >= expected - tolerance
+                && swap_order.dst_amount.0 <= expected.saturating_add(tolerance),
+            ErrorCode::RateOutOfBounds,
+            "Rate deviates from reference",
+        );
+    }
+
+    /// Build a native NEAR order funded with `args.amount`, resolved by the caller
+    fn native_order(&self, args: CreateHtlcArgs) -> SwapOrder {
+        // Token orders are funded through `ft_transfer_call`. Any casing of
+        // "NEAR" names native NEAR, stored as "NEAR".
+        require(
+            TokenId::is_native_name(&args.dst_token),
+            ErrorCode::InvalidToken,
+            "Only native NEAR can be attached",
+        );
+
+        // The safety deposit is carved out of the attached NEAR
+        let safety_deposit = args.safety_deposit.map_or(0, |deposit| deposit.0);
+        require(
+            safety_deposit < args.amount.0,
+            ErrorCode::InvalidAmount,
+            "Safety deposit exceeds attached deposit",
+        );
+        let deposit_amount = args.amount.0 - safety_deposit;
+        self.assert_order_amount(deposit_amount);
+        require(
+            args.amount.0 >= self.min_deposit,
+            ErrorCode::InvalidAmount,
+            "Deposit below minimum",
+        );
+
+        let created_at = env::block_timestamp();
+        let (timelock, public_window_start) = match args.timelocks {
+            Some(stages) => {
+                require(
+                    args.public_window_start.is_none(),
+                    ErrorCode::InvalidTimelock,
+                    "Public window is set by timelocks",
+                );
+                (
+                    created_at + stages.cancellation,
+                    Some(created_at + stages.public_withdraw),
+                )
+            }
+            None => (args.timelock, args.public_window_start),
+        };
+
+        SwapOrder {
+            order_hash: args.order_hash,
+            src_maker: args.src_maker,
+            src_chain: args.src_chain,
+            src_token: args.src_token,
+            src_amount: args.src_amount,
+            dst_recipient: args.dst_recipient,
+            dst_token: TokenId::Native.key(),
+            dst_amount: U128(deposit_amount),
+            hash_lock: args.hash_lock,
+            hash_algorithm: args.hash_algorithm.unwrap_or_default(),
+            timelock,
+            complete_by: args.complete_by,
+            state: HTLCState::Active,
+            created_at,
+            resolver: env::predecessor_account_id(),
+            refund_bridge: args.refund_bridge,
+            auto_complete_secret: args.auto_complete_secret,
+            src_timelock: args.src_timelock,
+            revealed_secret: None,
+            safety_deposit: U128(safety_deposit),
+            public_window_start,
+            timelocks: args.timelocks,
+            merkle_root: args.merkle_root,
+            parts_filled: 0,
+            filled_amount: U128(0),
+            storage_bytes: 0,
+            order_id: 0,
+            dst_asset: DstAsset::Native,
+            memo: args.memo,
+            refund_to: args.refund_to,
+            relayer_fee: args.relayer_fee.unwrap_or(U128(0)),
+            allow_recipient_override: args.allow_recipient_override.unwrap_or(false),
+            src_block_height: args.src_block_height.unwrap_or(0),
+            completion_attempts: 0,
+            alt_hash_locks: args.alt_hash_locks.unwrap_or_default(),
+            matched_hash_lock: None,
+            auto_register_recipient: false,
+            push_on_complete: args.push_on_complete.unwrap_or(true),
+            auction: args.auction,
+            parts: args.parts.unwrap_or(0),
+        }
+    }
+
+    /// Validate a new order and store it along with its deposit of `dst_amount`
+    fn internal_create_htlc(&mut self, swap_order: SwapOrder) {
+        self.assert_valid_order(&swap_order);
+        self.store_order(swap_order);
+    }
+
+    /// Check a new order against the contract settings and existing orders
+    fn assert_valid_order(&self, swap_order: &SwapOrder) {
+        let order_hash = &swap_order.order_hash;
+        let timelock = swap_order.timelock;
+
+        // Validate parameters
+        require(!self.paused, ErrorCode::Paused, "Contract is paused");
+        require(
+            self.is_resolver_allowed(swap_order.resolver.clone()),
+            ErrorCode::ResolverNotAllowed,
+            "Resolver not allowlisted",
+        );
+        require(
+            !self.blacklist.contains_key(&swap_order.resolver),
+            ErrorCode::ResolverNotAllowed,
+            "Resolver is blacklisted",
+        );
+        require(
+            self.is_chain_supported(swap_order.src_chain.clone()),
+            ErrorCode::UnsupportedChain,
+            "Unsupported source chain",
+        );
+        let (min_timelock, max_timelock) =
+            self.get_chain_timelock_limits(swap_order.src_chain.clone());
+        require(
+            timelock > env::block_timestamp() + min_timelock,
+            ErrorCode::InvalidTimelock,
+            "Timelock too short",
+        );
+        require(
+            timelock < env::block_timestamp() + max_timelock,
+            ErrorCode::InvalidTimelock,
+            "Timelock too long",
+        );
+        if let Some(complete_by) = swap_order.complete_by {
+            require(
+                complete_by > env::block_timestamp() && complete_by <= timelock,
+                ErrorCode::InvalidTimelock,
+                "Completion deadline must be between now and the timelock",
+            );
+        }
+        // The NEAR side must expire first so the secret is revealed here
+        // while the source-chain HTLC can still be claimed
+        if let Some(src_timelock) = swap_order.src_timelock {
+            require(
+                timelock < src_timelock.saturating_sub(self.timelock_safety_margin),
+                ErrorCode::InvalidTimelock,
+                "Timelock too close to source timelock",
+            );
+        }
+        if let Some(stages) = swap_order.timelocks {
+            require(
+                stages.finality_lock < stages.exclusive_withdraw
+                    && stages.exclusive_withdraw < stages.public_withdraw
+                    && stages.public_withdraw < stages.cancellation
+                    && stages
+                        .public_cancellation
+                        .is_none_or(|public_cancellation| {
+                            stages.cancellation < public_cancellation
+                        }),
+                ErrorCode::InvalidTimelock,
+                "Timelock stages must be increasing",
+            );
+        }
+        if let Some(public_window_start) = swap_order.public_window_start {
+            require(
+                public_window_start < timelock,
+                ErrorCode::InvalidTimelock,
+                "Public window must open before timelock",
+            );
+        }
+        require(
+            self.swap_orders.get(order_hash).is_none(),
+            ErrorCode::OrderExists,
+            "Order already exists",
+        );
+        require(
+            !self.used_order_hashes.contains(order_hash),
+            ErrorCode::OrderExists,
+            "Order hash already used",
+        );
+        let digest_len = swap_order.hash_algorithm.digest_len();
+        require(
+            swap_order.alt_hash_locks.len() < MAX_HASH_LOCKS,
+            ErrorCode::InvalidHashLock,
+            "Too many hash locks",
+        );
+        require(
+            swap_order
+                .hash_locks()
+                .all(|hash_lock| hash_lock.0.len() == digest_len),
+            ErrorCode::InvalidHashLock,
+            "Invalid hash lock length",
+        );
+        require(
+            swap_order
+                .hash_locks()
+                .all(|hash_lock| !self.active_hashlocks.contains_key(hash_lock)),
+            ErrorCode::InvalidHashLock,
+            "Hash lock already in use",
+        );
+        if self.verify_order_hashes {
+            let expected = self.compute_order_hash(
+                swap_order.src_maker.clone(),
+                swap_order.src_chain.clone(),
+                swap_order.src_token.clone(),
+                swap_order.src_amount,
+                swap_order.dst_recipient.clone(),
+                swap_order.dst_token.clone(),
+                swap_order.dst_amount,
+                swap_order.hash_lock.clone(),
+                swap_order.timelock,
+            );
+            require(
+                *order_hash == expected,
+                ErrorCode::OrderHashMismatch,
+                "Order hash mismatch",
+            );
+        }
+        require(
+            swap_order.relayer_fee.0 == 0 || swap_order.relayer_fee.0 < swap_order.dst_amount.0,
+            ErrorCode::InvalidAmount,
+            "Relayer fee must be below the order amount",
+        );
+        if self.reject_self_dealing {
+            require(
+                swap_order.dst_recipient != swap_order.resolver,
+                ErrorCode::InvalidRecipient,
+                "Recipient cannot be the resolver",
+            );
+        }
+        if let Some(merkle_root) = &swap_order.merkle_root {
+            require(
+                merkle_root.0.len() == digest_len,
+                ErrorCode::InvalidHashLock,
+                "Invalid merkle root length",
+            );
+        }
+        require(
+            (swap_order.parts > 0) == swap_order.merkle_root.is_some(),
+            ErrorCode::InvalidAmount,
+            "Merkle orders need a part count, other orders none",
+        );
+        if let Some(memo) = &swap_order.memo {
+            require(
+                memo.len() <= MAX_MEMO_LENGTH,
+                ErrorCode::InvalidMemo,
+                "Memo too long",
+            );
+        }
+        self.assert_rate_within_bounds(swap_order);
+        if let Some(auction) = &swap_order.auction {
+            require(
+                auction.floor_amount.0 <= auction.start_amount.0 && auction.duration > 0,
+                ErrorCode::InvalidAuction,
+                "Invalid auction",
+            );
+            Self::assert_auction_price(auction, swap_order.dst_amount.0);
+        }
+        if let Some(secret) = &swap_order.auto_complete_secret {
+            require(
+                self.verify_secret(
+                    secret.clone(),
+                    swap_order.hash_lock.clone(),
+                    Some(swap_order.hash_algorithm),
+                ),
+                ErrorCode::InvalidSecret,
+                "Auto-complete secret does not match hash lock",
+            );
+        }
+
+        require(
+            swap_order.dst_amount.0 > 0,
+            ErrorCode::InvalidAmount,
+            "Must attach deposit",
+        );
+    }
+
+    /// Store a validated order and its deposit, charging the storage it
+    /// takes to the resolver
+    fn store_order(&mut self, mut swap_order: SwapOrder) {
+        let initial_storage = env::storage_usage();
+        let order_hash = swap_order.order_hash.clone();
+
+        self.record_resolver_create(&swap_order.resolver);
+        self.order_counts.add(&swap_order.state);
+        self.assign_order_id(&mut swap_order);
+        self.swap_orders.insert(&order_hash, &swap_order);
+        self.used_order_hashes.insert(&order_hash);
+        self.index_order(&swap_order);
+        // Rewriting the order to record its charge doesn't change its size
+        swap_order.storage_bytes = self.charge_storage(&swap_order.resolver, initial_storage);
+        self.swap_orders.insert(&order_hash, &swap_order);
+
+        if swap_order.state != HTLCState::Announced {
+            self.fund_order(swap_order);
+            return;
+        }
+
+        self.emit_event(HtlcEvent::HtlcAnnounced(vec![HtlcAnnouncedData {
+            order_hash,
+            resolver: swap_order.resolver,
+            dst_recipient: swap_order.dst_recipient,
+            dst_amount: swap_order.dst_amount,
+            hash_lock: swap_order.hash_lock,
+            timelock: swap_order.timelock,
+        }]));
+    }
+
+    /// Take a stored order's deposit of `dst_amount` into escrow and make it
+    /// `Active`, charging the added storage to its resolver
+    fn fund_order(&mut self, mut swap_order: SwapOrder) {
+        let initial_storage = env::storage_usage();
+        let order_hash = swap_order.order_hash.clone();
+        let timelock = swap_order.timelock;
+        let deposit_amount = swap_order.dst_amount.0;
+
+        if swap_order.dst_asset == DstAsset::Native {
+            self.record_window_volume(deposit_amount);
+        }
+
+        self.set_state(&mut swap_order, HTLCState::Active);
+        self.swap_orders.insert(&order_hash, &swap_order);
+        self.deposits.insert(&order_hash, &deposit_amount);
+        self.activate_order(&order_hash, deposit_amount);
+        self.total_orders_created += 1;
+        self.update_resolver_stats(&swap_order.resolver, |stats| stats.orders_created += 1);
+        self.lock(&swap_order.token_id(), deposit_amount);
+        self.lock(&TokenId::Native, swap_order.safety_deposit.0);
+        swap_order.storage_bytes += self.charge_storage(&swap_order.resolver, initial_storage);
+        self.swap_orders.insert(&order_hash, &swap_order);
+
+        self.emit_event(HtlcEvent::HtlcCreated(vec![HtlcCreatedData {
+            order_hash,
+            resolver: swap_order.resolver,
+            dst_recipient: swap_order.dst_recipient,
+            dst_token: swap_order.dst_token,
+            dst_amount: swap_order.dst_amount,
+            hash_lock: swap_order.hash_lock,
+            timelock,
+            memo: swap_order.memo,
+        }]));
+    }
+
+    /// Validate the secret against an active order and mark it completed,
+    /// returning the order and its released deposit
+    fn internal_complete(
+        &mut self,
+        order_hash: &Base64VecU8,
+        secret: &Base64VecU8,
+    ) -> (SwapOrder, Balance) {
+        let mut swap_order = self
+            .swap_orders
+            .get(order_hash)
+            .unwrap_or_else(|| fail(ErrorCode::OrderNotFound, "Order not found"));
+
+        Self::assert_open_for_completion(&swap_order);
+        self.assert_source_confirmed(&swap_order);
+        self.assert_recipient_allowed(&swap_order);
+        require(
+            swap_order.merkle_root.is_none(),
+            ErrorCode::PartialFillRequired,
+            "Order must be filled with complete_htlc_partial",
+        );
+
+        // The auction price only falls, so this holds unless the order was
+        // funded below it
+        let amount = self.deposits.get(order_hash).expect("Deposit not found");
+        if let Some(auction) = &swap_order.auction {
+            Self::assert_auction_price(auction, amount);
+        }
+
+        // Verify secret matches one of the hash locks
+        self.assert_secret_length(secret);
+        let matched = Self::matching_hash_lock(&swap_order, secret)
+            .unwrap_or_else(|| fail(ErrorCode::InvalidSecret, "Invalid secret"));
+
+        // Update state. The secret stays recorded even if the payout later
+        // fails, since it is public once revealed.
+        self.set_state(&mut swap_order, HTLCState::Completed);
+        swap_order.revealed_secret = Some(secret.clone());
+        swap_order.matched_hash_lock = Some(matched);
+        self.swap_orders.insert(order_hash, &swap_order);
+        self.deactivate_order(order_hash);
+
+        self.deposits.remove(order_hash);
+        self.unlock(&swap_order.token_id(), amount);
+        self.total_completed += 1;
+        self.add_volume(&swap_order.dst_token, amount as i128);
+        self.update_resolver_stats(&swap_order.resolver, |stats| {
+            stats.completed += 1;
+            stats.total_volume = U128(stats.total_volume.0 + amount);
+        });
+
+        (swap_order, amount)
+    }
+
+    /// Why `executor` can't refund the order yet, if it can't. Shared by
+    /// `refund_htlc` and `refund_htlc_batch` so both apply the same rules.
+    fn refund_blocker(
+        &self,
+        swap_order: &SwapOrder,
+        executor: &AccountId,
+    ) -> Option<(ErrorCode, &'static str)> {
+        let cleanup = *executor != swap_order.resolver;
+        if !matches!(swap_order.state, HTLCState::Active | HTLCState::Expired) {
+            Some((ErrorCode::OrderNotActive, "Order not active"))
+        } else if env::block_timestamp() <= swap_order.timelock
+            && (cleanup || swap_order.completion_attempts <= self.max_completion_attempts)
+        {
+            Some((ErrorCode::OrderNotExpired, "HTLC not expired"))
+        } else if cleanup && self.in_refund_grace(swap_order) {
+            Some((
+                ErrorCode::Unauthorized,
+                "Only resolver can refund during the grace period",
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Whether an expired order is still within its resolver-only refund grace period
+    fn in_refund_grace(&self, swap_order: &SwapOrder) -> bool {
+        match swap_order
+            .timelocks
+            .and_then(|stages| stages.public_cancellation)
+        {
+            Some(public_cancellation) => {
+                env::block_timestamp() < swap_order.created_at + public_cancellation
+            }
+            None => env::block_timestamp() <= swap_order.timelock + self.refund_grace_ns,
+        }
+    }
+
+    /// Index of the hash lock `secret` opens, `hash_lock` first
+    fn matching_hash_lock(swap_order: &SwapOrder, secret: &Base64VecU8) -> Option<u8> {
+        let secret_hash = swap_order.hash_algorithm.digest(&secret.0);
+        swap_order
+            .hash_locks()
+            .position(|hash_lock| hash_lock.0 == secret_hash)
+            .map(|index| index as u8)
+    }
+
+    /// Assert an order can currently be completed: active, before its
+    /// timelock and completion deadline, and past any finality lock
+    fn assert_open_for_completion(swap_order: &SwapOrder) {
+        require(
+            swap_order.state == HTLCState::Active,
+            ErrorCode::OrderNotActive,
+            "Order not active",
+        );
+        require(
+            env::block_timestamp() <= swap_order.timelock,
+            ErrorCode::OrderExpired,
+            "HTLC expired",
+        );
+        require(
+            env::block_timestamp() <= Self::completion_deadline(swap_order),
+            ErrorCode::OrderExpired,
+            "Completion window closed",
+        );
+        if let Some(stages) = swap_order.timelocks {
+            require(
+                env::block_timestamp() >= swap_order.created_at + stages.finality_lock,
+                ErrorCode::NotFinal,
+                "Finality lock active",
+            );
+        }
+    }
+
+    fn assert_auction_price(auction: &AuctionConfig, amount: Balance) {
+        require(
+            amount >= auction.price_at(env::block_timestamp()),
+            ErrorCode::InvalidAmount,
+            "Amount below auction price",
+        );
+    }
+
+    /// Reject secrets shorter than `min_secret_length`, which may be brute-forceable
+    fn assert_secret_length(&self, secret: &Base64VecU8) {
+        require(
+            secret.0.len() >= self.min_secret_length as usize,
+            ErrorCode::InvalidSecret,
+            "Secret too short",
+        );
+    }
+
+    /// Whether the reported height of the order's source chain is at least
+    /// `min_confirmations` past its `src_block_height`
+    fn is_source_confirmed(&self, swap_order: &SwapOrder) -> bool {
+        let min_confirmations = self
+            .supported_chains
+            .get(&swap_order.src_chain)
+            .map_or(0, |config| config.min_confirmations);
+        self.get_chain_height(swap_order.src_chain.clone())
+            >= swap_order.src_block_height + min_confirmations as u64
+    }
+
+    fn assert_source_confirmed(&self, swap_order: &SwapOrder) {
+        require(
+            self.is_source_confirmed(swap_order),
+            ErrorCode::NotFinal,
+            "Source transaction not confirmed",
+        );
+    }
+
+    /// Only the resolver may complete an order before its public window opens
+    fn assert_exclusive_access(swap_order: &SwapOrder) {
+        if let Some(public_window_start) = swap_order.public_window_start {
+            if env::block_timestamp() < public_window_start {
+                require(
+                    env::predecessor_account_id() == swap_order.resolver,
+                    ErrorCode::Unauthorized,
+                    "Only resolver can complete before the public window",
+                );
+            }
+        }
+    }
+
+    fn internal_purge_order(&mut self, order_hash: &Base64VecU8) {
+        let swap_order = self
+            .swap_orders
+            .get(order_hash)
+            .expect("Order not found");
+
+        let caller = env::predecessor_account_id();
+        assert!(
+            caller == swap_order.resolver || caller == self.owner,
+            "Only resolver or owner can purge"
+        );
+        assert!(
+            matches!(swap_order.state, HTLCState::Completed | HTLCState::Refunded),
+            "Order not settled"
+        );
+        assert!(
+            env::block_timestamp() > swap_order.timelock,
+            "Timelock not passed"
+        );
+
+        self.swap_orders.remove(order_hash);
+        self.order_counts.remove(&swap_order.state);
+        self.deposits.remove(order_hash);
+        self.contributions.remove(order_hash);
+        self.order_ids.remove(&swap_order.order_id);
+        self.deactivate_order(order_hash);
+        self.release_storage(&swap_order.resolver, swap_order.storage_bytes);
+
+        self.emit_event(HtlcEvent::HtlcPurged(vec![HtlcPurgedData {
+            order_hash: order_hash.clone(),
+        }]));
+    }
+
+    /// Move an order to `state`, keeping `order_counts` in step. The caller
+    /// stores the order.
+    fn set_state(&mut self, swap_order: &mut SwapOrder, state: HTLCState) {
+        self.order_counts.remove(&swap_order.state);
+        self.order_counts.add(&state);
+        swap_order.state = state;
+    }
+
+    /// Keeper action currently applicable to an order, if any
+    fn keeper_action(&self, order: &SwapOrder) -> Option<KeeperAction> {
+        if order.state != HTLCState::Active {
+            return None;
+        }
+
+        let now = env::block_timestamp();
+        if now > order.timelock {
+            Some(KeeperAction::Refund)
+        } else if order.auto_complete_secret.is_some()
+            && now + self.auto_complete_window >= Self::completion_deadline(order)
+            && now <= Self::completion_deadline(order)
+        {
+            Some(KeeperAction::AutoComplete)
+        } else {
+            None
+        }
+    }
+
+    /// Complete an order, emit the event and pay the recipient (or its
+    /// `recipient_override`), resolving in `on_transfer_complete` with the
+    /// caller as executor. The executor earns `reward_bps` of the amount
+    /// besides any relayer fee. With `partial`, only that part of a Merkle
+    /// order is filled and paid, resolving in `on_partial_fill_transfer`.
+    fn internal_complete_and_pay(
+        &mut self,
+        order_hash: Base64VecU8,
+        secret: Base64VecU8,
+        recipient_override: Option<AccountId>,
+        min_payout: Option<U128>,
+        reward_bps: u16,
+        partial: Option<PartialFill>,
+    ) -> PromiseOrValue<bool> {
+        let executor = env::predecessor_account_id();
+        let order = self.swap_orders.get(&order_hash);
+        let relayed = recipient_override.is_none()
+            && order
+                .as_ref()
+                .is_some_and(|order| order.relayer_fee.0 > 0 && order.dst_recipient != executor);
+        let register = order.is_some_and(|order| order.auto_register_recipient);
+        // The callback may pay the executor in tokens, with its own callback
+        let callback_gas = if relayed || reward_bps > 0 {
+            self.gas_for_resolve + self.gas_for_resolve + self.gas_for_ft_transfer
+        } else {
+            self.gas_for_resolve
+        };
+        let mut payout_gas = callback_gas;
+        if register {
+            payout_gas += self.gas_for_ft_transfer;
+        }
+        self.assert_gas_for_payout(payout_gas);
+        let partial_fill = partial.is_some();
+        let (swap_order, amount) = match partial {
+            Some(fill) => self.internal_fill_part(&order_hash, &secret, fill),
+            None => self.internal_complete(&order_hash, &secret),
+        };
+        let fee = self.accrue_fee(&swap_order.dst_token, amount);
+        let relayer_fee = if relayed {
+            swap_order.relayer_fee.0.min(amount - fee)
+        } else {
+            0
+        };
+        let reward = amount * reward_bps as u128 / 10_000;
+        let recipient = recipient_override.unwrap_or_else(|| swap_order.dst_recipient.clone());
+
+        self.emit_event(HtlcEvent::HtlcCompleted(vec![HtlcCompletedData {
+            order_hash: order_hash.clone(),
+            secret,
+            dst_recipient: recipient.clone(),
+            amount: U128(amount),
+        }]));
+
+        // Pull mode: owe the recipient (and executor) instead of paying out
+        let executor_fee = relayer_fee + reward;
+        let net = (amount - fee - relayer_fee)
+            .checked_sub(reward)
+            .unwrap_or_else(|| fail(ErrorCode::InvalidAmount, "Keeper reward exceeds payout"));
+        require(
+            net >= min_payout.map_or(0, |min_payout| min_payout.0),
+            ErrorCode::PayoutBelowMinimum,
+            "Payout below minimum",
+        );
+        if !swap_order.push_on_complete {
+            self.credit_claimable(&recipient, &swap_order.token_id(), net);
+            self.credit_claimable(&executor, &swap_order.token_id(), executor_fee);
+            if swap_order.state == HTLCState::Completed {
+                self.pay_safety_deposit(&order_hash, executor);
+            }
+            return PromiseOrValue::Value(true);
+        }
+
+        // Transfer to recipient, net of the protocol and relayer fees. A
+        // recipient registration is skipped if the resolver's storage
+        // balance can't cover it.
+        let payout = if register
+            && swap_order.dst_asset == DstAsset::Ft
+            && self.take_storage_balance(&swap_order.resolver, self.storage_subsidy)
+        {
+            self.register_with_token(&swap_order.dst_token, recipient.clone())
+                .then(self.transfer_token(&swap_order, recipient, net))
+        } else {
+            self.transfer_token(&swap_order, recipient, net)
+        };
+        let callback = Self::ext(env::current_account_id()).with_static_gas(callback_gas);
+        let callback = if partial_fill {
+            callback.on_partial_fill_transfer(
+                order_hash,
+                U128(amount),
+                executor,
+                U128(executor_fee),
+                U128(fee),
+                swap_order.parts_filled - 1,
+            )
+        } else {
+            callback.on_transfer_complete(
+                order_hash,
+                U128(amount),
+                executor,
+                U128(executor_fee),
+                U128(fee),
+            )
+        };
+        PromiseOrValue::Promise(payout.then(callback))
+    }
+
+    /// Pay the executor its relayer fee and keeper reward in the order's
+    /// token. `on_claim_complete` credits it as claimable if the transfer
+    /// fails.
+    fn pay_executor_fee(&self, swap_order: &SwapOrder, executor: AccountId, amount: Balance) {
+        self.transfer_token(swap_order, executor.clone(), amount)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(self.gas_for_resolve)
+                    .on_claim_complete(executor, swap_order.dst_token.clone(), U128(amount)),
+            );
+    }
+
+    /// Pay a completed order's safety deposit (always native NEAR) to whoever
+    /// executed the completion
+    fn pay_safety_deposit(&mut self, order_hash: &Base64VecU8, executor: AccountId) {
+        let swap_order = self
+            .swap_orders
+            .get(order_hash)
+            .expect("Order not found");
+        if swap_order.safety_deposit.0 > 0 {
+            self.unlock(&TokenId::Native, swap_order.safety_deposit.0);
+            Promise::new(executor).transfer(swap_order.safety_deposit.0);
+        }
+    }
+
+    /// Check that enough gas is left for a token transfer followed by a
+    /// callback with `callback_gas`
+    fn assert_gas_for_payout(&self, callback_gas: Gas) {
+        require(
+            env::prepaid_gas() - env::used_gas() >= self.gas_for_ft_transfer + callback_gas,
+            ErrorCode::NotEnoughGas,
+            "Not enough gas attached",
+        );
+    }
+
+    fn transfer_succeeded() -> bool {
+        matches!(env::promise_result(0), PromiseResult::Successful(_))
+    }
+
+    /// Refund an order to its resolver (or refund address) and
+    /// contributors, paying `executor` the cleanup reward and safety deposit
+    /// when `cleanup`
+    fn internal_refund(
+        &mut self,
+        order_hash: Base64VecU8,
+        mut swap_order: SwapOrder,
+        initial_storage: StorageUsage,
+        executor: AccountId,
+        cleanup: bool,
+    ) {
+        // The refund itself has no callback, only the bridge notification
+        // and the cleanup reward
+        let mut extra_gas = if swap_order.refund_bridge.is_some() {
+            GAS_FOR_REFUND_MESSAGE
+        } else {
+            Gas(0)
+        };
+        if cleanup && self.cleanup_reward_bps > 0 {
+            extra_gas += self.gas_for_ft_transfer;
+        }
+        self.assert_gas_for_payout(extra_gas);
+
+        // Update state
+        self.set_state(&mut swap_order, HTLCState::Refunded);
+        self.deactivate_order(&order_hash);
+
+        // Get deposit amount
+        let amount = self.deposits.get(&order_hash).expect("Deposit not found");
+        self.deposits.remove(&order_hash);
+        self.unlock(&swap_order.token_id(), amount);
+        self.release_order_storage(&mut swap_order, initial_storage);
+        self.swap_orders.insert(&order_hash, &swap_order);
+        let contributions = self.contributions.remove(&order_hash).unwrap_or_default();
+        let contributed: Balance = contributions.iter().map(|(_, amount)| amount).sum();
+        self.total_refunded += 1;
+        self.update_resolver_stats(&swap_order.resolver, |stats| stats.refunded += 1);
+
+        // Refund the resolver's share to it or its refund address, less any
+        // cleanup reward. The safety deposit goes to whoever executes the
+        // refund, like it does on completion.
+        let refund_to = swap_order
+            .refund_to
+            .clone()
+            .unwrap_or_else(|| swap_order.resolver.clone());
+        let deposit_to = if cleanup {
+            executor.clone()
+        } else {
+            refund_to.clone()
+        };
+        let resolver_share = amount - contributed;
+        let reward = if cleanup {
+            (amount * self.cleanup_reward_bps as u128 / 10_000).min(resolver_share)
+        } else {
+            0
+        };
+        if reward > 0 {
+            self.transfer_token(&swap_order, executor, reward);
+        }
+        self.transfer_token(&swap_order, refund_to.clone(), resolver_share - reward);
+        for (contributor, contribution) in contributions {
+            self.transfer_token(&swap_order, contributor, contribution);
+        }
+        if swap_order.safety_deposit.0 > 0 {
+            self.unlock(&TokenId::Native, swap_order.safety_deposit.0);
+            Promise::new(deposit_to).transfer(swap_order.safety_deposit.0);
+        }
+
+        // Best-effort: a failed notification doesn't affect the local refund
+        if let Some(bridge) = swap_order.refund_bridge.clone() {
+            ext_refund_bridge::ext(bridge)
+                .with_static_gas(GAS_FOR_REFUND_MESSAGE)
+                .send_refund_message(order_hash.clone(), swap_order.src_chain.clone());
+        }
+
+        self.emit_event(HtlcEvent::HtlcRefunded(vec![HtlcRefundedData {
+            order_hash,
+            resolver: swap_order.resolver,
+            amount: U128(amount),
+        }]));
+    }
+
+    /// Return a completed order to `Active` with its deposit after a failed
+    /// payout, taking back the `fee` accrued for it and counting the attempt
+    fn restore_failed_completion(
+        &mut self,
+        order_hash: &Base64VecU8,
+        amount: Balance,
+        fee: Balance,
+    ) {
+        let mut swap_order = self
+            .swap_orders
+            .get(order_hash)
+            .expect("Order not found");
+
+        self.set_state(&mut swap_order, HTLCState::Active);
+        swap_order.completion_attempts = swap_order.completion_attempts.saturating_add(1);
+        self.swap_orders.insert(order_hash, &swap_order);
+        self.deposits.insert(order_hash, &amount);
+        self.activate_order(order_hash, amount);
+        self.lock(&swap_order.token_id(), amount);
+        self.unaccrue_fee(&swap_order.dst_token, fee);
+        self.total_completed -= 1;
+        self.add_volume(&swap_order.dst_token, -(amount as i128));
+        self.update_resolver_stats(&swap_order.resolver, |stats| {
+            stats.completed -= 1;
+            stats.total_volume = U128(stats.total_volume.0 - amount);
+        });
+
+        self.emit_event(HtlcEvent::HtlcCompletionFailed(vec![
+            HtlcCompletionFailedData {
+                order_hash: order_hash.clone(),
+                amount: U128(amount),
+            },
+        ]));
+    }
+
+    /// Apply `update` to a resolver's stats, starting from zero
+    fn update_resolver_stats<F: FnOnce(&mut ResolverStats)>(
+        &mut self,
+        resolver: &AccountId,
+        update: F,
+    ) {
+        let mut stats = self.resolver_stats.get(resolver).unwrap_or_default();
+        update(&mut stats);
+        self.resolver_stats.insert(resolver, &stats);
+    }
+
+    /// Count an amount entering escrow towards the locked balance of its
+    /// token, and native NEAR also towards `total_locked`. Token deposits
+    /// don't affect the contract's NEAR balance.
+    fn lock(&mut self, token: &TokenId, amount: Balance) {
+        if *token == TokenId::Native {
+            self.total_locked += amount;
+        }
+        let token = token.key();
+        let locked = self.locked_by_token.get(&token).unwrap_or(0);
+        self.locked_by_token.insert(&token, &(locked + amount));
+    }
+
+    /// Release an amount leaving escrow from the locked balances
+    fn unlock(&mut self, token: &TokenId, amount: Balance) {
+        if *token == TokenId::Native {
+            self.total_locked -= amount;
+        }
+        let token = token.key();
+        let locked = self.locked_by_token.get(&token).unwrap_or(0) - amount;
+        if locked == 0 {
+            self.locked_by_token.remove(&token);
+        } else {
+            self.locked_by_token.insert(&token, &locked);
+        }
+    }
+
+    /// NEAR that must stay on the account to cover its storage
+    fn storage_staking() -> Balance {
+        env::storage_usage() as Balance * env::storage_byte_cost()
+    }
+
+    /// NEAR the owner may move out: the balance not locked in orders, owed
+    /// to claimants or storage depositors, staked for storage or held as
+    /// `storage_reserve`
+    fn available_balance(&self) -> Balance {
+        env::account_balance()
+            .saturating_sub(self.total_locked)
+            .saturating_sub(self.total_claimable)
+            .saturating_sub(self.total_storage_available)
+            .saturating_sub(Self::storage_staking())
+            .saturating_sub(self.storage_reserve)
+    }
+
+    fn assert_available_balance(&self, amount: Balance) {
+        assert!(
+            amount <= self.available_balance(),
+            "Amount exceeds free balance"
+        );
+    }
+
+    /// Adjust the completed volume of a token, negative when a payout fails
+    fn add_volume(&mut self, token: &String, delta: i128) {
+        let volume = self.total_volume.get(token).unwrap_or(0) as i128 + delta;
+        self.total_volume.insert(token, &(volume as Balance));
+    }
+
+    /// Accrue the protocol fee on a payout of `amount`, returning the fee
+    fn accrue_fee(&mut self, token: &String, amount: Balance) -> Balance {
+        let fee = amount * self.fee_bps as u128 / 10_000;
+        if fee > 0 {
+            let collected = self.collected_fees.get(token).unwrap_or(0);
+            self.collected_fees.insert(token, &(collected + fee));
+        }
+        fee
+    }
+
+    /// Owe `amount` of `token` to `account_id` until it calls `claim`
+    fn credit_claimable(&mut self, account_id: &AccountId, token: &TokenId, amount: Balance) {
+        if amount == 0 {
+            return;
+        }
+        let key = (account_id.clone(), token.key());
+        let claimable = self.claimable.get(&key).unwrap_or(0);
+        self.claimable.insert(&key, &(claimable + amount));
+        if *token == TokenId::Native {
+            self.total_claimable += amount;
+        }
+    }
+
+    /// Take back a fee accrued for a payout that failed
+    fn unaccrue_fee(&mut self, token: &String, fee: Balance) {
+        if fee > 0 {
+            let collected = self.collected_fees.get(token).unwrap_or(0);
+            self.collected_fees
+                .insert(token, &collected.saturating_sub(fee));
+        }
+    }
+
+    /// Send an order's funds with the order hash as memo, routed by its
+    /// asset rather than by the `dst_token` name
+    fn transfer_token(
+        &self,
+        swap_order: &SwapOrder,
+        receiver_id: AccountId,
+        amount: Balance,
+    ) -> Promise {
+        let memo = Some(format!("htlc:{}", hex::encode(&swap_order.order_hash.0)));
+        match &swap_order.dst_asset {
+            DstAsset::Nft { contract, token_id } => ext_nft::ext(contract.clone())
+                .with_attached_deposit(1)
+                .with_static_gas(self.gas_for_ft_transfer)
+                .nft_transfer(receiver_id, token_id.clone(), None, memo),
+            DstAsset::Native | DstAsset::Ft => {
+                self.send_token(&swap_order.token_id(), receiver_id, amount, memo)
+            }
+        }
+    }
+
+    /// Send a token: native NEAR transfer, or NEP-141 `ft_transfer` to the
+    /// token contract
+    fn send_token(
+        &self,
+        token: &TokenId,
+        receiver_id: AccountId,
+        amount: Balance,
+        memo: Option<String>,
+    ) -> Promise {
+        match token {
+            TokenId::Native => Promise::new(receiver_id).transfer(amount),
+            TokenId::Ft(contract) => ext_ft::ext(contract.clone())
+                .with_attached_deposit(1)
+                .with_static_gas(self.gas_for_ft_transfer)
+                .ft_transfer(receiver_id, U128(amount), memo),
+        }
+    }
+
+    /// Register `account_id` with the `token` contract, attaching
+    /// `storage_subsidy`. Any excess is refunded to this contract.
+    fn register_with_token(&self, token: &str, account_id: AccountId) -> Promise {
+        let token: AccountId = token.parse().expect("Invalid token account");
+        ext_ft::ext(token)
+            .with_attached_deposit(self.storage_subsidy)
+            .with_static_gas(self.gas_for_ft_transfer)
+            .storage_deposit(Some(account_id), Some(true))
+    }
+
+    /// Index an order entering the `Active` state. The order must be stored.
+    fn activate_order(&mut self, order_hash: &Base64VecU8, amount: Balance) {
+        if self.active_order_hashes.insert(order_hash) {
+            if let Some(order) = self.swap_orders.get(order_hash) {
+                for hash_lock in order.hash_locks() {
+                    let count = self.active_hashlocks.get(hash_lock).unwrap_or(0);
+                    self.active_hashlocks.insert(hash_lock, &(count + 1));
+                }
+            }
+        }
+        self.track_largest_order(order_hash, amount);
+    }
+
+    /// Drop an order leaving the `Active` state from the active indexes
+    fn deactivate_order(&mut self, order_hash: &Base64VecU8) {
+        if self.active_order_hashes.remove(order_hash) {
+            if let Some(order) = self.swap_orders.get(order_hash) {
+                for hash_lock in order.hash_locks() {
+                    match self.active_hashlocks.get(hash_lock).unwrap_or(0) {
+                        0 | 1 => self.active_hashlocks.remove(hash_lock),
+                        count => self.active_hashlocks.insert(hash_lock, &(count - 1)),
+                    };
+                }
+            }
+        }
+        self.untrack_largest_order(order_hash);
+    }
+
+    /// Give a new order the next sequential id
+    fn assign_order_id(&mut self, swap_order: &mut SwapOrder) {
+        swap_order.order_id = self.next_order_id;
+        self.order_ids
+            .insert(&swap_order.order_id, &swap_order.order_hash);
+        self.next_order_id += 1;
+    }
+
+    /// Record a new order in the lookup indexes
+    fn index_order(&mut self, swap_order: &SwapOrder) {
+        Self::push_to_index(
+            &mut self.maker_orders,
+            b"M",
+            &swap_order.src_maker,
+            &swap_order.order_hash,
+        );
+        Self::push_to_index(
+            &mut self.recipient_orders,
+            b"R",
+            &swap_order.dst_recipient,
+            &swap_order.order_hash,
+        );
+        Self::push_to_index(
+            &mut self.chain_orders,
+            b"Q",
+            &swap_order.src_chain,
+            &swap_order.order_hash,
+        );
+    }
+
+    /// Drop a cancelled order from the lookup indexes
+    fn unindex_order(&mut self, swap_order: &SwapOrder) {
+        Self::remove_from_index(
+            &mut self.maker_orders,
+            &swap_order.src_maker,
+            &swap_order.order_hash,
+        );
+        Self::remove_from_index(
+            &mut self.recipient_orders,
+            &swap_order.dst_recipient,
+            &swap_order.order_hash,
+        );
+        Self::remove_from_index(
+            &mut self.chain_orders,
+            &swap_order.src_chain,
+            &swap_order.order_hash,
+        );
+    }
+
+    /// Remove an order hash from `key`'s list in a lookup index, shifting
+    /// later entries down so the list stays in creation order. The search
+    /// starts from the newest entry, where announced orders usually are.
+    fn remove_from_index<K: BorshSerialize + BorshDeserialize>(
+        index: &mut LookupMap<K, Vector<Base64VecU8>>,
+        key: &K,
+        order_hash: &Base64VecU8,
+    ) {
+        let mut order_hashes = match index.get(key) {
+            Some(order_hashes) => order_hashes,
+            None => return,
+        };
+        let position = match (0..order_hashes.len())
+            .rev()
+            .find(|&position| order_hashes.get(position).as_ref() == Some(order_hash))
+        {
+            Some(position) => position,
+            None => return,
+        };
+        for position in position..order_hashes.len() - 1 {
+            let next = order_hashes.get(position + 1).expect("Index entry missing");
+            order_hashes.replace(position, &next);
+        }
+        order_hashes.pop();
+        if order_hashes.is_empty() {
+            index.remove(key);
+        } else {
+            index.insert(key, &order_hashes);
+        }
+    }
+
+    /// Append an order hash to `key`'s list in a lookup index. Each list is
+    /// its own `Vector`, stored under `prefix` and a hash of the key.
+    fn push_to_index<K: BorshSerialize + BorshDeserialize>(
+        index: &mut LookupMap<K, Vector<Base64VecU8>>,
+        prefix: &[u8],
+        key: &K,
+        order_hash: &Base64VecU8,
+    ) {
+        let mut order_hashes = index.get(key).unwrap_or_else(|| {
+            let key_hash = env::sha256(&key.try_to_vec().expect("Failed to serialize key"));
+            Vector::new([prefix, key_hash.as_slice()].concat())
+        });
+        order_hashes.push(order_hash);
+        index.insert(key, &order_hashes);
+    }
+
+    /// Page through the orders of a lookup index list
+    fn orders_from_index(
+        &self,
+        order_hashes: Option<Vector<Base64VecU8>>,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Vec<SwapOrder> {
+        let order_hashes = match order_hashes {
+            Some(order_hashes) => order_hashes,
+            None => return Vec::new(),
+        };
+        let start = from_index.unwrap_or(0) as u64;
+        let limit = limit.unwrap_or(10) as u64;
+
+        let end = order_hashes.len().min(start + limit);
+        (start..end)
+            .filter_map(|index| order_hashes.get(index))
+            .filter_map(|order_hash| self.swap_orders.get(&order_hash))
+            .collect()
+    }
+
+    /// Orders in the active index, less disputed ones
+    fn active_orders(&self) -> impl Iterator<Item = SwapOrder> + '_ {
+        self.active_order_hashes
+            .iter()
+            .filter_map(|order_hash| self.swap_orders.get(&order_hash))
+            .filter(|order| order.state == HTLCState::Active)
+    }
+
+    /// Number of orders in the active index, less disputed ones, which keep
+    /// their place there until refunded
+    fn active_order_count(&self) -> u64 {
+        self.active_order_hashes
+            .len()
+            .saturating_sub(self.order_counts.disputed_count)
+    }
+
+    /// Index an active order by its deposit of `amount`, replacing any
+    /// earlier entry. Costs a logarithmic number of storage accesses.
+    fn track_largest_order(&mut self, order_hash: &Base64VecU8, amount: Balance) {
+        self.untrack_largest_order(order_hash);
+        let order_id = self
+            .swap_orders
+            .get(order_hash)
+            .map_or(0, |order| order.order_id);
+        let key = (amount, order_id);
+        self.active_orders_by_amount.insert(&key, order_hash);
+        self.active_order_amounts.insert(order_hash, &key);
+    }
+
+    /// Drop an order leaving the `Active` state from the amount index
+    fn untrack_largest_order(&mut self, order_hash: &Base64VecU8) {
+        if let Some(key) = self.active_order_amounts.remove(order_hash) {
+            self.active_orders_by_amount.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::{accounts, get_created_receipts, get_logs, VMContextBuilder};
+    use near_sdk::{testing_env, RuntimeFeesConfig, VMConfig};
+
+    const ONE_NEAR: Balance = 1_000_000_000_000_000_000_000_000;
+    const HOUR: Timestamp = 3_600_000_000_000;
+    const START: Timestamp = 1_700_000_000_000_000_000;
+
+    fn get_context(predecessor: AccountId) -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder.predecessor_account_id(predecessor);
+        builder
+    }
+
+    fn set_context(predecessor: AccountId, timestamp: Timestamp, deposit: Balance) {
+        let mut context = get_context(predecessor);
+        context.block_timestamp(timestamp).attached_deposit(deposit);
+        testing_env!(context.build());
+    }
+
+    fn contract_account() -> AccountId {
+        VMContextBuilder::new().context.current_account_id
+    }
+
+    /// Mock the result of the promise a callback is resolving, calling as the
+    /// contract itself
+    fn set_promise_result(result: PromiseResult) {
+        let mut context = get_context(contract_account());
+        context.block_timestamp(START + HOUR);
+        testing_env!(
+            context.build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result],
+        );
+    }
+
+    /// Storage balance the test resolver `accounts(1)` starts with
+    const RESOLVER_STORAGE: Balance = ONE_NEAR;
+
+    fn setup_contract() -> NEAREscrow {
+        set_context(accounts(0), START, 0);
+        let mut contract = NEAREscrow::new_with_chains(accounts(0), vec!["ethereum".to_string()]);
+
+        set_context(accounts(1), START, RESOLVER_STORAGE);
+        contract.storage_deposit(None, None);
+        set_context(accounts(0), START, 0);
+        contract
+    }
+
+    fn order_hash_for(id: u8) -> Base64VecU8 {
+        Base64VecU8(vec![id; 32])
+    }
+
+    /// 32-byte secret of order `id`
+    fn secret_for(id: u8) -> Base64VecU8 {
+        Base64VecU8(format!("secret-{:025}", id).into_bytes())
+    }
+
+    fn hash_lock_for(secret: &Base64VecU8) -> Base64VecU8 {
+        Base64VecU8(Sha256::digest(&secret.0).to_vec())
+    }
+
+    /// Order arguments for tests, defaulting to a plain NEAR order from
+    /// resolver `accounts(1)` to recipient `accounts(2)`
+    struct TestOrder {
+        id: u8,
+        amount: Balance,
+        src_chain: String,
+        dst_token: String,
+        hash_lock: Base64VecU8,
+        timelock: Timestamp,
+        complete_by: Option<Timestamp>,
+        refund_bridge: Option<AccountId>,
+        auto_complete_secret: Option<Base64VecU8>,
+        src_timelock: Option<Timestamp>,
+        hash_algorithm: Option<HashAlgorithm>,
+        safety_deposit: Option<U128>,
+        public_window_start: Option<Timestamp>,
+        timelocks: Option<Timelocks>,
+        merkle_root: Option<Base64VecU8>,
+        src_maker: String,
+        memo: Option<String>,
+        refund_to: Option<AccountId>,
+        relayer_fee: Option<U128>,
+        allow_recipient_override: Option<bool>,
+        src_block_height: Option<u64>,
+        alt_hash_locks: Option<Vec<Base64VecU8>>,
+        push_on_complete: Option<bool>,
+        auction: Option<AuctionConfig>,
+        parts: Option<u32>,
+    }
+
+    impl TestOrder {
+        fn new(id: u8, amount: Balance) -> Self {
+            Self {
+                id,
+                amount,
+                src_chain: "ethereum".to_string(),
+                dst_token: "NEAR".to_string(),
+                hash_lock: hash_lock_for(&secret_for(id)),
+                timelock: START + 2 * HOUR,
+                complete_by: None,
+                refund_bridge: None,
+                auto_complete_secret: None,
+                src_timelock: None,
+                hash_algorithm: None,
+                safety_deposit: None,
+                public_window_start: None,
+                timelocks: None,
+                merkle_root: None,
+                src_maker: "0xmaker".to_string(),
+                memo: None,
+                refund_to: None,
+                relayer_fee: None,
+                allow_recipient_override: None,
+                src_block_height: None,
+                alt_hash_locks: None,
+                push_on_complete: None,
+                auction: None,
+                parts: None,
+            }
+        }
+    }
+
+    fn create_test_order(contract: &mut NEAREscrow, order: TestOrder) -> Base64VecU8 {
+        set_context(accounts(1), START, order.amount);
+        contract.create_htlc(CreateHtlcArgs {
+            order_hash: order_hash_for(order.id),
+            src_maker: order.src_maker,
+            src_chain: order.src_chain,
+            src_token: "ETH".to_string(),
+            src_amount: U128(1),
+            dst_recipient: accounts(2),
+            dst_token: order.dst_token,
+            hash_lock: order.hash_lock,
+            timelock: order.timelock,
+            complete_by: order.complete_by,
+            refund_bridge: order.refund_bridge,
+            auto_complete_secret: order.auto_complete_secret,
+            src_timelock: order.src_timelock,
+            hash_algorithm: order.hash_algorithm,
+            safety_deposit: order.safety_deposit,
+            public_window_start: order.public_window_start,
+            timelocks: order.timelocks,
+            merkle_root: order.merkle_root,
+            memo: order.memo,
+            refund_to: order.refund_to,
+            relayer_fee: order.relayer_fee,
+            allow_recipient_override: order.allow_recipient_override,
+            src_block_height: order.src_block_height,
+            alt_hash_locks: order.alt_hash_locks,
+            push_on_complete: order.push_on_complete,
+            auction: order.auction,
+            parts: order.parts,
+            amount: U128(order.amount),
+        });
+        order_hash_for(order.id)
+    }
+
+    fn create_order(contract: &mut NEAREscrow, id: u8, amount: Balance) -> Base64VecU8 {
+        create_test_order(contract, TestOrder::new(id, amount))
+    }
+
+    fn complete_order(contract: &mut NEAREscrow, id: u8) {
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc(order_hash_for(id), secret_for(id), None, None);
+    }
+
+    fn refund_order(contract: &mut NEAREscrow, id: u8) {
+        set_context(accounts(1), START + 3 * HOUR, 0);
+        contract.refund_htlc(order_hash_for(id));
+    }
+
+    fn largest_amounts(contract: &NEAREscrow) -> Vec<u128> {
+        contract
+            .get_largest_active_orders(None)
+            .iter()
+            .map(|order| order.dst_amount.0)
+            .collect()
+    }
+
+    #[test]
+    fn test_contract_creation() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+
+        let contract = NEAREscrow::new(accounts(0));
+        assert_eq!(contract.get_owner(), accounts(0));
+    }
+
+    #[test]
+    fn test_new_with_chains_from_factory() {
+        // Deployed by a factory on behalf of another owner
+        set_context(accounts(5), START, 0);
+        let contract = NEAREscrow::new_with_chains(accounts(0), vec!["ethereum".to_string()]);
+
+        assert_eq!(contract.get_owner(), accounts(0));
+        assert!(contract.is_chain_supported("ethereum".to_string()));
+        assert_eq!(
+            contract.get_chain_config("ethereum".to_string()),
+            Some(ChainConfig {
+                chain_id: 0,
+                min_confirmations: 0,
+                enabled: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_secret() {
+        let context = get_context(accounts(0));
+        testing_env!(context.build());
+
+        let contract = NEAREscrow::new(accounts(0));
+        let secret = Base64VecU8(b"test_secret".to_vec());
+        let hash = Sha256::digest(&secret.0);
+        let hash_lock = Base64VecU8(hash.to_vec());
+
+        assert!(contract.verify_secret(secret, hash_lock, None));
+    }
+
+    /// Order 1 of one NEAR, completable only until `complete_by`
+    fn create_complete_by_order(contract: &mut NEAREscrow, complete_by: Timestamp) {
+        create_test_order(
+            contract,
+            TestOrder {
+                complete_by: Some(complete_by),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+    }
+
+    fn purchase_extension(contract: &mut NEAREscrow, new_complete_by: Timestamp, fee: Balance) {
+        set_context(accounts(1), START + HOUR / 2, fee);
+        contract.purchase_completion_extension(order_hash_for(1), new_complete_by);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ORDER_EXPIRED: Completion window closed")]
+    fn test_completion_blocked_after_complete_by() {
+        let mut contract = setup_contract();
+        create_complete_by_order(&mut contract, START + HOUR);
+        set_context(accounts(2), START + HOUR + 1, 0);
+        contract.complete_htlc(order_hash_for(1), secret_for(1), None, None);
+    }
+
+    #[test]
+    fn test_purchase_completion_extension_collects_fee() {
+        let mut contract = setup_contract();
+        create_complete_by_order(&mut contract, START + HOUR);
+        set_context(accounts(0), START, 0);
+        contract.set_completion_extension_fee(U128(ONE_NEAR / 10));
+
+        purchase_extension(&mut contract, START + 3 * HOUR / 2, ONE_NEAR / 10);
+        assert_eq!(
+            contract
+                .get_swap_order(order_hash_for(1))
+                .unwrap()
+                .complete_by,
+            Some(START + 3 * HOUR / 2)
+        );
+        assert_eq!(
+            contract.get_collected_fees("NEAR".to_string()),
+            U128(ONE_NEAR / 10)
+        );
+        assert_eq!(
+            emitted_events().pop().unwrap().event,
+            HtlcEvent::HtlcCompletionExtended(vec![HtlcCompletionExtendedData {
+                order_hash: order_hash_for(1),
+                old_complete_by: START + HOUR,
+                new_complete_by: START + 3 * HOUR / 2,
+                fee: U128(ONE_NEAR / 10),
+            }])
+        );
+
+        // Completable past the original deadline, the timelock unchanged
+        set_context(accounts(2), START + 5 * HOUR / 4, 0);
+        contract.complete_htlc(order_hash_for(1), secret_for(1), None, None);
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.state, HTLCState::Completed);
+        assert_eq!(order.timelock, START + 2 * HOUR);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attach exactly the extension fee")]
+    fn test_purchase_completion_extension_requires_fee() {
+        let mut contract = setup_contract();
+        create_complete_by_order(&mut contract, START + HOUR);
+        set_context(accounts(0), START, 0);
+        contract.set_completion_extension_fee(U128(ONE_NEAR / 10));
+        purchase_extension(&mut contract, START + 3 * HOUR / 2, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Completion deadline past timelock")]
+    fn test_purchase_completion_extension_capped_at_timelock() {
+        let mut contract = setup_contract();
+        create_complete_by_order(&mut contract, START + HOUR);
+        purchase_extension(&mut contract, START + 2 * HOUR + 1, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "New completion deadline must be later")]
+    fn test_purchase_completion_extension_must_extend() {
+        let mut contract = setup_contract();
+        create_complete_by_order(&mut contract, START + HOUR);
+        purchase_extension(&mut contract, START + HOUR, 0);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "E_INVALID_TIMELOCK: Completion deadline must be between now and the timelock"
+    )]
+    fn test_complete_by_after_timelock_rejected() {
+        let mut contract = setup_contract();
+        create_complete_by_order(&mut contract, START + 3 * HOUR);
+    }
+
+    #[test]
+    fn test_largest_active_orders_sorted_and_bounded() {
+        let mut contract = setup_contract();
+        for id in 1..=12u8 {
+            create_order(&mut contract, id, id as u128 * ONE_NEAR);
+        }
+
+        let expected: Vec<u128> = (3..=12u128).rev().map(|n| n * ONE_NEAR).collect();
+        assert_eq!(largest_amounts(&contract), expected);
+        assert_eq!(contract.get_largest_active_orders(Some(2)).len(), 2);
+    }
+
+    #[test]
+    fn test_largest_active_orders_follow_settlement() {
+        let mut contract = setup_contract();
+        for id in 1..=12u8 {
+            create_order(&mut contract, id, id as u128 * ONE_NEAR);
+        }
+
+        complete_order(&mut contract, 12);
+        refund_order(&mut contract, 11);
+
+        // Freed slots are refilled from the next largest active orders
+        let expected: Vec<u128> = (1..=10u128).rev().map(|n| n * ONE_NEAR).collect();
+        assert_eq!(largest_amounts(&contract), expected);
+
+        // With no untracked active orders left, the list simply shrinks
+        refund_order(&mut contract, 1);
+        assert_eq!(largest_amounts(&contract), expected[..9].to_vec());
+
+        create_order(&mut contract, 20, 20 * ONE_NEAR);
+        assert_eq!(largest_amounts(&contract)[0], 20 * ONE_NEAR);
+    }
+
+    #[test]
+    fn test_largest_active_orders_beyond_default_limit() {
+        let mut contract = setup_contract();
+        // Amounts of 1 to 25 NEAR, shuffled across ids
+        for id in 1..=25u8 {
+            create_order(&mut contract, id, (id as u128 * 7 % 25 + 1) * ONE_NEAR);
+        }
+        // Settle the five largest, so a top ten is refilled many times over
+        for id in [7, 14, 21, 3, 10] {
+            if id % 2 == 0 {
+                complete_order(&mut contract, id);
+            } else {
+                refund_order(&mut contract, id);
+            }
+        }
+
+        let mut active: Vec<u128> = contract
+            .get_active_orders(None, Some(25))
+            .iter()
+            .map(|order| order.dst_amount.0)
+            .collect();
+        active.sort_unstable_by(|a, b| b.cmp(a));
+        assert_eq!(active.len(), 20);
+        assert_eq!(active[0], 20 * ONE_NEAR);
+        assert_eq!(largest_amounts(&contract), active[..10].to_vec());
+        let all: Vec<u128> = contract
+            .get_largest_active_orders(Some(25))
+            .iter()
+            .map(|order| order.dst_amount.0)
+            .collect();
+        assert_eq!(all, active);
+    }
+
+    fn created_function_calls() -> Vec<(AccountId, String)> {
+        get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id.clone();
+                receipt
+                    .actions
+                    .into_iter()
+                    .filter_map(move |action| match action {
+                        VmAction::FunctionCall { function_name, .. } => {
+                            Some((receiver_id.clone(), function_name))
+                        }
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_refund_notifies_bridge_when_configured() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                refund_bridge: Some(accounts(3)),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+
+        refund_order(&mut contract, 1);
+        assert_eq!(
+            created_function_calls(),
+            vec![(accounts(3), "send_refund_message".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_refund_without_bridge_sends_no_message() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        refund_order(&mut contract, 1);
+        assert!(created_function_calls().is_empty());
+    }
+
+    fn created_transfers() -> Vec<(AccountId, Balance)> {
+        get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id.clone();
+                receipt
+                    .actions
+                    .into_iter()
+                    .filter_map(move |action| match action {
+                        VmAction::Transfer { deposit } => Some((receiver_id.clone(), deposit)),
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
+    fn create_auto_complete_order(contract: &mut NEAREscrow, id: u8, amount: Balance) {
+        create_test_order(
+            contract,
+            TestOrder {
+                auto_complete_secret: Some(secret_for(id)),
+                ..TestOrder::new(id, amount)
+            },
+        );
+    }
+
+    #[test]
+    fn test_auto_complete_pays_keeper_reward() {
+        let mut contract = setup_contract();
+        create_auto_complete_order(&mut contract, 1, 10 * ONE_NEAR);
+
+        // Within 10 minutes of the timelock
+        set_context(accounts(3), START + 2 * HOUR - 300_000_000_000, 0);
+        contract.auto_complete(order_hash_for(1));
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.state, HTLCState::Completed);
+        assert_eq!(
+            created_transfers(),
+            vec![(accounts(2), 10 * ONE_NEAR - ONE_NEAR / 100)]
+        );
+
+        // The keeper is paid once the payout resolves
+        set_promise_result(PromiseResult::Successful(vec![]));
+        assert!(contract.on_transfer_complete(
+            order_hash_for(1),
+            U128(10 * ONE_NEAR),
+            accounts(3),
+            U128(ONE_NEAR / 100),
+            U128(0)
+        ));
+        assert_eq!(created_transfers(), vec![(accounts(3), ONE_NEAR / 100)]);
+    }
+
+    #[test]
+    fn test_auto_complete_pull_mode_credits_recipient_and_keeper() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                auto_complete_secret: Some(secret_for(1)),
+                push_on_complete: Some(false),
+                ..TestOrder::new(1, 10 * ONE_NEAR)
+            },
+        );
+
+        set_context(accounts(3), START + 2 * HOUR - 300_000_000_000, 0);
+        contract.auto_complete(order_hash_for(1));
+
+        assert_eq!(order_state(&contract, 1), Some(HTLCState::Completed));
+        assert!(created_transfers().is_empty());
+        assert_eq!(
+            contract.get_claimable(accounts(2), "NEAR".to_string()),
+            U128(10 * ONE_NEAR - ONE_NEAR / 100)
+        );
+        assert_eq!(
+            contract.get_claimable(accounts(3), "NEAR".to_string()),
+            U128(ONE_NEAR / 100)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_AMOUNT: Keeper reward exceeds payout")]
+    fn test_auto_complete_reward_cannot_underflow_payout() {
+        let mut contract = setup_contract();
+        create_auto_complete_order(&mut contract, 1, 10 * ONE_NEAR);
+        // The protocol fee takes the whole amount, leaving nothing for the keeper
+        set_fee(&mut contract, 10_000);
+
+        set_context(accounts(3), START + 2 * HOUR - 300_000_000_000, 0);
+        contract.auto_complete(order_hash_for(1));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "E_UNAUTHORIZED: Only resolver can complete before the public window"
+    )]
+    fn test_auto_complete_respects_exclusive_window() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                auto_complete_secret: Some(secret_for(1)),
+                public_window_start: Some(START + 2 * HOUR - MINUTE),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+
+        set_context(accounts(3), START + 2 * HOUR - 5 * MINUTE, 0);
+        contract.auto_complete(order_hash_for(1));
+    }
+
+    #[test]
+    fn test_auto_complete_secret_hidden_from_views() {
+        let mut contract = setup_contract();
+        create_auto_complete_order(&mut contract, 1, ONE_NEAR);
+
+        let json = serde_json::to_string(&contract.get_swap_order(order_hash_for(1))).unwrap();
+        assert!(!json.contains("auto_complete_secret"));
+        let bytes = contract.get_order_borsh(order_hash_for(1)).unwrap();
+        let order = SwapOrder::try_from_slice(&bytes.0).unwrap();
+        assert_eq!(order.auto_complete_secret, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Auto-complete window not open")]
+    fn test_auto_complete_before_window() {
+        let mut contract = setup_contract();
+        create_auto_complete_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(3), START + HOUR, 0);
+        contract.auto_complete(order_hash_for(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "No auto-complete secret")]
+    fn test_auto_complete_requires_stored_secret() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(3), START + 2 * HOUR, 0);
+        contract.auto_complete(order_hash_for(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_SECRET: Auto-complete secret does not match hash lock")]
+    fn test_auto_complete_secret_must_match_hash_lock() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                auto_complete_secret: Some(secret_for(2)),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+    }
+
+    #[test]
+    fn test_keeper_worklist_mixed_tasks() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        create_auto_complete_order(&mut contract, 2, ONE_NEAR);
+        create_order(&mut contract, 3, ONE_NEAR);
+        create_order(&mut contract, 4, ONE_NEAR);
+        complete_order(&mut contract, 4);
+
+        // Orders 1 and 3 still live, order 2 inside its auto-complete window
+        set_context(accounts(3), START + 2 * HOUR - 60_000_000_000, 0);
+        let tasks = contract.get_keeper_worklist(None, None);
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].order_hash, order_hash_for(2));
+        assert_eq!(tasks[0].action, KeeperAction::AutoComplete);
+
+        // Past the timelock every unsettled order needs a refund
+        set_context(accounts(3), START + 3 * HOUR, 0);
+        let tasks = contract.get_keeper_worklist(None, None);
+        assert_eq!(tasks.len(), 3);
+        assert!(tasks.iter().all(|task| task.action == KeeperAction::Refund));
+        assert_eq!(contract.get_keeper_worklist(Some(1), Some(1)).len(), 1);
+    }
+
+    fn create_order_with_src_timelock(contract: &mut NEAREscrow, src_timelock: Timestamp) {
+        create_test_order(
+            contract,
+            TestOrder {
+                src_timelock: Some(src_timelock),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+    }
+
+    #[test]
+    fn test_timelock_ordered_before_source_timelock() {
+        let mut contract = setup_contract();
+        create_order_with_src_timelock(&mut contract, START + 4 * HOUR);
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.src_timelock, Some(START + 4 * HOUR));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_TIMELOCK: Timelock too close to source timelock")]
+    fn test_timelock_too_close_to_source_timelock() {
+        let mut contract = setup_contract();
+        create_order_with_src_timelock(&mut contract, START + 2 * HOUR + HOUR / 2);
+    }
+
+    #[test]
+    fn test_timelock_safety_margin_is_configurable() {
+        let mut contract = setup_contract();
+        contract.update_timelock_safety_margin(HOUR / 4);
+        assert_eq!(contract.get_timelock_safety_margin(), HOUR / 4);
+
+        create_order_with_src_timelock(&mut contract, START + 2 * HOUR + HOUR / 2);
+        assert!(contract.get_swap_order(order_hash_for(1)).is_some());
+    }
+
+    fn token_account() -> AccountId {
+        "usdc.testnet".parse().unwrap()
+    }
+
+    #[test]
+    fn test_complete_ft_order_calls_ft_transfer() {
+        let mut contract = setup_contract();
+        ft_on_transfer(&mut contract, 500, ft_message(1, "ethereum"));
+
+        complete_order(&mut contract, 1);
+
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].receiver_id, token_account());
+        match &receipts[0].actions[0] {
+            VmAction::FunctionCall {
+                function_name,
+                args,
+                gas,
+                deposit,
+            } => {
+                assert_eq!(function_name, "ft_transfer");
+                assert_eq!(*deposit, 1);
+                assert_eq!(*gas, GAS_FOR_FT_TRANSFER);
+                let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                assert_eq!(args["receiver_id"], accounts(2).to_string());
+                assert_eq!(args["amount"], "500");
+                assert_eq!(
+                    args["memo"],
+                    format!("htlc:{}", hex::encode(&order_hash_for(1).0))
+                );
+            }
+            action => panic!("Unexpected action {:?}", action),
+        }
+    }
+
+    #[test]
+    fn test_failed_ft_payout_keeps_tokens_locked() {
+        let mut contract = setup_contract();
+        ft_on_transfer(&mut contract, 500, ft_message(1, "ethereum"));
+        complete_order(&mut contract, 1);
+        assert_locked(&contract, token_account().as_str(), 0);
+
+        set_promise_result(PromiseResult::Failed);
+        assert!(!contract.on_transfer_complete(
+            order_hash_for(1),
+            U128(500),
+            accounts(2),
+            U128(0),
+            U128(0)
+        ));
+        assert_eq!(order_state(&contract, 1), Some(HTLCState::Active));
+        assert_locked(&contract, token_account().as_str(), 500);
+
+        refund_order(&mut contract, 1);
+        assert_eq!(
+            created_function_calls(),
+            vec![(token_account(), "ft_transfer".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_complete_native_order_transfers_near() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        complete_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(2), ONE_NEAR)]);
+        assert_eq!(
+            created_function_calls(),
+            vec![(contract_account(), "on_transfer_complete".to_string())]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_TOKEN: Only native NEAR can be attached")]
+    fn test_create_htlc_rejects_token_orders() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                dst_token: token_account().to_string(),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+    }
+
+    fn ft_message(id: u8, src_chain: &str) -> String {
+        serde_json::json!({
+            "order_hash": order_hash_for(id),
+            "src_maker": "0xmaker",
+            "src_chain": src_chain,
+            "dst_recipient": accounts(2),
+            "hash_lock": hash_lock_for(&secret_for(id)),
+            "timelock": START + 2 * HOUR,
+        })
+        .to_string()
+    }
+
+    fn ft_on_transfer(contract: &mut NEAREscrow, amount: u128, msg: String) -> U128 {
+        set_context(token_account(), START, 0);
+        match contract.ft_on_transfer(accounts(1), U128(amount), msg) {
+            PromiseOrValue::Value(unused) => unused,
+            PromiseOrValue::Promise(_) => panic!("Expected a value"),
+        }
+    }
+
+    #[test]
+    fn test_ft_on_transfer_creates_token_order() {
+        let mut contract = setup_contract();
+        let unused = ft_on_transfer(&mut contract, 500, ft_message(1, "ethereum"));
+        assert_eq!(unused, U128(0));
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.dst_token, token_account().to_string());
+        assert_eq!(order.dst_amount, U128(500));
+        assert_eq!(order.resolver, accounts(1));
+        assert_eq!(order.state, HTLCState::Active);
+    }
+
+    #[test]
+    fn test_ft_on_transfer_reads_optional_fields() {
+        let mut contract = setup_contract();
+        let msg = serde_json::json!({
+            "order_hash": order_hash_for(1),
+            "src_maker": "0xmaker",
+            "src_chain": "ethereum",
+            "src_token": "USDC",
+            "src_amount": "500",
+            "dst_recipient": accounts(2),
+            "hash_lock": hash_lock_for(&secret_for(1)),
+            "timelock": START + 2 * HOUR,
+            "memo": "swap 42",
+            "refund_to": accounts(3),
+            "relayer_fee": "5",
+        })
+        .to_string();
+        ft_on_transfer(&mut contract, 500, msg);
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.src_token, "USDC");
+        assert_eq!(order.src_amount, U128(500));
+        assert_eq!(order.memo, Some("swap 42".to_string()));
+        assert_eq!(order.refund_to, Some(accounts(3)));
+        assert_eq!(order.relayer_fee, U128(5));
+        assert_eq!(order.dst_asset, DstAsset::Ft);
+    }
+
+    #[test]
+    fn test_ft_on_transfer_returns_tokens_on_bad_message() {
+        let mut contract = setup_contract();
+        let unused = ft_on_transfer(&mut contract, 500, "not json".to_string());
+        assert_eq!(unused, U128(500));
+
+        let unused = ft_on_transfer(&mut contract, 500, ft_message(1, "bitcoin"));
+        assert_eq!(unused, U128(500));
+        assert!(contract.get_swap_order(order_hash_for(1)).is_none());
+    }
+
+    #[test]
+    fn test_refund_ft_order_returns_tokens_to_resolver() {
+        let mut contract = setup_contract();
+        ft_on_transfer(&mut contract, 500, ft_message(1, "ethereum"));
+
+        refund_order(&mut contract, 1);
+        assert_eq!(
+            created_function_calls(),
+            vec![(token_account(), "ft_transfer".to_string())]
+        );
+        assert!(created_transfers().is_empty());
+    }
+
+    #[test]
+    fn test_failed_completion_restores_deposit() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        complete_order(&mut contract, 1);
+        assert!(!contract.deposits.contains_key(&order_hash_for(1)));
+
+        set_promise_result(PromiseResult::Failed);
+        assert!(!contract.on_transfer_complete(
+            order_hash_for(1),
+            U128(ONE_NEAR),
+            accounts(2),
+            U128(0),
+            U128(0)
+        ));
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.state, HTLCState::Active);
+        assert_eq!(contract.deposits.get(&order_hash_for(1)), Some(ONE_NEAR));
+
+        // The restored order can still be refunded after expiry
+        refund_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(1), ONE_NEAR)]);
+    }
+
+    #[test]
+    fn test_successful_completion_keeps_order_completed() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        complete_order(&mut contract, 1);
+
+        set_promise_result(PromiseResult::Successful(vec![]));
+        assert!(contract.on_transfer_complete(
+            order_hash_for(1),
+            U128(ONE_NEAR),
+            accounts(2),
+            U128(0),
+            U128(0)
+        ));
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.state, HTLCState::Completed);
+        assert!(!contract.deposits.contains_key(&order_hash_for(1)));
+    }
+
+    #[test]
+    fn test_get_active_orders_filters_before_paginating() {
+        let mut contract = setup_contract();
+        for id in 1..=7u8 {
+            create_order(&mut contract, id, ONE_NEAR);
+        }
+        for id in 1..=4u8 {
+            complete_order(&mut contract, id);
+        }
+        for id in 5..=7u8 {
+            refund_order(&mut contract, id);
+        }
+        for id in 8..=19u8 {
+            create_order(&mut contract, id, ONE_NEAR);
+        }
+
+        let page = contract.get_active_orders(None, Some(10));
+        assert_eq!(page.len(), 10);
+        assert!(page.iter().all(|order| order.state == HTLCState::Active));
+
+        let rest = contract.get_active_orders(Some(10), Some(10));
+        assert_eq!(rest.len(), 2);
+    }
+
+    #[test]
+    fn test_active_order_index_tracks_settlement() {
+        let mut contract = setup_contract();
+        for id in 1..=3u8 {
+            create_order(&mut contract, id, ONE_NEAR);
+        }
+        assert_eq!(contract.active_order_hashes.len(), 3);
+
+        complete_order(&mut contract, 1);
+        refund_order(&mut contract, 2);
+        assert_eq!(
+            contract.active_order_hashes.to_vec(),
+            vec![order_hash_for(3)]
+        );
+
+        // A failed payout puts the order back in the index
+        set_promise_result(PromiseResult::Failed);
+        contract.on_transfer_complete(
+            order_hash_for(1),
+            U128(ONE_NEAR),
+            accounts(2),
+            U128(0),
+            U128(0),
+        );
+        assert!(contract.active_order_hashes.contains(&order_hash_for(1)));
+
+        let active: Vec<Base64VecU8> = contract
+            .get_active_orders(None, None)
+            .into_iter()
+            .map(|order| order.order_hash)
+            .collect();
+        assert_eq!(active.len(), 2);
+        assert!(active.contains(&order_hash_for(1)));
+        assert!(active.contains(&order_hash_for(3)));
+    }
+
+    /// keccak256("hello")
+    const HELLO_KECCAK256: &str =
+        "1c8aff950685c2ed4bc3174f3472287b56d9517b9c948127319a09a7a36deac8";
+
+    #[test]
+    fn test_verify_secret_keccak256() {
+        let contract = setup_contract();
+        let secret = Base64VecU8(b"hello".to_vec());
+        let hash_lock = Base64VecU8(hex::decode(HELLO_KECCAK256).unwrap());
+
+        assert!(contract.verify_secret(
+            secret.clone(),
+            hash_lock.clone(),
+            Some(HashAlgorithm::Keccak256)
+        ));
+        assert!(!contract.verify_secret(secret, hash_lock, None));
+    }
+
+    /// blake2b-256("hello")
+    const HELLO_BLAKE2B256: &str =
+        "324dcf027dd4a30a932c441f365a25e86b173defa4b8e58948253471b81b72cf";
+
+    /// ripemd160(sha256("hello"))
+    const HELLO_HASH160: &str = "b6a9c8c230722b7c748331a8b450f05566dc7d0f";
+
+    #[test]
+    fn test_verify_secret_blake2b256_and_hash160() {
+        let contract = setup_contract();
+        let secret = Base64VecU8(b"hello".to_vec());
+        let blake2b_lock = Base64VecU8(hex::decode(HELLO_BLAKE2B256).unwrap());
+        let hash160_lock = Base64VecU8(hex::decode(HELLO_HASH160).unwrap());
+
+        assert!(contract.verify_secret(
+            secret.clone(),
+            blake2b_lock.clone(),
+            Some(HashAlgorithm::Blake2b256)
+        ));
+        assert!(contract.verify_secret(
+            secret.clone(),
+            hash160_lock.clone(),
+            Some(HashAlgorithm::Hash160)
+        ));
+        assert!(!contract.verify_secret(
+            secret.clone(),
+            blake2b_lock,
+            Some(HashAlgorithm::Keccak256)
+        ));
+        assert!(!contract.verify_secret(secret, hash160_lock, None));
+    }
+
+    #[test]
+    fn test_complete_hash160_order() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                hash_lock: Base64VecU8(hex::decode(HELLO_HASH160).unwrap()),
+                hash_algorithm: Some(HashAlgorithm::Hash160),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+
+        set_context(accounts(0), START, 0);
+        contract.set_min_secret_length(5);
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc(
+            order_hash_for(1),
+            Base64VecU8(b"hello".to_vec()),
+            None,
+            None,
+        );
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.hash_algorithm, HashAlgorithm::Hash160);
+        assert_eq!(order.state, HTLCState::Completed);
+    }
+
+    #[test]
+    fn test_complete_keccak256_order() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                hash_lock: Base64VecU8(hex::decode(HELLO_KECCAK256).unwrap()),
+                hash_algorithm: Some(HashAlgorithm::Keccak256),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+
+        // The known preimage is shorter than the default minimum
+        set_context(accounts(0), START, 0);
+        contract.set_min_secret_length(5);
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc(
+            order_hash_for(1),
+            Base64VecU8(b"hello".to_vec()),
+            None,
+            None,
+        );
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.hash_algorithm, HashAlgorithm::Keccak256);
+        assert_eq!(order.state, HTLCState::Completed);
+    }
+
+    #[test]
+    fn test_orders_default_to_sha256() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.hash_algorithm, HashAlgorithm::Sha256);
+    }
+
+    fn emitted_events() -> Vec<EventLog> {
+        get_logs()
+            .iter()
+            .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+            .map(|json| serde_json::from_str(json).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_lifecycle_events() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        let events = emitted_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].standard, "htlc");
+        assert_eq!(events[0].version, "1.0.0");
+        assert_eq!(
+            events[0].event,
+            HtlcEvent::HtlcCreated(vec![HtlcCreatedData {
+                order_hash: order_hash_for(1),
+                resolver: accounts(1),
+                dst_recipient: accounts(2),
+                dst_token: "NEAR".to_string(),
+                dst_amount: U128(ONE_NEAR),
+                hash_lock: hash_lock_for(&secret_for(1)),
+                timelock: START + 2 * HOUR,
+                memo: None,
+            }])
+        );
+
+        complete_order(&mut contract, 1);
+        assert_eq!(
+            emitted_events()[0].event,
+            HtlcEvent::HtlcCompleted(vec![HtlcCompletedData {
+                order_hash: order_hash_for(1),
+                secret: secret_for(1),
+                dst_recipient: accounts(2),
+                amount: U128(ONE_NEAR),
+            }])
+        );
+
+        create_order(&mut contract, 2, ONE_NEAR);
+        refund_order(&mut contract, 2);
+        assert_eq!(
+            emitted_events()[0].event,
+            HtlcEvent::HtlcRefunded(vec![HtlcRefundedData {
+                order_hash: order_hash_for(2),
+                resolver: accounts(1),
+                amount: U128(ONE_NEAR),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_event_json_format() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        refund_order(&mut contract, 1);
+
+        let log = get_logs().pop().unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(log.strip_prefix("EVENT_JSON:").unwrap()).unwrap();
+        assert_eq!(json["standard"], "htlc");
+        assert_eq!(json["event"], "htlc_refunded");
+        assert_eq!(json["data"][0]["amount"], ONE_NEAR.to_string());
+        assert_eq!(
+            json["data"][0]["order_hash"],
+            serde_json::to_value(order_hash_for(1)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_revealed_secret_recorded_on_completion() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        assert_eq!(contract.get_revealed_secret(order_hash_for(1)), None);
+
+        complete_order(&mut contract, 1);
+        assert_eq!(
+            contract.get_revealed_secret(order_hash_for(1)),
+            Some(secret_for(1))
+        );
+        assert_eq!(contract.get_revealed_secret(order_hash_for(2)), None);
+    }
+
+    /// 10 NEAR order carrying a 1 NEAR safety deposit, public after one hour
+    fn create_safety_deposit_order(contract: &mut NEAREscrow) {
+        create_test_order(
+            contract,
+            TestOrder {
+                safety_deposit: Some(U128(ONE_NEAR)),
+                public_window_start: Some(START + HOUR),
+                ..TestOrder::new(1, 11 * ONE_NEAR)
+            },
+        );
+    }
+
+    #[test]
+    fn test_safety_deposit_split_from_attached_deposit() {
+        let mut contract = setup_contract();
+        create_safety_deposit_order(&mut contract);
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.dst_amount, U128(10 * ONE_NEAR));
+        assert_eq!(order.safety_deposit, U128(ONE_NEAR));
+        assert_eq!(
+            contract.deposits.get(&order_hash_for(1)),
+            Some(10 * ONE_NEAR)
+        );
+    }
+
+    #[test]
+    fn test_exclusive_completion_by_resolver() {
+        let mut contract = setup_contract();
+        create_safety_deposit_order(&mut contract);
+
+        set_context(accounts(1), START + HOUR / 2, 0);
+        contract.complete_htlc(order_hash_for(1), secret_for(1), None, None);
+        assert_eq!(created_transfers(), vec![(accounts(2), 10 * ONE_NEAR)]);
+
+        set_promise_result(PromiseResult::Successful(vec![]));
+        contract.on_transfer_complete(
+            order_hash_for(1),
+            U128(10 * ONE_NEAR),
+            accounts(1),
+            U128(0),
+            U128(0),
+        );
+        assert_eq!(created_transfers(), vec![(accounts(1), ONE_NEAR)]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "E_UNAUTHORIZED: Only resolver can complete before the public window"
+    )]
+    fn test_exclusive_completion_rejects_others() {
+        let mut contract = setup_contract();
+        create_safety_deposit_order(&mut contract);
+
+        set_context(accounts(3), START + HOUR / 2, 0);
+        contract.complete_htlc(order_hash_for(1), secret_for(1), None, None);
+    }
+
+    #[test]
+    fn test_public_withdraw_pays_executor() {
+        let mut contract = setup_contract();
+        create_safety_deposit_order(&mut contract);
+
+        set_context(accounts(3), START + HOUR, 0);
+        contract.public_withdraw_htlc(order_hash_for(1), secret_for(1));
+        assert_eq!(created_transfers(), vec![(accounts(2), 10 * ONE_NEAR)]);
+        assert_eq!(
+            contract.get_swap_order(order_hash_for(1)).unwrap().state,
+            HTLCState::Completed
+        );
+
+        set_promise_result(PromiseResult::Successful(vec![]));
+        contract.on_transfer_complete(
+            order_hash_for(1),
+            U128(10 * ONE_NEAR),
+            accounts(3),
+            U128(0),
+            U128(0),
+        );
+        assert_eq!(created_transfers(), vec![(accounts(3), ONE_NEAR)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Public window not open")]
+    fn test_public_withdraw_before_window() {
+        let mut contract = setup_contract();
+        create_safety_deposit_order(&mut contract);
+
+        set_context(accounts(3), START + HOUR / 2, 0);
+        contract.public_withdraw_htlc(order_hash_for(1), secret_for(1));
+    }
+
+    #[test]
+    fn test_refund_returns_safety_deposit() {
+        let mut contract = setup_contract();
+        create_safety_deposit_order(&mut contract);
+
+        refund_order(&mut contract, 1);
+        assert_eq!(
+            created_transfers(),
+            vec![(accounts(1), 10 * ONE_NEAR), (accounts(1), ONE_NEAR)]
+        );
+    }
+
+    #[test]
+    fn test_cleanup_refund_pays_safety_deposit_to_executor() {
+        let mut contract = setup_contract();
+        set_refund_grace(&mut contract);
+        create_safety_deposit_order(&mut contract);
+
+        set_context(accounts(3), START + 3 * HOUR + 1, 0);
+        contract.refund_htlc(order_hash_for(1));
+        assert_eq!(
+            created_transfers(),
+            vec![
+                (accounts(3), ONE_NEAR / 10),
+                (accounts(1), ONE_NEAR * 99 / 10),
+                (accounts(3), ONE_NEAR)
+            ]
+        );
+    }
+
+    const MINUTE: Timestamp = 60_000_000_000;
+
+    /// Finality after 10 minutes, exclusive at 20, public at 1 hour and
+    /// cancellation at 2 hours
+    fn staged_timelocks() -> Timelocks {
+        Timelocks {
+            finality_lock: 10 * MINUTE,
+            exclusive_withdraw: 20 * MINUTE,
+            public_withdraw: HOUR,
+            cancellation: 2 * HOUR,
+            public_cancellation: None,
+        }
+    }
+
+    fn create_staged_order(contract: &mut NEAREscrow, timelocks: Timelocks) {
+        create_test_order(
+            contract,
+            TestOrder {
+                timelock: 0,
+                timelocks: Some(timelocks),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+    }
+
+    #[test]
+    fn test_staged_timelocks_stages() {
+        let mut contract = setup_contract();
+        create_staged_order(&mut contract, staged_timelocks());
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.timelock, START + 2 * HOUR);
+        assert_eq!(order.public_window_start, Some(START + HOUR));
+
+        let stage_at = |contract: &NEAREscrow, time: Timestamp| {
+            set_context(accounts(3), time, 0);
+            contract.get_current_stage(order_hash_for(1))
+        };
+        assert_eq!(stage_at(&contract, START + 5 * MINUTE), STAGE_FINALITY_LOCK);
+        assert_eq!(stage_at(&contract, START + 15 * MINUTE), STAGE_FINALIZED);
+        assert_eq!(
+            stage_at(&contract, START + 30 * MINUTE),
+            STAGE_EXCLUSIVE_WITHDRAW
+        );
+        assert_eq!(stage_at(&contract, START + HOUR), STAGE_PUBLIC_WITHDRAW);
+        assert_eq!(stage_at(&contract, START + 3 * HOUR), STAGE_CANCELLATION);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_NOT_FINAL: Finality lock active")]
+    fn test_staged_completion_blocked_during_finality() {
+        let mut contract = setup_contract();
+        create_staged_order(&mut contract, staged_timelocks());
+
+        set_context(accounts(1), START + 5 * MINUTE, 0);
+        contract.complete_htlc(order_hash_for(1), secret_for(1), None, None);
+    }
+
+    #[test]
+    fn test_staged_completion_after_finality() {
+        let mut contract = setup_contract();
+        create_staged_order(&mut contract, staged_timelocks());
+
+        set_context(accounts(1), START + 30 * MINUTE, 0);
+        contract.complete_htlc(order_hash_for(1), secret_for(1), None, None);
+        assert_eq!(
+            contract.get_swap_order(order_hash_for(1)).unwrap().state,
+            HTLCState::Completed
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ORDER_NOT_EXPIRED: HTLC not expired")]
+    fn test_staged_refund_waits_for_cancellation() {
+        let mut contract = setup_contract();
+        create_staged_order(&mut contract, staged_timelocks());
+
+        set_context(accounts(1), START + 90 * MINUTE, 0);
+        contract.refund_htlc(order_hash_for(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_TIMELOCK: Timelock stages must be increasing")]
+    fn test_staged_timelocks_must_increase() {
+        let mut contract = setup_contract();
+        create_staged_order(
+            &mut contract,
+            Timelocks {
+                public_withdraw: 10 * MINUTE,
+                ..staged_timelocks()
+            },
+        );
+    }
+
+    fn public_cancellation_timelocks() -> Timelocks {
+        Timelocks {
+            public_cancellation: Some(3 * HOUR),
+            ..staged_timelocks()
+        }
+    }
+
+    #[test]
+    fn test_staged_public_cancellation_stage() {
+        let mut contract = setup_contract();
+        create_staged_order(&mut contract, public_cancellation_timelocks());
+
+        set_context(accounts(3), START + 2 * HOUR + 1, 0);
+        assert_eq!(
+            contract.get_current_stage(order_hash_for(1)),
+            STAGE_CANCELLATION
+        );
+        set_context(accounts(3), START + 3 * HOUR, 0);
+        assert_eq!(
+            contract.get_current_stage(order_hash_for(1)),
+            STAGE_PUBLIC_CANCELLATION
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E_UNAUTHORIZED: Only resolver can refund during the grace period")]
+    fn test_staged_cancellation_resolver_only() {
+        let mut contract = setup_contract();
+        create_staged_order(&mut contract, public_cancellation_timelocks());
+
+        set_context(accounts(3), START + 150 * MINUTE, 0);
+        contract.refund_htlc(order_hash_for(1));
+    }
+
+    #[test]
+    fn test_staged_public_cancellation_open_to_anyone() {
+        let mut contract = setup_contract();
+        create_staged_order(&mut contract, public_cancellation_timelocks());
+
+        set_context(accounts(1), START + 150 * MINUTE, 0);
+        contract.refund_htlc(order_hash_for(1));
+        assert_eq!(order_state(&contract, 1), Some(HTLCState::Refunded));
+
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                timelock: 0,
+                timelocks: Some(public_cancellation_timelocks()),
+                ..TestOrder::new(2, ONE_NEAR)
+            },
+        );
+        set_context(accounts(3), START + 3 * HOUR, 0);
+        contract.refund_htlc(order_hash_for(2));
+        assert_eq!(order_state(&contract, 2), Some(HTLCState::Refunded));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_TIMELOCK: Timelock stages must be increasing")]
+    fn test_public_cancellation_after_cancellation() {
+        let mut contract = setup_contract();
+        create_staged_order(
+            &mut contract,
+            Timelocks {
+                public_cancellation: Some(2 * HOUR),
+                ..staged_timelocks()
+            },
+        );
+    }
+
+    /// Two-part Merkle order of 10 NEAR over the secrets of ids 1 and 2,
+    /// returning the proofs for leaves 0 and 1
+    fn create_merkle_order(contract: &mut NEAREscrow) -> (Base64VecU8, Base64VecU8) {
+        let leaf_0 = hash_lock_for(&secret_for(1));
+        let leaf_1 = hash_lock_for(&secret_for(2));
+        let root = Sha256::digest([leaf_0.0.clone(), leaf_1.0.clone()].concat()).to_vec();
+        create_test_order(
+            contract,
+            TestOrder {
+                merkle_root: Some(Base64VecU8(root)),
+                parts: Some(2),
+                ..TestOrder::new(1, 10 * ONE_NEAR)
+            },
+        );
+        (leaf_1, leaf_0)
+    }
+
+    #[test]
+    fn test_partial_fills_complete_order() {
+        let mut contract = setup_contract();
+        let (proof_0, proof_1) = create_merkle_order(&mut contract);
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc_partial(
+            order_hash_for(1),
+            secret_for(1),
+            U128(4 * ONE_NEAR),
+            vec![proof_0],
+            0,
+            None,
+        );
+        assert_eq!(created_transfers(), vec![(accounts(2), 4 * ONE_NEAR)]);
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.state, HTLCState::Active);
+        assert_eq!(order.parts_filled, 1);
+        assert_eq!(order.filled_amount, U128(4 * ONE_NEAR));
+        assert_eq!(
+            contract.get_unfilled_amount(order_hash_for(1)),
+            U128(6 * ONE_NEAR)
+        );
+        assert!(contract.is_leaf_used(order_hash_for(1), 0));
+        assert!(!contract.is_leaf_used(order_hash_for(1), 1));
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc_partial(
+            order_hash_for(1),
+            secret_for(2),
+            U128(6 * ONE_NEAR),
+            vec![proof_1],
+            1,
+            None,
+        );
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.state, HTLCState::Completed);
+        assert_eq!(order.filled_amount, U128(10 * ONE_NEAR));
+        assert!(!contract.is_htlc_active(order_hash_for(1)));
+        assert_eq!(contract.get_unfilled_amount(order_hash_for(1)), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_SECRET: Invalid merkle proof")]
+    fn test_partial_fill_rejects_invalid_proof() {
+        let mut contract = setup_contract();
+        let (_, proof_1) = create_merkle_order(&mut contract);
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc_partial(
+            order_hash_for(1),
+            secret_for(1),
+            U128(4 * ONE_NEAR),
+            vec![proof_1],
+            0,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_AMOUNT: Invalid fill amount")]
+    fn test_partial_fill_cannot_exceed_remaining() {
+        let mut contract = setup_contract();
+        let (proof_0, _) = create_merkle_order(&mut contract);
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc_partial(
+            order_hash_for(1),
+            secret_for(1),
+            U128(11 * ONE_NEAR),
+            vec![proof_0],
+            0,
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_AMOUNT: Fill exceeds the leaf's share")]
+    fn test_partial_fill_limited_to_leaf_share() {
+        let mut contract = setup_contract();
+        let (proof_0, _) = create_merkle_order(&mut contract);
+
+        // Leaf 0 of two unlocks at most half of the order
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc_partial(
+            order_hash_for(1),
+            secret_for(1),
+            U128(5 * ONE_NEAR + 1),
+            vec![proof_0],
+            0,
+            None,
+        );
+    }
+
+    #[test]
+    fn test_partial_fill_uses_completion_payout_rules() {
+        let mut contract = setup_contract();
+        let leaf_0 = hash_lock_for(&secret_for(1));
+        let leaf_1 = hash_lock_for(&secret_for(2));
+        let root = Sha256::digest([leaf_0.0, leaf_1.0.clone()].concat()).to_vec();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                merkle_root: Some(Base64VecU8(root)),
+                parts: Some(2),
+                relayer_fee: Some(U128(ONE_NEAR / 10)),
+                push_on_complete: Some(false),
+                ..TestOrder::new(1, 10 * ONE_NEAR)
+            },
+        );
+
+        // A relayer fills the first part in pull mode, earning its fee
+        set_context(accounts(3), START + HOUR, 0);
+        contract.complete_htlc_partial(
+            order_hash_for(1),
+            secret_for(1),
+            U128(5 * ONE_NEAR),
+            vec![leaf_1],
+            0,
+            Some(U128(4 * ONE_NEAR)),
+        );
+        assert!(created_transfers().is_empty());
+        assert_eq!(
+            contract.get_claimable(accounts(2), "NEAR".to_string()),
+            U128(5 * ONE_NEAR - ONE_NEAR / 10)
+        );
+        assert_eq!(
+            contract.get_claimable(accounts(3), "NEAR".to_string()),
+            U128(ONE_NEAR / 10)
+        );
+        assert_eq!(order_state(&contract, 1), Some(HTLCState::Active));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_PAYOUT_BELOW_MINIMUM: Payout below minimum")]
+    fn test_partial_fill_respects_min_payout() {
+        let mut contract = setup_contract();
+        let (proof_0, _) = create_merkle_order(&mut contract);
+        set_fee(&mut contract, 100);
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc_partial(
+            order_hash_for(1),
+            secret_for(1),
+            U128(5 * ONE_NEAR),
+            vec![proof_0],
+            0,
+            Some(U128(5 * ONE_NEAR)),
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "E_INVALID_AMOUNT: Merkle orders need a part count, other orders none"
+    )]
+    fn test_merkle_order_requires_parts() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                merkle_root: Some(Base64VecU8(vec![0; 32])),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+    }
+
+    #[test]
+    fn test_failed_partial_fill_restores_remaining_deposit() {
+        let mut contract = setup_contract();
+        let (proof_0, _) = create_merkle_order(&mut contract);
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc_partial(
+            order_hash_for(1),
+            secret_for(1),
+            U128(4 * ONE_NEAR),
+            vec![proof_0],
+            0,
+            None,
+        );
+
+        set_promise_result(PromiseResult::Failed);
+        assert!(!contract.on_partial_fill_transfer(
+            order_hash_for(1),
+            U128(4 * ONE_NEAR),
+            accounts(2),
+            U128(0),
+            U128(0),
+            0
+        ));
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.filled_amount, U128(0));
+        assert_eq!(order.parts_filled, 0);
+        assert!(!contract.is_leaf_used(order_hash_for(1), 0));
+
+        // The rest of the order is still refundable in full
+        refund_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(1), 10 * ONE_NEAR)]);
+    }
+
+    #[test]
+    fn test_failed_last_partial_fill_releases_leaf() {
+        let mut contract = setup_contract();
+        let (proof_0, proof_1) = create_merkle_order(&mut contract);
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc_partial(
+            order_hash_for(1),
+            secret_for(1),
+            U128(4 * ONE_NEAR),
+            vec![proof_0],
+            0,
+            None,
+        );
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc_partial(
+            order_hash_for(1),
+            secret_for(2),
+            U128(6 * ONE_NEAR),
+            vec![proof_1.clone()],
+            1,
+            None,
+        );
+        assert_eq!(order_state(&contract, 1), Some(HTLCState::Completed));
+
+        set_promise_result(PromiseResult::Failed);
+        assert!(!contract.on_partial_fill_transfer(
+            order_hash_for(1),
+            U128(6 * ONE_NEAR),
+            accounts(2),
+            U128(0),
+            U128(0),
+            1
+        ));
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.state, HTLCState::Active);
+        assert_eq!(order.parts_filled, 1);
+        assert_eq!(order.filled_amount, U128(4 * ONE_NEAR));
+        assert!(!contract.is_leaf_used(order_hash_for(1), 1));
+        assert_eq!(
+            contract.get_unfilled_amount(order_hash_for(1)),
+            U128(6 * ONE_NEAR)
+        );
+
+        // The last leaf can be filled again, completing the order
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc_partial(
+            order_hash_for(1),
+            secret_for(2),
+            U128(6 * ONE_NEAR),
+            vec![proof_1],
+            1,
+            None,
+        );
+        assert_eq!(order_state(&contract, 1), Some(HTLCState::Completed));
+        assert_eq!(created_transfers(), vec![(accounts(2), 6 * ONE_NEAR)]);
+    }
+
+    #[test]
+    fn test_failed_partial_fill_keeps_later_leaf_consumed() {
+        let mut contract = setup_contract();
+        let (proof_0, proof_1) = create_merkle_order(&mut contract);
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc_partial(
+            order_hash_for(1),
+            secret_for(1),
+            U128(4 * ONE_NEAR),
+            vec![proof_0],
+            0,
+            None,
+        );
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc_partial(
+            order_hash_for(1),
+            secret_for(2),
+            U128(5 * ONE_NEAR),
+            vec![proof_1],
+            1,
+            None,
+        );
+
+        // Leaf 0's payout fails after leaf 1 was filled
+        set_promise_result(PromiseResult::Failed);
+        contract.on_partial_fill_transfer(
+            order_hash_for(1),
+            U128(4 * ONE_NEAR),
+            accounts(2),
+            U128(0),
+            U128(0),
+            0,
+        );
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.parts_filled, 2);
+        assert_eq!(order.filled_amount, U128(5 * ONE_NEAR));
+        assert_eq!(
+            contract.get_unfilled_amount(order_hash_for(1)),
+            U128(5 * ONE_NEAR)
+        );
+    }
+
+    fn pause_contract(contract: &mut NEAREscrow) {
+        set_context(accounts(0), START, 0);
+        contract.pause();
+    }
+
+    #[test]
+    #[should_panic(expected = "E_PAUSED: Contract is paused")]
+    fn test_pause_blocks_creation() {
+        let mut contract = setup_contract();
+        pause_contract(&mut contract);
+
+        create_order(&mut contract, 1, ONE_NEAR);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_PAUSED: Contract is paused")]
+    fn test_pause_blocks_ft_orders() {
+        let mut contract = setup_contract();
+        pause_contract(&mut contract);
+
+        ft_on_transfer(&mut contract, 1_000, ft_message(1, "ethereum"));
+    }
+
+    #[test]
+    fn test_pause_allows_settlement() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        create_order(&mut contract, 2, ONE_NEAR);
+        pause_contract(&mut contract);
+        assert!(contract.is_paused());
+
+        complete_order(&mut contract, 1);
+        refund_order(&mut contract, 2);
+        assert_eq!(
+            contract.get_swap_order(order_hash_for(1)).unwrap().state,
+            HTLCState::Completed
+        );
+        assert_eq!(
+            contract.get_swap_order(order_hash_for(2)).unwrap().state,
+            HTLCState::Refunded
+        );
+
+        set_context(accounts(0), START, 0);
+        contract.unpause();
+        create_order(&mut contract, 3, ONE_NEAR);
+        assert!(contract.is_htlc_active(order_hash_for(3)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner can call this method")]
+    fn test_pause_owner_only() {
+        let mut contract = setup_contract();
+        set_context(accounts(1), START, 0);
+        contract.pause();
+    }
+
+    fn old_order(id: u8, state: HTLCState) -> OldSwapOrder {
+        OldSwapOrder {
+            order_hash: order_hash_for(id),
+            src_maker: "0xmaker".to_string(),
+            src_chain: "ethereum".to_string(),
+            src_token: "ETH".to_string(),
+            src_amount: U128(1),
+            dst_recipient: accounts(2),
+            dst_token: "NEAR".to_string(),
+            dst_amount: U128(ONE_NEAR),
+            hash_lock: hash_lock_for(&secret_for(id)),
+            timelock: START + 2 * HOUR,
+            state,
+            created_at: START,
+            resolver: accounts(1),
+        }
+    }
+
+    #[test]
+    fn test_migrate_from_initial_layout() {
+        set_context(accounts(0), START, 0);
+        let mut old = OldNEAREscrow {
+            owner: accounts(0),
+            swap_orders: UnorderedMap::new(b"s".to_vec()),
+            deposits: LookupMap::new(b"d".to_vec()),
+            supported_chains: LookupMap::new(b"c".to_vec()),
+            min_timelock: HOUR / 2,
+            max_timelock: 48 * HOUR,
+        };
+        old.swap_orders
+            .insert(&order_hash_for(1), &old_order(1, HTLCState::Active));
+        old.swap_orders
+            .insert(&order_hash_for(2), &old_order(2, HTLCState::Completed));
+        // The old contract took attached NEAR whatever dst_token named
+        let mut labelled = old_order(3, HTLCState::Active);
+        labelled.dst_token = "usdc.near".to_string();
+        old.swap_orders.insert(&order_hash_for(3), &labelled);
+        old.deposits.insert(&order_hash_for(3), &ONE_NEAR);
+        old.deposits.insert(&order_hash_for(1), &ONE_NEAR);
+        old.supported_chains.insert(&"ethereum".to_string(), &true);
+        env::state_write(&old);
+
+        let mut contract = NEAREscrow::migrate(vec!["ethereum".to_string(), "solana".to_string()]);
+        assert_eq!(contract.get_owner(), accounts(0));
+        assert_eq!(contract.get_timelock_limits(), (HOUR / 2, 48 * HOUR));
+        assert!(contract.is_chain_supported("ethereum".to_string()));
+        assert_eq!(contract.get_chain_config("solana".to_string()), None);
+        assert!(!contract.is_paused());
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.hash_algorithm, HashAlgorithm::Sha256);
+        assert_eq!(order.safety_deposit, U128(0));
+        let active: Vec<_> = contract
+            .get_active_orders(None, None)
+            .into_iter()
+            .map(|order| order.order_hash)
+            .collect();
+        assert_eq!(active.len(), 2);
+        assert!(active.contains(&order_hash_for(1)));
+        assert!(active.contains(&order_hash_for(3)));
+        assert_eq!(largest_amounts(&contract), vec![ONE_NEAR, ONE_NEAR]);
+        assert_eq!(contract.get_total_locked(), U128(2 * ONE_NEAR));
+        assert_eq!(
+            contract.get_locked_balance("NEAR".to_string()),
+            U128(2 * ONE_NEAR)
+        );
+        assert_eq!(
+            contract.get_locked_balance("usdc.near".to_string()),
+            U128(0)
+        );
+        assert_eq!(
+            contract
+                .get_orders_by_maker("0xmaker".to_string(), None, None)
+                .len(),
+            3
+        );
+
+        let order = contract.get_swap_order(order_hash_for(3)).unwrap();
+        assert_eq!(order.dst_asset, DstAsset::Native);
+        assert_eq!(order.dst_token, "NEAR");
+
+        // Migrated orders settle like any other, in NEAR
+        complete_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(2), ONE_NEAR)]);
+        complete_order(&mut contract, 3);
+        assert_eq!(created_transfers(), vec![(accounts(2), ONE_NEAR)]);
+        assert_eq!(contract.get_locked_balance("NEAR".to_string()), U128(0));
+    }
+
+    #[test]
+    fn test_contract_source_metadata() {
+        let contract = setup_contract();
+        let metadata = contract.contract_source_metadata();
+
+        assert_eq!(
+            metadata.version,
+            Some(env!("CARGO_PKG_VERSION").to_string())
+        );
+        assert_eq!(metadata.link, Some(CONTRACT_SOURCE_LINK.to_string()));
+        let standards: Vec<&str> = metadata
+            .standards
+            .iter()
+            .map(|standard| standard.standard.as_str())
+            .collect();
+        assert_eq!(
+            standards,
+            vec!["nep330", "nep297", "nep141", "nep171", "htlc"]
+        );
+    }
+
+    fn set_fee(contract: &mut NEAREscrow, fee_bps: u16) {
+        set_context(accounts(0), START, 0);
+        contract.set_fee_bps(fee_bps);
+    }
+
+    #[test]
+    fn test_completion_pays_net_of_fee() {
+        let mut contract = setup_contract();
+        set_fee(&mut contract, 100);
+        create_order(&mut contract, 1, 10 * ONE_NEAR);
+        create_order(&mut contract, 2, 20 * ONE_NEAR);
+
+        complete_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(2), 99 * ONE_NEAR / 10)]);
+        complete_order(&mut contract, 2);
+        assert_eq!(
+            contract.get_collected_fees("NEAR".to_string()),
+            U128(3 * ONE_NEAR / 10)
+        );
+    }
+
+    #[test]
+    fn test_failed_completion_takes_back_fee() {
+        let mut contract = setup_contract();
+        set_fee(&mut contract, 100);
+        create_order(&mut contract, 1, 10 * ONE_NEAR);
+        create_order(&mut contract, 2, 10 * ONE_NEAR);
+        complete_order(&mut contract, 1);
+        let collected = contract.get_collected_fees("NEAR".to_string());
+
+        complete_order(&mut contract, 2);
+        set_promise_result(PromiseResult::Failed);
+        assert!(!contract.on_transfer_complete(
+            order_hash_for(2),
+            U128(10 * ONE_NEAR),
+            accounts(2),
+            U128(0),
+            U128(ONE_NEAR / 10)
+        ));
+        assert_eq!(contract.get_collected_fees("NEAR".to_string()), collected);
+    }
+
+    #[test]
+    fn test_refund_takes_no_fee() {
+        let mut contract = setup_contract();
+        set_fee(&mut contract, 100);
+        create_order(&mut contract, 1, 10 * ONE_NEAR);
+
+        refund_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(1), 10 * ONE_NEAR)]);
+        assert_eq!(contract.get_collected_fees("NEAR".to_string()), U128(0));
+    }
+
+    #[test]
+    fn test_withdraw_fees() {
+        let mut contract = setup_contract();
+        set_fee(&mut contract, 100);
+        create_order(&mut contract, 1, 10 * ONE_NEAR);
+        complete_order(&mut contract, 1);
+
+        set_context(accounts(0), START + HOUR, 0);
+        contract.withdraw_fees("NEAR".to_string(), U128(ONE_NEAR / 20), accounts(4));
+        assert_eq!(created_transfers(), vec![(accounts(4), ONE_NEAR / 20)]);
+        assert_eq!(
+            contract.get_collected_fees("NEAR".to_string()),
+            U128(ONE_NEAR / 20)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient collected fees")]
+    fn test_withdraw_fees_limited_to_collected() {
+        let mut contract = setup_contract();
+        set_context(accounts(0), START, 0);
+        contract.withdraw_fees("NEAR".to_string(), U128(1), accounts(0));
+    }
+
+    #[test]
+    fn test_withdraw_token_fees_to_treasury() {
+        let mut contract = setup_contract();
+        set_fee(&mut contract, 100);
+        ft_on_transfer(&mut contract, 1_000, ft_message(1, "ethereum"));
+        complete_order(&mut contract, 1);
+
+        set_context(accounts(0), START + HOUR, 0);
+        contract.withdraw_fees(token_account().to_string(), U128(10), accounts(4));
+        let receipts = get_created_receipts();
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].receiver_id, token_account());
+        match &receipts[0].actions[0] {
+            VmAction::FunctionCall {
+                function_name,
+                args,
+                ..
+            } => {
+                assert_eq!(function_name, "ft_transfer");
+                let args: serde_json::Value = serde_json::from_slice(args).unwrap();
+                assert_eq!(args["receiver_id"], accounts(4).to_string());
+                assert_eq!(args["amount"], "10");
+            }
+            action => panic!("Unexpected action {:?}", action),
+        }
+        assert_eq!(
+            contract.get_collected_fees(token_account().to_string()),
+            U128(0)
+        );
+    }
+
+    /// The mocked account also pays for 300 KiB of storage, about 3 NEAR
+    fn set_balance_context(predecessor: AccountId, balance: Balance) {
+        let mut context = get_context(predecessor);
+        context.block_timestamp(START).account_balance(balance);
+        testing_env!(context.build());
+    }
+
+    #[test]
+    fn test_get_solvency() {
+        let mut contract = setup_contract();
+        set_fee(&mut contract, 100);
+        create_order(&mut contract, 1, 10 * ONE_NEAR);
+        create_order(&mut contract, 2, 10 * ONE_NEAR);
+        complete_order(&mut contract, 1);
+
+        set_balance_context(accounts(0), 20 * ONE_NEAR);
+        let report = contract.get_solvency();
+        assert_eq!(report.account_balance, U128(20 * ONE_NEAR));
+        assert_eq!(report.total_locked_native, U128(10 * ONE_NEAR));
+        assert_eq!(report.collected_fees_native, U128(ONE_NEAR / 10));
+        assert!(report.storage_reserve.0 > 0);
+        assert!(report.solvent);
+
+        set_balance_context(accounts(0), 12 * ONE_NEAR);
+        assert!(!contract.get_solvency().solvent);
+    }
+
+    #[test]
+    fn test_total_locked_follows_orders() {
+        let mut contract = setup_contract();
+        create_safety_deposit_order(&mut contract);
+        create_order(&mut contract, 2, 10 * ONE_NEAR);
+        assert_eq!(contract.get_total_locked(), U128(21 * ONE_NEAR));
+
+        // Token deposits aren't NEAR held by the contract
+        ft_on_transfer(&mut contract, 1_000, ft_message(3, "ethereum"));
+        assert_eq!(contract.get_total_locked(), U128(21 * ONE_NEAR));
+
+        complete_order(&mut contract, 2);
+        refund_order(&mut contract, 1);
+        assert_eq!(contract.get_total_locked(), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount exceeds free balance")]
+    fn test_emergency_withdraw_cannot_take_locked_funds() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, 10 * ONE_NEAR);
+
+        set_balance_context(accounts(0), 20 * ONE_NEAR);
+        contract.emergency_withdraw(U128(9 * ONE_NEAR));
+    }
+
+    #[test]
+    fn test_emergency_withdraw_free_balance() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, 10 * ONE_NEAR);
+
+        set_balance_context(accounts(0), 20 * ONE_NEAR);
+        contract.emergency_withdraw(U128(5 * ONE_NEAR));
+        assert_eq!(created_transfers(), vec![(accounts(0), 5 * ONE_NEAR)]);
+    }
+
+    fn storage_available(contract: &NEAREscrow, account_id: AccountId) -> Balance {
+        contract
+            .storage_balance_of(account_id)
+            .map_or(0, |balance| balance.available.0)
+    }
+
+    #[test]
+    fn test_storage_charged_on_create_and_released_on_refund() {
+        let mut contract = setup_contract();
+        let before = storage_available(&contract, accounts(1));
+
+        create_order(&mut contract, 1, ONE_NEAR);
+        let after_create = storage_available(&contract, accounts(1));
+        assert!(after_create < before);
+
+        refund_order(&mut contract, 1);
+        assert!(storage_available(&contract, accounts(1)) > after_create);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INSUFFICIENT_STORAGE: Account not registered for storage")]
+    fn test_create_requires_storage_registration() {
+        let mut contract = setup_contract();
+        set_context(accounts(3), START, ONE_NEAR);
+        contract.create_htlc(batch_args(1, ONE_NEAR));
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount must equal attached deposit")]
+    fn test_create_rejects_amount_mismatch() {
+        let mut contract = setup_contract();
+        set_context(accounts(1), START, ONE_NEAR);
+        contract.create_htlc(batch_args(1, 2 * ONE_NEAR));
+    }
+
+    #[test]
+    fn test_storage_deposit_and_withdraw() {
+        let mut contract = setup_contract();
+        let min = contract.storage_balance_bounds().min.0;
+
+        set_context(accounts(3), START, min + ONE_NEAR);
+        let balance = contract.storage_deposit(None, None);
+        assert_eq!(balance.total, U128(min + ONE_NEAR));
+        assert_eq!(balance.available, U128(ONE_NEAR));
+
+        set_context(accounts(3), START, 1);
+        let balance = contract.storage_withdraw(Some(U128(ONE_NEAR / 2)));
+        assert_eq!(balance.available, U128(ONE_NEAR / 2));
+        assert_eq!(created_transfers(), vec![(accounts(3), ONE_NEAR / 2)]);
+
+        set_context(accounts(3), START, 1);
+        assert!(contract.storage_unregister(None));
+        assert_eq!(contract.storage_balance_of(accounts(3)), None);
+    }
+
+    #[test]
+    fn test_storage_registration_only_refunds_excess() {
+        let mut contract = setup_contract();
+        let min = contract.storage_balance_bounds().min.0;
+
+        set_context(accounts(3), START, min + ONE_NEAR);
+        let balance = contract.storage_deposit(None, Some(true));
+        assert_eq!(balance.total, U128(min));
+        assert_eq!(created_transfers(), vec![(accounts(3), ONE_NEAR)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Storage still in use by orders")]
+    fn test_storage_unregister_with_active_orders() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(1), START, 1);
+        contract.storage_unregister(None);
+    }
+
+    fn purge_order(contract: &mut NEAREscrow, id: u8) {
+        set_context(accounts(1), START + 3 * HOUR, 0);
+        contract.purge_order(order_hash_for(id));
+    }
+
+    #[test]
+    fn test_purge_completed_order() {
+        let mut contract = setup_contract();
+        let before = storage_available(&contract, accounts(1));
+        create_order(&mut contract, 1, ONE_NEAR);
+        complete_order(&mut contract, 1);
+        assert!(storage_available(&contract, accounts(1)) < before);
+
+        purge_order(&mut contract, 1);
+        assert!(contract.get_swap_order(order_hash_for(1)).is_none());
+        assert_eq!(storage_available(&contract, accounts(1)), before);
+    }
+
+    #[test]
+    #[should_panic(expected = "Order not settled")]
+    fn test_purge_rejects_active_order() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        purge_order(&mut contract, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only resolver or owner can purge")]
+    fn test_purge_restricted_to_resolver_and_owner() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        refund_order(&mut contract, 1);
+
+        set_context(accounts(3), START + 3 * HOUR, 0);
+        contract.purge_order(order_hash_for(1));
+    }
+
+    #[test]
+    fn test_purge_orders_batch() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        create_order(&mut contract, 2, ONE_NEAR);
+        complete_order(&mut contract, 1);
+        refund_order(&mut contract, 2);
+
+        set_context(accounts(0), START + 3 * HOUR, 0);
+        contract.purge_orders(vec![order_hash_for(1), order_hash_for(2)]);
+        assert!(contract.get_swap_order(order_hash_for(1)).is_none());
+        assert!(contract.get_swap_order(order_hash_for(2)).is_none());
+        assert_eq!(
+            emitted_events()
+                .into_iter()
+                .map(|event| event.event)
+                .collect::<Vec<_>>(),
+            vec![
+                HtlcEvent::HtlcPurged(vec![HtlcPurgedData {
+                    order_hash: order_hash_for(1),
+                }]),
+                HtlcEvent::HtlcPurged(vec![HtlcPurgedData {
+                    order_hash: order_hash_for(2),
+                }]),
+            ]
+        );
+    }
+
+    fn order_ids(orders: Vec<SwapOrder>) -> Vec<u8> {
+        orders.iter().map(|order| order.order_hash.0[0]).collect()
+    }
+
+    #[test]
+    fn test_get_orders_by_maker() {
+        let mut contract = setup_contract();
+        for id in 1..=5u8 {
+            let src_maker = if id % 2 == 1 { "0xalice" } else { "0xbob" };
+            create_test_order(
+                &mut contract,
+                TestOrder {
+                    src_maker: src_maker.to_string(),
+                    ..TestOrder::new(id, ONE_NEAR)
+                },
+            );
+        }
+
+        let by_maker = |from_index, limit| {
+            order_ids(contract.get_orders_by_maker("0xalice".to_string(), from_index, limit))
+        };
+        assert_eq!(by_maker(None, None), vec![1, 3, 5]);
+        assert_eq!(by_maker(Some(1), Some(1)), vec![3]);
+        assert_eq!(
+            order_ids(contract.get_orders_by_maker("0xbob".to_string(), None, None)),
+            vec![2, 4]
+        );
+        assert!(contract
+            .get_orders_by_maker("0xcarol".to_string(), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_get_orders_by_chain() {
+        let mut contract = setup_contract();
+        contract.add_supported_chain("polygon".to_string());
+        for id in 1..=5u8 {
+            let src_chain = if id % 2 == 1 { "ethereum" } else { "polygon" };
+            create_test_order(
+                &mut contract,
+                TestOrder {
+                    src_chain: src_chain.to_string(),
+                    ..TestOrder::new(id, ONE_NEAR)
+                },
+            );
+        }
+
+        let by_chain =
+            |chain: &str| order_ids(contract.get_orders_by_chain(chain.to_string(), None, None));
+        assert_eq!(by_chain("ethereum"), vec![1, 3, 5]);
+        assert_eq!(by_chain("polygon"), vec![2, 4]);
+        assert!(by_chain("bitcoin").is_empty());
+        assert_eq!(
+            order_ids(contract.get_orders_by_chain("ethereum".to_string(), Some(2), Some(5))),
+            vec![5]
+        );
+    }
+
+    #[test]
+    fn test_get_orders_by_recipient() {
+        let mut contract = setup_contract();
+        for id in 1..=3u8 {
+            create_order(&mut contract, id, ONE_NEAR);
+        }
+        ft_on_transfer(&mut contract, 1_000, ft_message(4, "ethereum"));
+
+        let by_recipient = |from_index, limit| {
+            order_ids(contract.get_orders_by_recipient(accounts(2), from_index, limit))
+        };
+        assert_eq!(by_recipient(None, None), vec![1, 2, 3, 4]);
+        assert_eq!(by_recipient(Some(1), Some(2)), vec![2, 3]);
+        assert!(by_recipient(Some(4), None).is_empty());
+    }
+
+    #[test]
+    fn test_get_orders_by_recipient_without_orders() {
+        let contract = setup_contract();
+        assert!(contract
+            .get_orders_by_recipient(accounts(3), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_get_orders_by_state() {
+        let mut contract = setup_contract();
+        for id in 1..=6u8 {
+            create_order(&mut contract, id, ONE_NEAR);
+        }
+        complete_order(&mut contract, 1);
+        complete_order(&mut contract, 3);
+        refund_order(&mut contract, 2);
+
+        let by_state = |state, from_index, limit| {
+            order_ids(contract.get_orders_by_state(state, from_index, limit))
+        };
+        let mut active = by_state(HTLCState::Active, None, None);
+        active.sort();
+        assert_eq!(active, vec![4, 5, 6]);
+        assert_eq!(by_state(HTLCState::Completed, None, None), vec![1, 3]);
+        assert_eq!(by_state(HTLCState::Completed, Some(1), Some(1)), vec![3]);
+        assert_eq!(by_state(HTLCState::Refunded, None, None), vec![2]);
+        assert!(by_state(HTLCState::Expired, None, None).is_empty());
+    }
+
+    #[test]
+    fn test_stats_after_complete_and_refund() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, 2 * ONE_NEAR);
+        create_order(&mut contract, 2, ONE_NEAR);
+        create_order(&mut contract, 3, ONE_NEAR);
+        complete_order(&mut contract, 1);
+        refund_order(&mut contract, 2);
+
+        assert_eq!(
+            contract.get_stats(),
+            ContractStats {
+                total_orders_created: 3,
+                total_completed: 1,
+                total_refunded: 1,
+                active_orders: 1,
+            }
+        );
+        assert_eq!(
+            contract.get_total_volume("NEAR".to_string()),
+            U128(2 * ONE_NEAR)
+        );
+    }
+
+    #[test]
+    fn test_stats_undo_failed_completion() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        complete_order(&mut contract, 1);
+
+        set_promise_result(PromiseResult::Failed);
+        contract.on_transfer_complete(
+            order_hash_for(1),
+            U128(ONE_NEAR),
+            accounts(2),
+            U128(0),
+            U128(0),
+        );
+        assert_eq!(contract.get_stats().total_completed, 0);
+        assert_eq!(contract.get_total_volume("NEAR".to_string()), U128(0));
+
+        complete_order(&mut contract, 1);
+        assert_eq!(contract.get_stats().total_completed, 1);
+        assert_eq!(
+            contract.get_total_volume("NEAR".to_string()),
+            U128(ONE_NEAR)
+        );
+    }
+
+    #[test]
+    fn test_expire_htlc_after_timelock() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(3), START + 3 * HOUR, 0);
+        contract.expire_htlc(order_hash_for(1));
+        assert_eq!(
+            contract.get_swap_order(order_hash_for(1)).unwrap().state,
+            HTLCState::Expired
+        );
+        assert!(!contract.is_htlc_active(order_hash_for(1)));
+        assert!(contract.get_active_orders(None, None).is_empty());
+        assert_eq!(contract.get_total_locked(), U128(ONE_NEAR));
+        assert!(created_transfers().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "HTLC not expired")]
+    fn test_expire_htlc_before_timelock() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(3), START + HOUR, 0);
+        contract.expire_htlc(order_hash_for(1));
+    }
+
+    #[test]
+    fn test_refund_from_expired_state() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        set_context(accounts(3), START + 3 * HOUR, 0);
+        contract.expire_htlc(order_hash_for(1));
+
+        refund_order(&mut contract, 1);
+        assert_eq!(
+            contract.get_swap_order(order_hash_for(1)).unwrap().state,
+            HTLCState::Refunded
+        );
+        assert_eq!(created_transfers(), vec![(accounts(1), ONE_NEAR)]);
+        assert_eq!(contract.get_total_locked(), U128(0));
+    }
+
+    #[test]
+    fn test_top_up_htlc() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        create_order(&mut contract, 2, 2 * ONE_NEAR);
+
+        set_context(accounts(1), START, 2 * ONE_NEAR);
+        contract.top_up_htlc(order_hash_for(1));
+        assert_eq!(
+            contract
+                .get_swap_order(order_hash_for(1))
+                .unwrap()
+                .dst_amount,
+            U128(3 * ONE_NEAR)
+        );
+        assert_eq!(contract.get_total_locked(), U128(5 * ONE_NEAR));
+        assert_eq!(largest_amounts(&contract), vec![3 * ONE_NEAR, 2 * ONE_NEAR]);
+        assert_eq!(
+            emitted_events()[0].event,
+            HtlcEvent::HtlcToppedUp(vec![HtlcToppedUpData {
+                order_hash: order_hash_for(1),
+                amount: U128(2 * ONE_NEAR),
+                dst_amount: U128(3 * ONE_NEAR),
+            }])
+        );
+
+        complete_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(2), 3 * ONE_NEAR)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Order not active")]
+    fn test_top_up_rejects_settled_order() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        complete_order(&mut contract, 1);
+
+        set_context(accounts(1), START + HOUR, ONE_NEAR);
+        contract.top_up_htlc(order_hash_for(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only resolver can top up")]
+    fn test_top_up_resolver_only() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(3), START, ONE_NEAR);
+        contract.top_up_htlc(order_hash_for(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_AMOUNT: Order amount below minimum")]
+    fn test_order_amount_below_minimum() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR / 1_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_AMOUNT: Order amount above maximum")]
+    fn test_order_amount_above_maximum() {
+        let mut contract = setup_contract();
+        set_context(accounts(0), START, 0);
+        contract.set_order_amount_limits(U128(ONE_NEAR), U128(5 * ONE_NEAR));
+
+        create_order(&mut contract, 1, 6 * ONE_NEAR);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_AMOUNT: Order amount above maximum")]
+    fn test_top_up_respects_maximum() {
+        let mut contract = setup_contract();
+        set_context(accounts(0), START, 0);
+        contract.set_order_amount_limits(U128(ONE_NEAR), U128(5 * ONE_NEAR));
+        create_order(&mut contract, 1, 5 * ONE_NEAR);
+
+        set_context(accounts(1), START, ONE_NEAR);
+        contract.top_up_htlc(order_hash_for(1));
+    }
+
+    fn enable_allowlist(contract: &mut NEAREscrow) {
+        set_context(accounts(0), START, 0);
+        contract.set_allowlist_enabled(true);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_RESOLVER_NOT_ALLOWED: Resolver not allowlisted")]
+    fn test_allowlist_blocks_unlisted_resolver() {
+        let mut contract = setup_contract();
+        enable_allowlist(&mut contract);
+
+        create_order(&mut contract, 1, ONE_NEAR);
+    }
+
+    #[test]
+    fn test_allowlist_admits_listed_resolver() {
+        let mut contract = setup_contract();
+        enable_allowlist(&mut contract);
+        contract.add_resolver(accounts(1));
+
+        create_order(&mut contract, 1, ONE_NEAR);
+        assert!(contract.is_htlc_active(order_hash_for(1)));
+
+        set_context(accounts(0), START, 0);
+        contract.remove_resolver(accounts(1));
+        assert!(!contract.is_resolver_allowed(accounts(1)));
+    }
+
+    #[test]
+    fn test_allowlist_disabled_is_open() {
+        let mut contract = setup_contract();
+        assert!(contract.is_resolver_allowed(accounts(1)));
+
+        create_order(&mut contract, 1, ONE_NEAR);
+        assert!(contract.is_htlc_active(order_hash_for(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_TIMELOCK: Timelock too short")]
+    fn test_chain_timelock_limits_override_global() {
+        let mut contract = setup_contract();
+        set_context(accounts(0), START, 0);
+        contract.set_chain_timelock_limits("ethereum".to_string(), 3 * HOUR, 12 * HOUR);
+        assert_eq!(
+            contract.get_chain_timelock_limits("ethereum".to_string()),
+            (3 * HOUR, 12 * HOUR)
+        );
+
+        // Two hours passes the global minimum but not Ethereum's
+        create_order(&mut contract, 1, ONE_NEAR);
+    }
+
+    #[test]
+    fn test_chain_timelock_limits_default_to_global() {
+        let mut contract = setup_contract();
+        set_context(accounts(0), START, 0);
+        contract.add_supported_chain("bitcoin".to_string());
+        contract.set_chain_timelock_limits("bitcoin".to_string(), 3 * HOUR, 12 * HOUR);
+
+        assert_eq!(
+            contract.get_chain_timelock_limits("ethereum".to_string()),
+            contract.get_timelock_limits()
+        );
+        create_order(&mut contract, 1, ONE_NEAR);
+        assert!(contract.is_htlc_active(order_hash_for(1)));
+    }
+
+    #[test]
+    fn test_chain_config_round_trip() {
+        let mut contract = setup_contract();
+        let config = ChainConfig {
+            chain_id: 1,
+            min_confirmations: 12,
+            enabled: true,
+        };
+        contract.set_chain_config("ethereum".to_string(), config.clone());
+        assert_eq!(
+            contract.get_chain_config("ethereum".to_string()),
+            Some(config)
+        );
+
+        contract.remove_supported_chain("ethereum".to_string());
+        assert!(!contract.is_chain_supported("ethereum".to_string()));
+        assert_eq!(
+            contract
+                .get_chain_config("ethereum".to_string())
+                .unwrap()
+                .min_confirmations,
+            12
+        );
+        assert!(contract.list_supported_chains().is_empty());
+
+        // Re-adding keeps the config
+        contract.add_supported_chain("ethereum".to_string());
+        assert_eq!(
+            contract.get_chain_config("ethereum".to_string()),
+            Some(ChainConfig {
+                chain_id: 1,
+                min_confirmations: 12,
+                enabled: true,
+            })
+        );
+        assert_eq!(
+            contract.list_supported_chains(),
+            vec!["ethereum".to_string()]
+        );
+        assert_eq!(contract.get_chain_config("bitcoin".to_string()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_UNSUPPORTED_CHAIN: Unsupported source chain")]
+    fn test_create_rejects_disabled_chain_config() {
+        let mut contract = setup_contract();
+        contract.set_chain_config(
+            "ethereum".to_string(),
+            ChainConfig {
+                chain_id: 1,
+                min_confirmations: 12,
+                enabled: false,
+            },
+        );
+        create_order(&mut contract, 1, ONE_NEAR);
+    }
+
+    fn batch_args(id: u8, amount: Balance) -> CreateHtlcArgs {
+        CreateHtlcArgs {
+            order_hash: order_hash_for(id),
+            src_maker: "0xmaker".to_string(),
+            src_chain: "ethereum".to_string(),
+            src_token: "ETH".to_string(),
+            src_amount: U128(1),
+            dst_recipient: accounts(2),
+            dst_token: "NEAR".to_string(),
+            hash_lock: hash_lock_for(&secret_for(id)),
+            timelock: START + 2 * HOUR,
+            complete_by: None,
+            refund_bridge: None,
+            auto_complete_secret: None,
+            src_timelock: None,
+            hash_algorithm: None,
+            safety_deposit: None,
+            public_window_start: None,
+            timelocks: None,
+            merkle_root: None,
+            memo: None,
+            refund_to: None,
+            relayer_fee: None,
+            allow_recipient_override: None,
+            src_block_height: None,
+            alt_hash_locks: None,
+            push_on_complete: None,
+            auction: None,
+            parts: None,
+            amount: U128(amount),
+        }
+    }
+
+    #[test]
+    fn test_create_htlc_batch() {
+        let mut contract = setup_contract();
+        let mut with_deposit = batch_args(2, 2 * ONE_NEAR);
+        with_deposit.safety_deposit = Some(U128(ONE_NEAR / 2));
+
+        set_context(accounts(1), START, 3 * ONE_NEAR);
+        contract.create_htlc_batch(vec![batch_args(1, ONE_NEAR), with_deposit]);
+
+        assert_eq!(contract.get_stats().active_orders, 2);
+        assert_eq!(
+            contract
+                .get_swap_order(order_hash_for(1))
+                .unwrap()
+                .dst_amount,
+            U128(ONE_NEAR)
+        );
+        let order = contract.get_swap_order(order_hash_for(2)).unwrap();
+        assert_eq!(order.dst_amount, U128(3 * ONE_NEAR / 2));
+        assert_eq!(order.resolver, accounts(1));
+        assert_eq!(contract.get_total_locked(), U128(3 * ONE_NEAR));
+
+        complete_order(&mut contract, 2);
+        assert_eq!(created_transfers(), vec![(accounts(2), 3 * ONE_NEAR / 2)]);
+    }
+
+    #[test]
+    fn test_create_htlc_batch_reverts_on_invalid_order() {
+        let mut contract = setup_contract();
+        let mut unsupported = batch_args(2, ONE_NEAR);
+        unsupported.src_chain = "bitcoin".to_string();
+
+        set_context(accounts(1), START, 2 * ONE_NEAR);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            contract.create_htlc_batch(vec![batch_args(1, ONE_NEAR), unsupported])
+        }));
+
+        assert!(result.is_err());
+        // The valid order was never stored
+        assert!(contract.get_swap_order(order_hash_for(1)).is_none());
+        assert_eq!(contract.get_stats().total_orders_created, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Batch amounts must equal attached deposit")]
+    fn test_create_htlc_batch_requires_exact_deposit() {
+        let mut contract = setup_contract();
+        set_context(accounts(1), START, 3 * ONE_NEAR);
+        contract.create_htlc_batch(vec![batch_args(1, ONE_NEAR), batch_args(2, ONE_NEAR)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate order in batch")]
+    fn test_create_htlc_batch_rejects_duplicates() {
+        let mut contract = setup_contract();
+        set_context(accounts(1), START, 2 * ONE_NEAR);
+        contract.create_htlc_batch(vec![batch_args(1, ONE_NEAR), batch_args(1, ONE_NEAR)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate hash lock in batch")]
+    fn test_create_htlc_batch_rejects_shared_hash_lock() {
+        let mut contract = setup_contract();
+        let mut second = batch_args(2, ONE_NEAR);
+        second.hash_lock = hash_lock_for(&secret_for(1));
+        set_context(accounts(1), START, 2 * ONE_NEAR);
+        contract.create_htlc_batch(vec![batch_args(1, ONE_NEAR), second]);
+    }
+
+    #[test]
+    fn test_refund_htlc_batch_skips_unexpired_orders() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        let mut later = TestOrder::new(2, 2 * ONE_NEAR);
+        later.timelock = START + 5 * HOUR;
+        create_test_order(&mut contract, later);
+        create_order(&mut contract, 3, 3 * ONE_NEAR);
+
+        set_context(accounts(3), START + 3 * HOUR, 0);
+        let refunded = contract.refund_htlc_batch(vec![
+            order_hash_for(1),
+            order_hash_for(2),
+            order_hash_for(3),
+            order_hash_for(4),
+        ]);
+
+        assert_eq!(refunded, vec![order_hash_for(1), order_hash_for(3)]);
+        assert_eq!(
+            created_transfers(),
+            vec![(accounts(1), ONE_NEAR), (accounts(1), 3 * ONE_NEAR)]
+        );
+        assert!(contract.is_htlc_active(order_hash_for(2)));
+        assert_eq!(contract.get_stats().total_refunded, 2);
+        assert_eq!(
+            get_logs()
+                .iter()
+                .filter(|log| log.starts_with("Skipping refund"))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_refund_htlc_batch_refunds_after_completion_failures() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        create_order(&mut contract, 2, 2 * ONE_NEAR);
+        for _ in 0..4 {
+            fail_completion(&mut contract, 1);
+        }
+
+        set_context(accounts(1), START + HOUR, 0);
+        let refunded = contract.refund_htlc_batch(vec![order_hash_for(1), order_hash_for(2)]);
+
+        assert_eq!(refunded, vec![order_hash_for(1)]);
+        assert_eq!(order_state(&contract, 1), Some(HTLCState::Refunded));
+        assert!(contract.is_htlc_active(order_hash_for(2)));
+        assert_eq!(created_transfers(), vec![(accounts(1), ONE_NEAR)]);
+    }
+
+    #[test]
+    fn test_two_step_ownership_transfer() {
+        let mut contract = setup_contract();
+        contract.propose_new_owner(accounts(3));
+        assert_eq!(contract.get_owner(), accounts(0));
+        assert_eq!(contract.get_pending_owner(), Some(accounts(3)));
+
+        set_context(accounts(3), START, 0);
+        contract.accept_ownership();
+        assert_eq!(contract.get_owner(), accounts(3));
+        assert_eq!(contract.get_pending_owner(), None);
+
+        // The new owner can administer
+        contract.pause();
+        assert!(contract.is_paused());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only pending owner can accept ownership")]
+    fn test_accept_ownership_requires_pending_owner() {
+        let mut contract = setup_contract();
+        contract.propose_new_owner(accounts(3));
+
+        set_context(accounts(4), START, 0);
+        contract.accept_ownership();
+    }
+
+    #[test]
+    #[should_panic(expected = "Only pending owner can accept ownership")]
+    fn test_cancel_ownership_transfer() {
+        let mut contract = setup_contract();
+        contract.propose_new_owner(accounts(3));
+        contract.cancel_ownership_transfer();
+        assert_eq!(contract.get_pending_owner(), None);
+
+        set_context(accounts(3), START, 0);
+        contract.accept_ownership();
+    }
+
+    fn test_order_hash(contract: &NEAREscrow) -> Base64VecU8 {
+        contract.compute_order_hash(
+            "0xmaker".to_string(),
+            "ethereum".to_string(),
+            "ETH".to_string(),
+            U128(1),
+            accounts(2),
+            "NEAR".to_string(),
+            U128(ONE_NEAR),
+            hash_lock_for(&secret_for(1)),
+            START + 2 * HOUR,
+        )
+    }
+
+    #[test]
+    fn test_compute_order_hash() {
+        let contract = setup_contract();
+        assert_eq!(
+            hex::encode(test_order_hash(&contract).0),
+            "85ebcbe20c80bf15785f231bf39cbaba866dc974334148e1a7b38c42fa1b7c6b"
+        );
+    }
+
+    #[test]
+    fn test_create_with_verified_order_hash() {
+        let mut contract = setup_contract();
+        contract.set_verify_order_hashes(true);
+        let mut args = batch_args(1, ONE_NEAR);
+        args.order_hash = test_order_hash(&contract);
+
+        set_context(accounts(1), START, ONE_NEAR);
+        contract.create_htlc_batch(vec![args]);
+        assert!(contract.is_htlc_active(test_order_hash(&contract)));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ORDER_HASH_MISMATCH: Order hash mismatch")]
+    fn test_create_rejects_mismatched_order_hash() {
+        let mut contract = setup_contract();
+        contract.set_verify_order_hashes(true);
+        create_order(&mut contract, 1, ONE_NEAR);
+    }
+
+    #[test]
+    fn test_orders_by_id_range() {
+        let mut contract = setup_contract();
+        for id in 1..=4 {
+            create_order(&mut contract, id, ONE_NEAR);
+        }
+        let ids: Vec<u64> = (1..=4)
+            .map(|id| {
+                contract
+                    .get_swap_order(order_hash_for(id))
+                    .unwrap()
+                    .order_id
+            })
+            .collect();
+        assert_eq!(ids, vec![0, 1, 2, 3]);
+
+        let page: Vec<_> = contract
+            .get_orders_by_id_range(1, 3)
+            .into_iter()
+            .map(|order| order.order_hash)
+            .collect();
+        assert_eq!(page, vec![order_hash_for(2), order_hash_for(3)]);
+        // Ranges past the last id are clamped
+        assert_eq!(contract.get_orders_by_id_range(3, 100).len(), 1);
+        assert!(contract.get_orders_by_id_range(4, 10).is_empty());
+
+        // Purged orders leave a gap
+        refund_order(&mut contract, 2);
+        purge_order(&mut contract, 2);
+        assert_eq!(contract.get_orders_by_id_range(0, 4).len(), 3);
+    }
+
+    #[test]
+    fn test_id_queries_skip_large_gaps() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        // Stand in for a long run of purged ids
+        contract.next_order_id = u64::MAX / 2;
+        create_order(&mut contract, 2, ONE_NEAR);
+
+        let hashes = |orders: Vec<SwapOrder>| -> Vec<Base64VecU8> {
+            orders.into_iter().map(|order| order.order_hash).collect()
+        };
+        assert_eq!(
+            hashes(contract.get_orders_after_id(Some(0), None)),
+            vec![order_hash_for(2)]
+        );
+        assert_eq!(
+            hashes(contract.get_orders_by_id_range(0, u64::MAX)),
+            vec![order_hash_for(1), order_hash_for(2)]
+        );
+        assert!(contract.get_orders_by_id_range(1, u64::MAX / 2).is_empty());
+    }
+
+    #[test]
+    fn test_id_queries_are_capped() {
+        let mut contract = setup_contract();
+        for id in 1..=(MAX_ORDERS_PER_QUERY as u8 + 1) {
+            create_order(&mut contract, id, ONE_NEAR);
+        }
+        assert_eq!(
+            contract.get_orders_by_id_range(0, u64::MAX).len(),
+            MAX_ORDERS_PER_QUERY
+        );
+        assert_eq!(
+            contract.get_orders_after_id(None, Some(u32::MAX)).len(),
+            MAX_ORDERS_PER_QUERY
+        );
+    }
+
+    #[test]
+    fn test_active_orders_detailed() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        let mut later = TestOrder::new(2, ONE_NEAR);
+        later.timelock = START + 4 * HOUR;
+        create_test_order(&mut contract, later);
+        create_order(&mut contract, 3, ONE_NEAR);
+
+        set_context(accounts(3), START + 3 * HOUR, 0);
+        let page = contract.get_active_orders_detailed(None, Some(10));
+        assert_eq!(page.total_active, 3);
+        let mut remaining: Vec<_> = page
+            .orders
+            .iter()
+            .map(|info| (info.order.order_hash.0[0], info.remaining_ns))
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, vec![(1, 0), (2, HOUR), (3, 0)]);
+
+        // The total covers every page
+        let page = contract.get_active_orders_detailed(Some(1), Some(1));
+        assert_eq!(page.orders.len(), 1);
+        assert_eq!(page.total_active, 3);
+    }
+
+    fn nft_account() -> AccountId {
+        "nft.testnet".parse().unwrap()
+    }
+
+    fn nft_on_transfer(contract: &mut NEAREscrow, msg: String) -> bool {
+        set_context(nft_account(), START, 0);
+        match contract.nft_on_transfer(accounts(3), accounts(1), "token-7".to_string(), msg) {
+            PromiseOrValue::Value(return_nft) => return_nft,
+            PromiseOrValue::Promise(_) => panic!("Expected a value"),
+        }
+    }
+
+    /// Receiver and arguments of the `nft_transfer` calls made by the contract
+    fn created_nft_transfers() -> Vec<(AccountId, serde_json::Value)> {
+        get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id.clone();
+                receipt
+                    .actions
+                    .into_iter()
+                    .filter_map(move |action| match action {
+                        VmAction::FunctionCall {
+                            function_name,
+                            args,
+                            deposit,
+                            ..
+                        } if function_name == "nft_transfer" => {
+                            assert_eq!(deposit, 1);
+                            Some((receiver_id.clone(), serde_json::from_slice(&args).unwrap()))
+                        }
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_nft_on_transfer_creates_nft_order() {
+        let mut contract = setup_contract();
+        assert!(!nft_on_transfer(&mut contract, ft_message(1, "ethereum")));
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(
+            order.dst_asset,
+            DstAsset::Nft {
+                contract: nft_account(),
+                token_id: "token-7".to_string(),
+            }
+        );
+        assert_eq!(order.dst_token, nft_account().to_string());
+        assert_eq!(order.dst_amount, U128(1));
+        assert_eq!(order.resolver, accounts(1));
+        assert_eq!(contract.get_total_locked(), U128(0));
+
+        // Bad messages and unsupported chains send the NFT back
+        assert!(nft_on_transfer(&mut contract, "not json".to_string()));
+        assert!(nft_on_transfer(&mut contract, ft_message(2, "bitcoin")));
+    }
+
+    #[test]
+    fn test_complete_nft_order_calls_nft_transfer() {
+        let mut contract = setup_contract();
+        nft_on_transfer(&mut contract, ft_message(1, "ethereum"));
+
+        complete_order(&mut contract, 1);
+        let transfers = created_nft_transfers();
+        assert_eq!(transfers.len(), 1);
+        let (receiver_id, args) = &transfers[0];
+        assert_eq!(*receiver_id, nft_account());
+        assert_eq!(args["receiver_id"], accounts(2).to_string());
+        assert_eq!(args["token_id"], "token-7");
+        assert!(created_transfers().is_empty());
+        assert_eq!(
+            contract.get_swap_order(order_hash_for(1)).unwrap().state,
+            HTLCState::Completed
+        );
+    }
+
+    #[test]
+    fn test_refund_nft_order_returns_nft_to_resolver() {
+        let mut contract = setup_contract();
+        nft_on_transfer(&mut contract, ft_message(1, "ethereum"));
+
+        refund_order(&mut contract, 1);
+        let transfers = created_nft_transfers();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].0, nft_account());
+        assert_eq!(transfers[0].1["receiver_id"], accounts(1).to_string());
+        assert_eq!(transfers[0].1["token_id"], "token-7");
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_HASH_LOCK: Hash lock already in use")]
+    fn test_create_rejects_active_hash_lock() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                hash_lock: hash_lock_for(&secret_for(1)),
+                ..TestOrder::new(2, ONE_NEAR)
+            },
+        );
+    }
+
+    #[test]
+    fn test_hash_lock_reusable_after_settlement() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        create_order(&mut contract, 2, ONE_NEAR);
+        complete_order(&mut contract, 1);
+        refund_order(&mut contract, 2);
+        assert!(!contract
+            .active_hashlocks
+            .contains_key(&hash_lock_for(&secret_for(1))));
+
+        for (id, secret) in [(3, 1), (4, 2)] {
+            create_test_order(
+                &mut contract,
+                TestOrder {
+                    hash_lock: hash_lock_for(&secret_for(secret)),
+                    ..TestOrder::new(id, ONE_NEAR)
+                },
+            );
+        }
+        assert_eq!(
+            contract
+                .active_hashlocks
+                .get(&hash_lock_for(&secret_for(2))),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_order_memo_round_trip() {
+        let mut contract = setup_contract();
+        let memo = "0xsourcetx".to_string();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                memo: Some(memo.clone()),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.memo, Some(memo.clone()));
+        match &emitted_events()[0].event {
+            HtlcEvent::HtlcCreated(data) => assert_eq!(data[0].memo, Some(memo)),
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_MEMO: Memo too long")]
+    fn test_create_rejects_long_memo() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                memo: Some("x".repeat(MAX_MEMO_LENGTH + 1)),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+    }
+
+    /// Reference rate of 1 ETH (10^18 wei) for 1 NEAR
+    fn set_eth_near_rate(contract: &mut NEAREscrow) {
+        contract.set_reference_rate(
+            "ETH".to_string(),
+            "NEAR".to_string(),
+            U128(10u128.pow(18)),
+            U128(ONE_NEAR),
+        );
+    }
+
+    #[test]
+    fn test_order_within_reference_rate() {
+        let mut contract = setup_contract();
+        set_eth_near_rate(&mut contract);
+
+        // 1 ETH for 0.96 NEAR is within the default 5%
+        let mut args = batch_args(1, ONE_NEAR * 96 / 100);
+        args.src_amount = U128(10u128.pow(18));
+        set_context(accounts(1), START, ONE_NEAR * 96 / 100);
+        contract.create_htlc_batch(vec![args]);
+        assert!(contract.is_htlc_active(order_hash_for(1)));
+
+        // Pairs without a reference rate aren't checked
+        set_context(accounts(0), START, 0);
+        contract.remove_reference_rate("ETH".to_string(), "NEAR".to_string());
+        assert_eq!(
+            contract.get_reference_rate("ETH".to_string(), "NEAR".to_string()),
+            None
+        );
+        create_order(&mut contract, 2, ONE_NEAR);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_RATE_OUT_OF_BOUNDS: Rate deviates from reference")]
+    fn test_order_outside_reference_rate() {
+        let mut contract = setup_contract();
+        set_eth_near_rate(&mut contract);
+        contract.set_max_rate_deviation_bps(100);
+
+        // 1 ETH for 0.98 NEAR is outside 1%
+        let mut args = batch_args(1, ONE_NEAR * 98 / 100);
+        args.src_amount = U128(10u128.pow(18));
+        set_context(accounts(1), START, ONE_NEAR * 98 / 100);
+        contract.create_htlc_batch(vec![args]);
+    }
+
+    #[test]
+    fn test_volume_cap_per_window() {
+        let mut contract = setup_contract();
+        contract.set_volume_cap(U128(3 * ONE_NEAR));
+        contract.set_volume_window(2 * HOUR);
+
+        create_order(&mut contract, 1, ONE_NEAR);
+        create_order(&mut contract, 2, 2 * ONE_NEAR);
+        assert_eq!(contract.get_current_window_volume(), U128(3 * ONE_NEAR));
+
+        // Completing doesn't free up room in the window
+        complete_order(&mut contract, 1);
+        assert_eq!(contract.get_current_window_volume(), U128(3 * ONE_NEAR));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_RATE_LIMITED: Volume cap exceeded for current window")]
+    fn test_volume_cap_rejects_excess() {
+        let mut contract = setup_contract();
+        contract.set_volume_cap(U128(3 * ONE_NEAR));
+
+        create_order(&mut contract, 1, 2 * ONE_NEAR);
+        create_order(&mut contract, 2, 2 * ONE_NEAR);
+    }
+
+    #[test]
+    fn test_volume_window_rolls_over() {
+        let mut contract = setup_contract();
+        contract.set_volume_cap(U128(3 * ONE_NEAR));
+        contract.set_volume_window(HOUR);
+        create_order(&mut contract, 1, 3 * ONE_NEAR);
+
+        set_context(accounts(1), START + HOUR, 2 * ONE_NEAR);
+        assert_eq!(contract.get_current_window_volume(), U128(0));
+        contract.create_htlc_batch(vec![CreateHtlcArgs {
+            timelock: START + 3 * HOUR,
+            ..batch_args(2, 2 * ONE_NEAR)
+        }]);
+        assert_eq!(contract.get_current_window_volume(), U128(2 * ONE_NEAR));
+        assert_eq!(contract.window_start, START + HOUR);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_RESOLVER_NOT_ALLOWED: Resolver is blacklisted")]
+    fn test_create_rejects_blacklisted_resolver() {
+        let mut contract = setup_contract();
+        contract.blacklist_account(accounts(1));
+        create_order(&mut contract, 1, ONE_NEAR);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_RECIPIENT: Recipient is blacklisted")]
+    fn test_complete_rejects_blacklisted_recipient() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(0), START, 0);
+        contract.blacklist_account(accounts(2));
+        complete_order(&mut contract, 1);
+    }
+
+    #[test]
+    fn test_blacklisted_resolver_can_still_refund() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(0), START, 0);
+        contract.blacklist_account(accounts(1));
+        assert!(contract.is_blacklisted(accounts(1)));
+        refund_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(1), ONE_NEAR)]);
+
+        set_context(accounts(0), START, 0);
+        contract.unblacklist_account(accounts(1));
+        create_order(&mut contract, 2, ONE_NEAR);
+    }
+
+    #[test]
+    fn test_refund_goes_to_refund_address() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                refund_to: Some(accounts(4)),
+                safety_deposit: Some(U128(ONE_NEAR)),
+                ..TestOrder::new(1, 3 * ONE_NEAR)
+            },
+        );
+
+        refund_order(&mut contract, 1);
+        assert_eq!(
+            created_transfers(),
+            vec![(accounts(4), 2 * ONE_NEAR), (accounts(4), ONE_NEAR)]
+        );
+    }
+
+    fn extend_timelock(contract: &mut NEAREscrow, id: u8, new_timelock: Timestamp) {
+        set_context(accounts(1), START + HOUR, 0);
+        contract.extend_timelock(order_hash_for(id), new_timelock);
+    }
+
+    #[test]
+    fn test_extend_timelock() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        extend_timelock(&mut contract, 1, START + 5 * HOUR);
+        assert_eq!(
+            contract.get_swap_order(order_hash_for(1)).unwrap().timelock,
+            START + 5 * HOUR
+        );
+        assert_eq!(
+            emitted_events().pop().unwrap().event,
+            HtlcEvent::HtlcTimelockExtended(vec![HtlcTimelockExtendedData {
+                order_hash: order_hash_for(1),
+                old_timelock: START + 2 * HOUR,
+                new_timelock: START + 5 * HOUR,
+            }])
+        );
+
+        // No longer refundable at the old timelock
+        set_context(accounts(1), START + 3 * HOUR, 0);
+        assert!(contract.is_htlc_active(order_hash_for(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "New timelock must be later")]
+    fn test_extend_timelock_rejects_shorter() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        extend_timelock(&mut contract, 1, START + 2 * HOUR - 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Timelock too long")]
+    fn test_extend_timelock_rejects_over_max() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        extend_timelock(&mut contract, 1, START + 24 * HOUR);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only resolver can extend timelock")]
+    fn test_extend_timelock_requires_resolver() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.extend_timelock(order_hash_for(1), START + 5 * HOUR);
+    }
+
+    #[test]
+    fn test_configured_gas_is_applied() {
+        let mut contract = setup_contract();
+        let transfer_gas = Gas(20_000_000_000_000);
+        let resolve_gas = Gas(15_000_000_000_000);
+        contract.set_gas_config(transfer_gas, resolve_gas);
+        assert_eq!(contract.get_gas_config(), (transfer_gas, resolve_gas));
+        ft_on_transfer(&mut contract, 500, ft_message(1, "ethereum"));
+
+        complete_order(&mut contract, 1);
+        let gas: Vec<_> = get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                VmAction::FunctionCall {
+                    function_name, gas, ..
+                } => Some((function_name, gas)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            gas,
+            vec![
+                ("ft_transfer".to_string(), transfer_gas),
+                ("on_transfer_complete".to_string(), resolve_gas),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E_NOT_ENOUGH_GAS: Not enough gas attached")]
+    fn test_complete_requires_enough_gas() {
+        let mut contract = setup_contract();
+        contract.set_gas_config(Gas(200_000_000_000_000), Gas(150_000_000_000_000));
+        create_order(&mut contract, 1, ONE_NEAR);
+        complete_order(&mut contract, 1);
+    }
+
+    #[test]
+    fn test_orders_expiring_within() {
+        let mut contract = setup_contract();
+        for (id, hours) in [(1, 2), (2, 3), (3, 5), (4, 2)] {
+            create_test_order(
+                &mut contract,
+                TestOrder {
+                    timelock: START + hours * HOUR,
+                    ..TestOrder::new(id, ONE_NEAR)
+                },
+            );
+        }
+        // Settled orders aren't listed
+        complete_order(&mut contract, 4);
+
+        // Order 1's timelock has just passed and order 3's is too far out
+        set_context(accounts(3), START + 2 * HOUR + 1, 0);
+        let mut expiring: Vec<u8> = contract
+            .get_orders_expiring_within(2 * HOUR, None, None)
+            .iter()
+            .map(|order| order.order_hash.0[0])
+            .collect();
+        expiring.sort();
+        assert_eq!(expiring, vec![2]);
+
+        set_context(accounts(3), START + HOUR, 0);
+        let mut expiring: Vec<u8> = contract
+            .get_orders_expiring_within(2 * HOUR, None, None)
+            .iter()
+            .map(|order| order.order_hash.0[0])
+            .collect();
+        expiring.sort();
+        assert_eq!(expiring, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_blake2b256_test_vectors() {
+        // Published BLAKE2b-256 vectors for "" and "abc"
+        assert_eq!(
+            hex::encode(HashAlgorithm::Blake2b256.digest(b"")),
+            "0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8"
+        );
+        assert_eq!(
+            hex::encode(HashAlgorithm::Blake2b256.digest(b"abc")),
+            "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319"
+        );
+    }
+
+    #[test]
+    fn test_digest_len_matches_digest() {
+        for algorithm in [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Keccak256,
+            HashAlgorithm::Blake2b256,
+            HashAlgorithm::Hash160,
+        ] {
+            assert_eq!(algorithm.digest_len(), algorithm.digest(b"secret").len());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_HASH_LOCK: Invalid hash lock length")]
+    fn test_create_rejects_short_hash_lock() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                hash_lock: Base64VecU8(vec![1; 20]),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_HASH_LOCK: Invalid hash lock length")]
+    fn test_create_rejects_long_keccak_hash_lock() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                hash_lock: Base64VecU8(vec![1; 64]),
+                hash_algorithm: Some(HashAlgorithm::Keccak256),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+    }
+
+    #[test]
+    fn test_resolver_stats() {
+        let mut contract = setup_contract();
+        assert_eq!(contract.get_resolver_stats(accounts(1)), None);
+        create_order(&mut contract, 1, ONE_NEAR);
+        create_order(&mut contract, 2, 2 * ONE_NEAR);
+        create_order(&mut contract, 3, 3 * ONE_NEAR);
+        complete_order(&mut contract, 1);
+        complete_order(&mut contract, 2);
+        refund_order(&mut contract, 3);
+
+        assert_eq!(
+            contract.get_resolver_stats(accounts(1)),
+            Some(ResolverStats {
+                orders_created: 3,
+                completed: 2,
+                refunded: 1,
+                total_volume: U128(3 * ONE_NEAR),
+            })
+        );
+
+        // A failed payout takes the completion back
+        set_promise_result(PromiseResult::Failed);
+        contract.on_transfer_complete(
+            order_hash_for(2),
+            U128(2 * ONE_NEAR),
+            accounts(2),
+            U128(0),
+            U128(0),
+        );
+        let stats = contract.get_resolver_stats(accounts(1)).unwrap();
+        assert_eq!(stats.completed, 1);
+        assert_eq!(stats.total_volume, U128(ONE_NEAR));
+    }
+
+    #[test]
+    fn test_verify_secret_for_order() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                hash_lock: Base64VecU8(env::keccak256(&secret_for(2).0)),
+                hash_algorithm: Some(HashAlgorithm::Keccak256),
+                ..TestOrder::new(2, ONE_NEAR)
+            },
+        );
+
+        assert!(contract.verify_secret_for_order(order_hash_for(1), secret_for(1)));
+        assert!(!contract.verify_secret_for_order(order_hash_for(1), secret_for(2)));
+        assert!(contract.verify_secret_for_order(order_hash_for(2), secret_for(2)));
+        assert!(!contract.verify_secret_for_order(order_hash_for(2), secret_for(1)));
+        // The SHA-256 digest doesn't unlock a Keccak-256 order
+        assert!(!contract.verify_secret(
+            secret_for(2),
+            Base64VecU8(env::keccak256(&secret_for(2).0)),
+            None
+        ));
+        assert!(!contract.verify_secret_for_order(order_hash_for(3), secret_for(3)));
+    }
+
+    fn reassign_to_danny(contract: &mut NEAREscrow) {
+        set_context(accounts(3), START, ONE_NEAR);
+        contract.storage_deposit(None, None);
+        set_context(accounts(1), START + HOUR, 0);
+        contract.reassign_resolver(order_hash_for(1), accounts(3));
+    }
+
+    #[test]
+    fn test_reassign_resolver() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        let storage_bytes = contract
+            .get_swap_order(order_hash_for(1))
+            .unwrap()
+            .storage_bytes;
+        reassign_to_danny(&mut contract);
+
+        assert_eq!(
+            emitted_events().pop().unwrap().event,
+            HtlcEvent::HtlcResolverReassigned(vec![HtlcResolverReassignedData {
+                order_hash: order_hash_for(1),
+                old_resolver: accounts(1),
+                new_resolver: accounts(3),
+            }])
+        );
+        // The storage charge moved with the order
+        let min = contract.storage_balance_bounds().min.0;
+        assert_eq!(
+            storage_available(&contract, accounts(1)),
+            RESOLVER_STORAGE - min
+        );
+        assert_eq!(
+            storage_available(&contract, accounts(3)),
+            ONE_NEAR - min - storage_bytes as Balance * env::storage_byte_cost()
+        );
+
+        // The new resolver can extend and gets the refund
+        set_context(accounts(3), START + HOUR, 0);
+        contract.extend_timelock(order_hash_for(1), START + 3 * HOUR);
+        set_context(accounts(3), START + 4 * HOUR, 0);
+        contract.refund_htlc(order_hash_for(1));
+        assert_eq!(created_transfers(), vec![(accounts(3), ONE_NEAR)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only resolver can extend timelock")]
+    fn test_old_resolver_loses_access_after_reassign() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        reassign_to_danny(&mut contract);
+
+        extend_timelock(&mut contract, 1, START + 3 * HOUR);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only resolver can reassign")]
+    fn test_reassign_requires_resolver() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.reassign_resolver(order_hash_for(1), accounts(2));
+    }
+
+    fn assert_locked(contract: &NEAREscrow, token: &str, amount: Balance) {
+        assert_eq!(contract.get_locked_balance(token.to_string()), U128(amount));
+        set_context(contract_account(), START + 3 * HOUR, 0);
+        assert!(contract.check_locked_invariant(token.to_string()));
+    }
+
+    #[test]
+    fn test_locked_balance_by_token() {
+        let mut contract = setup_contract();
+        let token = token_account().to_string();
+        create_safety_deposit_order(&mut contract);
+        create_order(&mut contract, 2, 10 * ONE_NEAR);
+        ft_on_transfer(&mut contract, 1_000, ft_message(3, "ethereum"));
+        ft_on_transfer(&mut contract, 500, ft_message(4, "ethereum"));
+        assert_locked(&contract, "NEAR", 21 * ONE_NEAR);
+        assert_locked(&contract, &token, 1_500);
+
+        // A failed payout puts the amount back under lock
+        complete_order(&mut contract, 3);
+        assert_eq!(contract.get_locked_balance(token.clone()), U128(500));
+        set_promise_result(PromiseResult::Failed);
+        contract.on_transfer_complete(
+            order_hash_for(3),
+            U128(1_000),
+            accounts(2),
+            U128(0),
+            U128(0),
+        );
+        assert_locked(&contract, &token, 1_500);
+
+        complete_order(&mut contract, 2);
+        refund_order(&mut contract, 1);
+        refund_order(&mut contract, 4);
+        assert_locked(&contract, "NEAR", 0);
+        assert_locked(&contract, &token, 1_000);
+    }
+
+    fn create_self_dealing_order(contract: &mut NEAREscrow) {
+        let mut args = batch_args(1, ONE_NEAR);
+        args.dst_recipient = accounts(1);
+        set_context(accounts(1), START, ONE_NEAR);
+        contract.create_htlc_batch(vec![args]);
+    }
+
+    #[test]
+    fn test_self_dealing_allowed_by_default() {
+        let mut contract = setup_contract();
+        create_self_dealing_order(&mut contract);
+        assert!(contract.is_htlc_active(order_hash_for(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_RECIPIENT: Recipient cannot be the resolver")]
+    fn test_reject_self_dealing() {
+        let mut contract = setup_contract();
+        contract.set_reject_self_dealing(true);
+        create_self_dealing_order(&mut contract);
+    }
+
+    fn create_relayed_order(contract: &mut NEAREscrow) {
+        create_test_order(
+            contract,
+            TestOrder {
+                relayer_fee: Some(U128(ONE_NEAR / 10)),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+    }
+
+    #[test]
+    fn test_relayer_submitted_completion_pays_relayer_fee() {
+        let mut contract = setup_contract();
+        create_relayed_order(&mut contract);
+
+        set_context(accounts(4), START + HOUR, 0);
+        contract.complete_htlc(order_hash_for(1), secret_for(1), None, None);
+        assert_eq!(created_transfers(), vec![(accounts(2), ONE_NEAR * 9 / 10)]);
+
+        set_promise_result(PromiseResult::Successful(vec![]));
+        assert!(contract.on_transfer_complete(
+            order_hash_for(1),
+            U128(ONE_NEAR),
+            accounts(4),
+            U128(ONE_NEAR / 10),
+            U128(0)
+        ));
+        assert_eq!(created_transfers(), vec![(accounts(4), ONE_NEAR / 10)]);
+    }
+
+    #[test]
+    fn test_failed_relayer_fee_transfer_becomes_claimable() {
+        let mut contract = setup_contract();
+        create_relayed_order(&mut contract);
+
+        set_context(accounts(4), START + HOUR, 0);
+        contract.complete_htlc(order_hash_for(1), secret_for(1), None, None);
+        set_promise_result(PromiseResult::Successful(vec![]));
+        contract.on_transfer_complete(
+            order_hash_for(1),
+            U128(ONE_NEAR),
+            accounts(4),
+            U128(ONE_NEAR / 10),
+            U128(0),
+        );
+        assert!(created_function_calls()
+            .contains(&(contract_account(), "on_claim_complete".to_string())));
+
+        set_promise_result(PromiseResult::Failed);
+        assert!(!contract.on_claim_complete(accounts(4), "NEAR".to_string(), U128(ONE_NEAR / 10)));
+        assert_eq!(
+            contract.get_claimable(accounts(4), "NEAR".to_string()),
+            U128(ONE_NEAR / 10)
+        );
+    }
+
+    #[test]
+    fn test_self_submitted_completion_takes_no_relayer_fee() {
+        let mut contract = setup_contract();
+        create_relayed_order(&mut contract);
+
+        complete_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(2), ONE_NEAR)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_AMOUNT: Relayer fee must be below the order amount")]
+    fn test_relayer_fee_must_be_below_amount() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                relayer_fee: Some(U128(ONE_NEAR)),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+    }
+
+    fn set_refund_grace(contract: &mut NEAREscrow) {
+        set_context(accounts(0), START, 0);
+        contract.set_refund_grace_config(HOUR, 100);
+    }
+
+    #[test]
+    fn test_cleanup_refund_after_grace_pays_reward() {
+        let mut contract = setup_contract();
+        set_refund_grace(&mut contract);
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(3), START + 3 * HOUR + 1, 0);
+        contract.refund_htlc(order_hash_for(1));
+        assert_eq!(
+            created_transfers(),
+            vec![
+                (accounts(3), ONE_NEAR / 100),
+                (accounts(1), ONE_NEAR * 99 / 100)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolver_refund_within_grace_gets_full_amount() {
+        let mut contract = setup_contract();
+        set_refund_grace(&mut contract);
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(1), START + 2 * HOUR + 1, 0);
+        contract.refund_htlc(order_hash_for(1));
+        assert_eq!(created_transfers(), vec![(accounts(1), ONE_NEAR)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_UNAUTHORIZED: Only resolver can refund during the grace period")]
+    fn test_cleanup_refund_within_grace_rejected() {
+        let mut contract = setup_contract();
+        set_refund_grace(&mut contract);
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(3), START + 3 * HOUR, 0);
+        contract.refund_htlc(order_hash_for(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid cleanup reward")]
+    fn test_cleanup_reward_is_bounded() {
+        let mut contract = setup_contract();
+        contract.set_refund_grace_config(HOUR, MAX_CLEANUP_REWARD_BPS + 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ORDER_EXISTS: Order hash already used")]
+    fn test_purged_order_hash_cannot_be_reused() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        complete_order(&mut contract, 1);
+        purge_order(&mut contract, 1);
+        assert!(contract.get_swap_order(order_hash_for(1)).is_none());
+        assert!(contract.is_order_hash_used(order_hash_for(1)));
+
+        create_order(&mut contract, 1, ONE_NEAR);
+    }
+
+    #[test]
+    fn test_get_swap_orders() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        create_order(&mut contract, 3, 3 * ONE_NEAR);
+
+        let orders = contract.get_swap_orders(vec![
+            order_hash_for(3),
+            order_hash_for(2),
+            order_hash_for(1),
+        ]);
+        let amounts: Vec<Option<U128>> = orders
+            .iter()
+            .map(|order| order.as_ref().map(|order| order.dst_amount))
+            .collect();
+        assert_eq!(
+            amounts,
+            vec![Some(U128(3 * ONE_NEAR)), None, Some(U128(ONE_NEAR))]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many order hashes")]
+    fn test_get_swap_orders_is_capped() {
+        let contract = setup_contract();
+        contract.get_swap_orders(vec![order_hash_for(1); MAX_ORDERS_PER_QUERY + 1]);
+    }
+
+    fn create_overridable_order(contract: &mut NEAREscrow, allow_recipient_override: bool) {
+        create_test_order(
+            contract,
+            TestOrder {
+                allow_recipient_override: Some(allow_recipient_override),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+    }
+
+    #[test]
+    fn test_complete_htlc_to_overridden_recipient() {
+        let mut contract = setup_contract();
+        create_overridable_order(&mut contract, true);
+
+        set_context(accounts(1), START + HOUR, 0);
+        contract.complete_htlc_to(order_hash_for(1), secret_for(1), accounts(4));
+        assert_eq!(created_transfers(), vec![(accounts(4), ONE_NEAR)]);
+        assert_eq!(
+            contract.get_swap_order(order_hash_for(1)).unwrap().state,
+            HTLCState::Completed
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Recipient override not allowed")]
+    fn test_complete_htlc_to_requires_override_flag() {
+        let mut contract = setup_contract();
+        create_overridable_order(&mut contract, false);
+
+        set_context(accounts(1), START + HOUR, 0);
+        contract.complete_htlc_to(order_hash_for(1), secret_for(1), accounts(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only resolver can override the recipient")]
+    fn test_complete_htlc_to_requires_resolver() {
+        let mut contract = setup_contract();
+        create_overridable_order(&mut contract, true);
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc_to(order_hash_for(1), secret_for(1), accounts(4));
+    }
+
+    #[test]
+    fn test_completion_waits_for_source_confirmations() {
+        let mut contract = setup_contract();
+        contract.set_chain_config(
+            "ethereum".to_string(),
+            ChainConfig {
+                chain_id: 1,
+                min_confirmations: 12,
+                enabled: true,
+            },
+        );
+        contract.set_height_oracle(Some(accounts(5)));
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                src_block_height: Some(100),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+
+        set_context(accounts(5), START, 0);
+        contract.update_chain_height("ethereum".to_string(), 111);
+        let blocked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            complete_order(&mut contract, 1)
+        }));
+        assert!(blocked.is_err());
+
+        set_context(accounts(5), START, 0);
+        contract.update_chain_height("ethereum".to_string(), 112);
+        complete_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(2), ONE_NEAR)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_NOT_FINAL: Source transaction not confirmed")]
+    fn test_completion_blocked_without_chain_height() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                src_block_height: Some(100),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+        complete_order(&mut contract, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner or height oracle can update chain heights")]
+    fn test_update_chain_height_requires_oracle() {
+        let mut contract = setup_contract();
+        set_context(accounts(5), START, 0);
+        contract.update_chain_height("ethereum".to_string(), 1);
+    }
+
+    fn order_state(contract: &NEAREscrow, id: u8) -> Option<HTLCState> {
+        contract.get_order_state(order_hash_for(id))
+    }
+
+    #[test]
+    fn test_get_order_state() {
+        let mut contract = setup_contract();
+        for id in 1..=3 {
+            create_order(&mut contract, id, ONE_NEAR);
+        }
+        assert_eq!(order_state(&contract, 1), Some(HTLCState::Active));
+        assert_eq!(order_state(&contract, 4), None);
+
+        complete_order(&mut contract, 1);
+        assert_eq!(order_state(&contract, 1), Some(HTLCState::Completed));
+
+        // Past the timelock an active order reads as expired without being marked
+        set_context(accounts(3), START + 3 * HOUR, 0);
+        assert_eq!(order_state(&contract, 2), Some(HTLCState::Expired));
+        assert_eq!(
+            contract.get_swap_order(order_hash_for(2)).unwrap().state,
+            HTLCState::Active
+        );
+
+        contract.expire_htlc(order_hash_for(3));
+        assert_eq!(order_state(&contract, 3), Some(HTLCState::Expired));
+        refund_order(&mut contract, 2);
+        assert_eq!(order_state(&contract, 2), Some(HTLCState::Refunded));
+    }
+
+    #[test]
+    fn test_refund_partially_filled_order_returns_remainder() {
+        let mut contract = setup_contract();
+        let (proof_0, _) = create_merkle_order(&mut contract);
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc_partial(
+            order_hash_for(1),
+            secret_for(1),
+            U128(4 * ONE_NEAR),
+            vec![proof_0],
+            0,
+            None,
+        );
+
+        refund_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(1), 6 * ONE_NEAR)]);
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.state, HTLCState::Refunded);
+        assert_eq!(order.filled_amount, U128(4 * ONE_NEAR));
+        assert_eq!(contract.get_total_locked(), U128(0));
+    }
+
+    #[test]
+    fn test_refund_unfilled_merkle_order_returns_full_deposit() {
+        let mut contract = setup_contract();
+        create_merkle_order(&mut contract);
+
+        refund_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(1), 10 * ONE_NEAR)]);
+        assert_eq!(
+            contract.get_swap_order(order_hash_for(1)).unwrap().state,
+            HTLCState::Refunded
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_AMOUNT: Deposit below minimum")]
+    fn test_deposit_below_minimum() {
+        let mut contract = setup_contract();
+        contract.set_order_amount_limits(U128(0), U128(Balance::MAX));
+        contract.set_min_deposit(U128(ONE_NEAR / 10));
+        create_order(&mut contract, 1, ONE_NEAR / 20);
+    }
+
+    #[test]
+    fn test_event_checkpoint_advances() {
+        let mut contract = setup_contract();
+        assert_eq!(contract.get_event_checkpoint(), (0, 0));
+
+        create_order(&mut contract, 1, ONE_NEAR);
+        assert_eq!(contract.get_event_checkpoint(), (0, 1));
+
+        let mut context = get_context(accounts(2));
+        context.block_timestamp(START + HOUR).block_index(42);
+        testing_env!(context.build());
+        contract.complete_htlc(order_hash_for(1), secret_for(1), None, None);
+        assert_eq!(contract.get_event_checkpoint(), (42, 2));
+    }
+
+    fn contribute(contract: &mut NEAREscrow, contributor: AccountId, amount: Balance) {
+        set_context(contributor, START + HOUR, amount);
+        contract.contribute_to_htlc(order_hash_for(1));
+    }
+
+    #[test]
+    fn test_refund_returns_each_contribution() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        contribute(&mut contract, accounts(3), 2 * ONE_NEAR);
+        contribute(&mut contract, accounts(4), 3 * ONE_NEAR);
+        contribute(&mut contract, accounts(3), ONE_NEAR);
+        assert_eq!(
+            contract.get_contributions(order_hash_for(1)),
+            vec![
+                (accounts(3), U128(3 * ONE_NEAR)),
+                (accounts(4), U128(3 * ONE_NEAR))
+            ]
+        );
+        assert_eq!(contract.get_total_locked(), U128(7 * ONE_NEAR));
+
+        refund_order(&mut contract, 1);
+        assert_eq!(
+            created_transfers(),
+            vec![
+                (accounts(1), ONE_NEAR),
+                (accounts(3), 3 * ONE_NEAR),
+                (accounts(4), 3 * ONE_NEAR)
+            ]
+        );
+        assert!(contract.get_contributions(order_hash_for(1)).is_empty());
+    }
+
+    #[test]
+    fn test_completion_pays_total_with_contributions() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        contribute(&mut contract, accounts(3), 2 * ONE_NEAR);
+
+        complete_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(2), 3 * ONE_NEAR)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Order not active")]
+    fn test_contribute_requires_active_order() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        complete_order(&mut contract, 1);
+
+        contribute(&mut contract, accounts(3), ONE_NEAR);
+    }
+
+    fn announce_order(contract: &mut NEAREscrow) {
+        set_context(accounts(1), START, 0);
+        contract.announce_htlc(batch_args(1, ONE_NEAR));
+    }
+
+    #[test]
+    fn test_announce_fund_and_complete() {
+        let mut contract = setup_contract();
+        announce_order(&mut contract);
+        assert_eq!(
+            contract.get_order_state(order_hash_for(1)),
+            Some(HTLCState::Announced)
+        );
+        assert!(!contract.is_htlc_active(order_hash_for(1)));
+        assert_eq!(contract.get_total_locked(), U128(0));
+
+        set_context(accounts(1), START + MINUTE, ONE_NEAR);
+        contract.fund_htlc(order_hash_for(1));
+        assert!(contract.is_htlc_active(order_hash_for(1)));
+        assert_eq!(contract.get_total_locked(), U128(ONE_NEAR));
+        assert_eq!(contract.get_stats().total_orders_created, 1);
+
+        complete_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(2), ONE_NEAR)]);
+    }
+
+    #[test]
+    fn test_announce_and_cancel() {
+        let mut contract = setup_contract();
+        announce_order(&mut contract);
+
+        set_context(accounts(1), START + MINUTE, 0);
+        contract.cancel_announced(order_hash_for(1));
+        assert!(contract.get_swap_order(order_hash_for(1)).is_none());
+        assert_eq!(
+            storage_available(&contract, accounts(1)),
+            RESOLVER_STORAGE - contract.storage_balance_bounds().min.0
+        );
+        assert_eq!(
+            emitted_events().pop().unwrap().event,
+            HtlcEvent::HtlcCancelled(vec![HtlcCancelledData {
+                order_hash: order_hash_for(1),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_cancel_announced_leaves_indexes() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 2, ONE_NEAR);
+        announce_order(&mut contract);
+        create_order(&mut contract, 3, ONE_NEAR);
+
+        set_context(accounts(1), START + MINUTE, 0);
+        contract.cancel_announced(order_hash_for(1));
+
+        let hashes = |orders: Vec<SwapOrder>| -> Vec<Base64VecU8> {
+            orders.into_iter().map(|order| order.order_hash).collect()
+        };
+        let remaining = vec![order_hash_for(2), order_hash_for(3)];
+        assert_eq!(
+            hashes(contract.get_orders_by_maker("0xmaker".to_string(), None, None)),
+            remaining
+        );
+        assert_eq!(
+            hashes(contract.get_orders_by_recipient(accounts(2), None, None)),
+            remaining
+        );
+        assert_eq!(
+            hashes(contract.get_orders_by_chain("ethereum".to_string(), None, None)),
+            remaining
+        );
+        // The entry itself is gone, so pages don't come up short
+        assert_eq!(
+            hashes(contract.get_orders_by_chain("ethereum".to_string(), Some(1), Some(1))),
+            vec![order_hash_for(3)]
+        );
+        assert_eq!(
+            contract
+                .chain_orders
+                .get(&"ethereum".to_string())
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ORDER_NOT_ACTIVE: Order not active")]
+    fn test_announced_order_cannot_be_completed() {
+        let mut contract = setup_contract();
+        announce_order(&mut contract);
+        complete_order(&mut contract, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_AMOUNT: Deposit must match announced amount")]
+    fn test_fund_requires_announced_amount() {
+        let mut contract = setup_contract();
+        announce_order(&mut contract);
+
+        set_context(accounts(1), START + MINUTE, ONE_NEAR / 2);
+        contract.fund_htlc(order_hash_for(1));
+    }
+
+    fn is_completable(contract: &NEAREscrow, id: u8, secret_id: u8) -> bool {
+        contract.is_order_completable(order_hash_for(id), secret_for(secret_id))
+    }
+
+    #[test]
+    fn test_is_order_completable() {
+        let mut contract = setup_contract();
+        for id in 1..=3 {
+            create_order(&mut contract, id, ONE_NEAR);
+        }
+        complete_order(&mut contract, 3);
+
+        set_context(accounts(2), START + HOUR, 0);
+        assert!(is_completable(&contract, 1, 1));
+        assert!(!is_completable(&contract, 1, 2));
+        assert!(!is_completable(&contract, 3, 3));
+        assert!(!is_completable(&contract, 4, 4));
+
+        set_context(accounts(2), START + 3 * HOUR, 0);
+        assert!(!is_completable(&contract, 2, 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_SECRET: Secret too short")]
+    fn test_complete_rejects_short_secret() {
+        let mut contract = setup_contract();
+        let secret = Base64VecU8(b"short".to_vec());
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                hash_lock: hash_lock_for(&secret),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+        set_context(accounts(2), START + HOUR, 0);
+        assert!(!contract.is_order_completable(order_hash_for(1), secret.clone()));
+
+        contract.complete_htlc(order_hash_for(1), secret, None, None);
+    }
+
+    #[test]
+    fn test_complete_accepts_full_length_secret() {
+        let mut contract = setup_contract();
+        assert_eq!(secret_for(1).0.len(), 32);
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        complete_order(&mut contract, 1);
+        assert_eq!(
+            contract.get_swap_order(order_hash_for(1)).unwrap().state,
+            HTLCState::Completed
+        );
+    }
+
+    #[test]
+    fn test_get_config_reflects_setters() {
+        let mut contract = setup_contract();
+        let defaults = contract.get_config();
+        assert_eq!(defaults.owner, accounts(0));
+        assert!(!defaults.paused);
+        assert_eq!(defaults.min_secret_length, 32);
+
+        contract.update_timelock_limits(2 * HOUR, 48 * HOUR);
+        contract.set_fee_bps(25);
+        contract.pause();
+        contract.set_order_amount_limits(U128(ONE_NEAR), U128(100 * ONE_NEAR));
+        contract.set_min_deposit(U128(ONE_NEAR / 10));
+        contract.set_allowlist_enabled(true);
+        contract.set_reject_self_dealing(true);
+
+        assert_eq!(
+            contract.get_config(),
+            ContractConfig {
+                min_timelock: 2 * HOUR,
+                max_timelock: 48 * HOUR,
+                fee_bps: 25,
+                paused: true,
+                min_order_amount: U128(ONE_NEAR),
+                max_order_amount: U128(100 * ONE_NEAR),
+                min_deposit: U128(ONE_NEAR / 10),
+                resolver_allowlist_enabled: true,
+                reject_self_dealing: true,
+                ..defaults
+            }
+        );
+    }
+
+    fn dispute(contract: &mut NEAREscrow, id: u8) {
+        set_context(accounts(0), START, 0);
+        contract.dispute_order(order_hash_for(id));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ORDER_NOT_ACTIVE: Order not active")]
+    fn test_disputed_order_cannot_complete() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        dispute(&mut contract, 1);
+        complete_order(&mut contract, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ORDER_NOT_ACTIVE: Order not active")]
+    fn test_disputed_order_cannot_refund() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        dispute(&mut contract, 1);
+        refund_order(&mut contract, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner can call this method")]
+    fn test_dispute_order_owner_only() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        set_context(accounts(1), START, 0);
+        contract.dispute_order(order_hash_for(1));
+    }
+
+    #[test]
+    fn test_disputed_order_left_out_of_active_views() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        create_order(&mut contract, 2, 2 * ONE_NEAR);
+        dispute(&mut contract, 2);
+
+        let hashes = |orders: Vec<SwapOrder>| -> Vec<Base64VecU8> {
+            orders.into_iter().map(|order| order.order_hash).collect()
+        };
+        let active = vec![order_hash_for(1)];
+        assert_eq!(hashes(contract.get_active_orders(None, None)), active);
+        assert_eq!(
+            hashes(contract.get_orders_by_state(HTLCState::Active, None, None)),
+            active
+        );
+        assert_eq!(hashes(contract.get_largest_active_orders(None)), active);
+        let page = contract.get_active_orders_detailed(None, None);
+        assert_eq!(page.total_active, 1);
+        assert_eq!(contract.get_stats().active_orders, 1);
+        assert_eq!(
+            hashes(contract.get_orders_by_state(HTLCState::Disputed, None, None)),
+            vec![order_hash_for(2)]
+        );
+
+        contract.resolve_dispute(order_hash_for(2), true);
+        assert_eq!(contract.get_stats().active_orders, 2);
+    }
+
+    #[test]
+    fn test_resolve_dispute_allows_completion() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        dispute(&mut contract, 1);
+        assert_eq!(order_state(&contract, 1), Some(HTLCState::Disputed));
+
+        contract.resolve_dispute(order_hash_for(1), true);
+        assert_eq!(order_state(&contract, 1), Some(HTLCState::Active));
+        assert_eq!(
+            emitted_events()[1].event,
+            HtlcEvent::HtlcDisputeResolved(vec![HtlcDisputeResolvedData {
+                order_hash: order_hash_for(1),
+                allow_complete: true,
+            }])
+        );
+        complete_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(2), ONE_NEAR)]);
+    }
+
+    #[test]
+    fn test_resolve_dispute_forces_refund() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        dispute(&mut contract, 1);
+        assert_eq!(
+            emitted_events()[0].event,
+            HtlcEvent::HtlcDisputed(vec![HtlcDisputedData {
+                order_hash: order_hash_for(1),
+            }])
+        );
+
+        contract.resolve_dispute(order_hash_for(1), false);
+        assert_eq!(order_state(&contract, 1), Some(HTLCState::Refunded));
+        assert_eq!(created_transfers(), vec![(accounts(1), ONE_NEAR)]);
+        assert!(!contract.active_order_hashes.contains(&order_hash_for(1)));
+        assert_locked(&contract, "NEAR", 0);
+    }
+
+    #[test]
+    fn test_register_token() {
+        let mut contract = setup_contract();
+        assert_eq!(contract.get_token_info(token_account().to_string()), None);
+
+        contract.register_token(token_account().to_string(), 6, "USDC".to_string());
+        assert_eq!(
+            contract.get_token_info(token_account().to_string()),
+            Some(TokenInfo {
+                decimals: 6,
+                symbol: "USDC".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Only owner can call this method")]
+    fn test_register_token_owner_only() {
+        let mut contract = setup_contract();
+        set_context(accounts(1), START, 0);
+        contract.register_token(token_account().to_string(), 6, "USDC".to_string());
+    }
+
+    fn fail_completion(contract: &mut NEAREscrow, id: u8) {
+        complete_order(contract, id);
+        set_promise_result(PromiseResult::Failed);
+        contract.on_transfer_complete(
+            order_hash_for(id),
+            U128(ONE_NEAR),
+            accounts(2),
+            U128(0),
+            U128(0),
+        );
+    }
+
+    #[test]
+    fn test_repeated_completion_failures_unlock_refund() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        for _ in 0..4 {
+            fail_completion(&mut contract, 1);
+        }
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.completion_attempts, 4);
+
+        set_context(accounts(1), START + HOUR, 0);
+        contract.refund_htlc(order_hash_for(1));
+        assert_eq!(order_state(&contract, 1), Some(HTLCState::Refunded));
+        assert_eq!(created_transfers(), vec![(accounts(1), ONE_NEAR)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ORDER_NOT_EXPIRED: HTLC not expired")]
+    fn test_completion_failures_within_limit_keep_timelock() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        for _ in 0..3 {
+            fail_completion(&mut contract, 1);
+        }
+        set_context(accounts(1), START + HOUR, 0);
+        contract.refund_htlc(order_hash_for(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_ORDER_NOT_EXPIRED: HTLC not expired")]
+    fn test_early_refund_after_failures_resolver_only() {
+        let mut contract = setup_contract();
+        set_context(accounts(0), START, 0);
+        contract.set_max_completion_attempts(0);
+        create_order(&mut contract, 1, ONE_NEAR);
+        fail_completion(&mut contract, 1);
+        set_context(accounts(3), START + HOUR, 0);
+        contract.refund_htlc(order_hash_for(1));
+    }
+
+    fn alt_hash_lock_order(contract: &mut NEAREscrow) {
+        let mut order = TestOrder::new(1, ONE_NEAR);
+        order.alt_hash_locks = Some((2..=4).map(|id| hash_lock_for(&secret_for(id))).collect());
+        create_test_order(contract, order);
+    }
+
+    #[test]
+    fn test_complete_with_primary_hash_lock() {
+        let mut contract = setup_contract();
+        alt_hash_lock_order(&mut contract);
+        complete_order(&mut contract, 1);
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.state, HTLCState::Completed);
+        assert_eq!(order.matched_hash_lock, Some(0));
+    }
+
+    #[test]
+    fn test_complete_with_last_alt_hash_lock() {
+        let mut contract = setup_contract();
+        alt_hash_lock_order(&mut contract);
+        set_context(accounts(2), START + HOUR, 0);
+        assert!(contract.is_order_completable(order_hash_for(1), secret_for(4)));
+        contract.complete_htlc(order_hash_for(1), secret_for(4), None, None);
+
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.state, HTLCState::Completed);
+        assert_eq!(order.matched_hash_lock, Some(3));
+        assert_eq!(created_transfers(), vec![(accounts(2), ONE_NEAR)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_SECRET: Invalid secret")]
+    fn test_complete_rejects_secret_matching_no_hash_lock() {
+        let mut contract = setup_contract();
+        alt_hash_lock_order(&mut contract);
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc(order_hash_for(1), secret_for(5), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_HASH_LOCK: Too many hash locks")]
+    fn test_create_rejects_too_many_hash_locks() {
+        let mut contract = setup_contract();
+        let mut order = TestOrder::new(1, ONE_NEAR);
+        order.alt_hash_locks = Some((2..=5).map(|id| hash_lock_for(&secret_for(id))).collect());
+        create_test_order(&mut contract, order);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_HASH_LOCK: Hash lock already in use")]
+    fn test_create_rejects_hash_lock_active_as_alt() {
+        let mut contract = setup_contract();
+        alt_hash_lock_order(&mut contract);
+        create_order(&mut contract, 2, ONE_NEAR);
     }
 
-    // Owner functions
+    #[test]
+    #[should_panic(expected = "E_INVALID_HASH_LOCK: Hash lock already in use")]
+    fn test_create_rejects_alt_hash_lock_in_use() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        let mut order = TestOrder::new(2, ONE_NEAR);
+        order.alt_hash_locks = Some(vec![hash_lock_for(&secret_for(1))]);
+        create_test_order(&mut contract, order);
+    }
 
-    /// Add supported chain (owner only)
-    pub fn add_supported_chain(&mut self, chain: String) {
-        self.assert_owner();
-        self.supported_chains.insert(&chain, &true);
+    #[test]
+    fn test_alt_hash_locks_released_on_completion() {
+        let mut contract = setup_contract();
+        alt_hash_lock_order(&mut contract);
+        complete_order(&mut contract, 1);
+
+        create_order(&mut contract, 2, ONE_NEAR);
+        assert_eq!(order_state(&contract, 2), Some(HTLCState::Active));
     }
 
-    /// Remove supported chain (owner only)
-    pub fn remove_supported_chain(&mut self, chain: String) {
-        self.assert_owner();
-        self.supported_chains.insert(&chain, &false);
+    const RESCUE_AT: Timestamp = START + 2 * HOUR + FORCE_REFUND_DELAY + 1;
+
+    #[test]
+    fn test_owner_force_refund() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(0), RESCUE_AT, 0);
+        contract.owner_force_refund(order_hash_for(1), accounts(4));
+        assert_eq!(order_state(&contract, 1), Some(HTLCState::Refunded));
+        assert_eq!(created_transfers(), vec![(accounts(4), ONE_NEAR)]);
+        assert_eq!(
+            emitted_events()[0].event,
+            HtlcEvent::HtlcForceRefunded(vec![HtlcForceRefundedData {
+                order_hash: order_hash_for(1),
+                rescue_to: accounts(4),
+                amount: U128(ONE_NEAR),
+            }])
+        );
+        assert_locked(&contract, "NEAR", 0);
     }
 
-    /// Update timelock limits (owner only)
-    pub fn update_timelock_limits(&mut self, min_timelock: Timestamp, max_timelock: Timestamp) {
-        self.assert_owner();
-        assert!(min_timelock < max_timelock, "Invalid timelock limits");
-        self.min_timelock = min_timelock;
-        self.max_timelock = max_timelock;
+    #[test]
+    #[should_panic(expected = "Force refund delay not passed")]
+    fn test_owner_force_refund_waits_for_delay() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(0), RESCUE_AT - 1, 0);
+        contract.owner_force_refund(order_hash_for(1), accounts(4));
     }
 
-    /// Emergency withdrawal (owner only)
-    pub fn emergency_withdraw(&mut self, amount: U128) {
-        self.assert_owner();
-        Promise::new(self.owner.clone()).transfer(amount.0);
+    #[test]
+    #[should_panic(expected = "Order not active")]
+    fn test_owner_force_refund_skips_settled_orders() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        complete_order(&mut contract, 1);
+
+        set_context(accounts(0), RESCUE_AT, 0);
+        contract.owner_force_refund(order_hash_for(1), accounts(4));
     }
 
-    /// Transfer ownership (owner only)
-    pub fn transfer_ownership(&mut self, new_owner: AccountId) {
-        self.assert_owner();
-        self.owner = new_owner;
+    #[test]
+    #[should_panic(expected = "Only owner can call this method")]
+    fn test_owner_force_refund_owner_only() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(1), RESCUE_AT, 0);
+        contract.owner_force_refund(order_hash_for(1), accounts(1));
     }
 
-    // View functions
+    #[test]
+    fn test_get_order_borsh_round_trip() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        assert_eq!(contract.get_order_borsh(order_hash_for(2)), None);
 
-    pub fn get_owner(&self) -> AccountId {
-        self.owner.clone()
+        let bytes = contract.get_order_borsh(order_hash_for(1)).unwrap();
+        let order = SwapOrder::try_from_slice(&bytes.0).unwrap();
+        assert_eq!(order.order_hash, order_hash_for(1));
+        assert_eq!(order.dst_amount, U128(ONE_NEAR));
+        assert_eq!(order.hash_lock, hash_lock_for(&secret_for(1)));
+        assert_eq!(order.try_to_vec().unwrap(), bytes.0);
     }
 
-    pub fn is_chain_supported(&self, chain: String) -> bool {
-        self.supported_chains.get(&chain).unwrap_or(false)
+    fn limit_creates_per_block(contract: &mut NEAREscrow, max_creates_per_block: u32) {
+        set_context(accounts(0), START, 0);
+        contract.set_max_creates_per_block(max_creates_per_block);
     }
 
-    pub fn get_timelock_limits(&self) -> (Timestamp, Timestamp) {
-        (self.min_timelock, self.max_timelock)
+    #[test]
+    #[should_panic(expected = "E_RATE_LIMITED: Too many orders created in this block")]
+    fn test_create_limit_per_block() {
+        let mut contract = setup_contract();
+        limit_creates_per_block(&mut contract, 2);
+        create_order(&mut contract, 1, ONE_NEAR);
+        create_order(&mut contract, 2, ONE_NEAR);
+        create_order(&mut contract, 3, ONE_NEAR);
     }
 
-    // Private functions
+    #[test]
+    fn test_create_limit_resets_next_block() {
+        let mut contract = setup_contract();
+        limit_creates_per_block(&mut contract, 2);
+        create_order(&mut contract, 1, ONE_NEAR);
+        create_order(&mut contract, 2, ONE_NEAR);
 
-    fn assert_owner(&self) {
+        let mut context = get_context(accounts(1));
+        context
+            .block_index(1)
+            .block_timestamp(START)
+            .attached_deposit(2 * ONE_NEAR);
+        testing_env!(context.build());
+        contract.create_htlc_batch(vec![batch_args(3, ONE_NEAR), batch_args(4, ONE_NEAR)]);
+        assert!(contract.get_swap_order(order_hash_for(4)).is_some());
+        assert_eq!(contract.resolver_creates.get(&accounts(1)), Some((1, 2)));
+    }
+
+    #[test]
+    fn test_native_token_name_is_case_insensitive() {
+        let mut contract = setup_contract();
+        for (id, dst_token) in [(1, "near"), (2, "Near")] {
+            create_test_order(
+                &mut contract,
+                TestOrder {
+                    dst_token: dst_token.to_string(),
+                    ..TestOrder::new(id, ONE_NEAR)
+                },
+            );
+            let order = contract.get_swap_order(order_hash_for(id)).unwrap();
+            assert_eq!(order.dst_token, "NEAR");
+            assert_eq!(order.dst_asset, DstAsset::Native);
+
+            complete_order(&mut contract, id);
+            assert_eq!(created_transfers(), vec![(accounts(2), ONE_NEAR)]);
+            assert!(created_function_calls()
+                .iter()
+                .all(|(_, function_name)| function_name != "ft_transfer"));
+        }
+        assert_locked(&contract, "NEAR", 0);
+    }
+
+    #[test]
+    fn test_token_order_routes_to_ft_transfer() {
+        let mut contract = setup_contract();
+        ft_on_transfer(&mut contract, 500, ft_message(1, "ethereum"));
+        let order = contract.get_swap_order(order_hash_for(1)).unwrap();
+        assert_eq!(order.dst_asset, DstAsset::Ft);
+
+        complete_order(&mut contract, 1);
         assert_eq!(
-            env::predecessor_account_id(),
-            self.owner,
-            "Only owner can call this method"
+            created_function_calls()[0],
+            (token_account(), "ft_transfer".to_string())
         );
+        assert!(created_transfers().is_empty());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use near_sdk::test_utils::{accounts, VMContextBuilder};
-    use near_sdk::{testing_env, MockedBlockchain};
+    #[test]
+    fn test_ft_on_transfer_rejects_sentinel_named_token() {
+        let mut contract = setup_contract();
+        set_context("near".parse().unwrap(), START, 0);
+        match contract.ft_on_transfer(accounts(1), U128(500), ft_message(1, "ethereum")) {
+            PromiseOrValue::Value(unused) => assert_eq!(unused, U128(500)),
+            PromiseOrValue::Promise(_) => panic!("Expected a value"),
+        }
+        assert!(contract.get_swap_order(order_hash_for(1)).is_none());
+    }
 
-    fn get_context(predecessor: AccountId) -> VMContextBuilder {
-        let mut builder = VMContextBuilder::new();
-        builder.predecessor_account_id(predecessor);
-        builder
+    #[test]
+    fn test_nft_on_transfer_rejects_sentinel_named_contract() {
+        let mut contract = setup_contract();
+        set_context("near".parse().unwrap(), START, 0);
+        match contract.nft_on_transfer(
+            accounts(3),
+            accounts(1),
+            "token-7".to_string(),
+            ft_message(1, "ethereum"),
+        ) {
+            PromiseOrValue::Value(return_nft) => assert!(return_nft),
+            PromiseOrValue::Promise(_) => panic!("Expected a value"),
+        }
+        assert!(contract.get_swap_order(order_hash_for(1)).is_none());
     }
 
     #[test]
-    fn test_contract_creation() {
-        let mut context = get_context(accounts(0));
-        testing_env!(context.build());
+    fn test_token_id_parse() {
+        assert_eq!(TokenId::parse("NEAR"), TokenId::Native);
+        assert_eq!(TokenId::parse("near"), TokenId::Native);
+        assert_eq!(TokenId::parse("Near"), TokenId::Native);
+        assert_eq!(
+            TokenId::parse(token_account().as_str()),
+            TokenId::Ft(token_account())
+        );
+        assert_eq!(TokenId::Native.key(), "NEAR");
+        assert_eq!(
+            TokenId::Ft(token_account()).key(),
+            token_account().to_string()
+        );
+    }
 
-        let contract = NEAREscrow::new(accounts(0));
-        assert_eq!(contract.get_owner(), accounts(0));
+    #[test]
+    #[should_panic(expected = "E_INVALID_TOKEN: Only native NEAR can be attached")]
+    fn test_create_rejects_token_name_for_native_order() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                dst_token: token_account().to_string(),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
     }
 
     #[test]
-    fn test_verify_secret() {
+    fn test_is_corridor_enabled() {
+        let mut contract = setup_contract();
+        let token = token_account().to_string();
+        let corridor = |contract: &NEAREscrow, chain: &str, token: &str| {
+            contract.is_corridor_enabled(chain.to_string(), token.to_string())
+        };
+        assert!(corridor(&contract, "ethereum", "NEAR"));
+        assert!(!corridor(&contract, "bitcoin", "NEAR"));
+        assert!(!corridor(&contract, "ethereum", &token));
+
+        contract.register_token(token.clone(), 6, "USDC".to_string());
+        assert!(corridor(&contract, "ethereum", &token));
+        assert!(!corridor(&contract, "bitcoin", &token));
+
+        contract.pause();
+        assert!(!corridor(&contract, "ethereum", "NEAR"));
+        assert!(!corridor(&contract, "ethereum", &token));
+    }
+
+    #[test]
+    fn test_get_orders_after_id_matches_offset_pages() {
+        let mut contract = setup_contract();
+        for id in 1..=7u8 {
+            create_order(&mut contract, id, ONE_NEAR);
+        }
+
+        let mut by_cursor = Vec::new();
+        let mut after_id = None;
+        loop {
+            let page = contract.get_orders_after_id(after_id, Some(3));
+            match page.last() {
+                Some(last) => after_id = Some(last.order_id),
+                None => break,
+            }
+            by_cursor.extend(order_ids(page));
+        }
+        let by_offset: Vec<u8> = (0..3)
+            .flat_map(|page| order_ids(contract.get_active_orders(Some(page * 3), Some(3))))
+            .collect();
+        assert_eq!(by_cursor, by_offset);
+        assert_eq!(by_cursor, (1..=7).collect::<Vec<u8>>());
+        assert!(contract.get_orders_after_id(Some(6), None).is_empty());
+    }
+
+    #[test]
+    fn test_auto_register_recipient_before_token_payout() {
+        let mut contract = setup_contract();
+        let msg = serde_json::json!({
+            "order_hash": order_hash_for(1),
+            "src_maker": "0xmaker",
+            "src_chain": "ethereum",
+            "dst_recipient": accounts(2),
+            "hash_lock": hash_lock_for(&secret_for(1)),
+            "timelock": START + 2 * HOUR,
+            "auto_register_recipient": true,
+        })
+        .to_string();
+        ft_on_transfer(&mut contract, 500, msg);
+        let available = storage_available(&contract, accounts(1));
+
+        complete_order(&mut contract, 1);
+        assert_eq!(
+            created_function_calls(),
+            vec![
+                (token_account(), "storage_deposit".to_string()),
+                (token_account(), "ft_transfer".to_string()),
+                (contract_account(), "on_transfer_complete".to_string()),
+            ]
+        );
+        assert_eq!(
+            storage_available(&contract, accounts(1)),
+            available - contract.get_storage_subsidy().0
+        );
+    }
+
+    #[test]
+    fn test_token_payout_without_auto_register() {
+        let mut contract = setup_contract();
+        ft_on_transfer(&mut contract, 500, ft_message(1, "ethereum"));
+
+        complete_order(&mut contract, 1);
+        assert!(created_function_calls()
+            .iter()
+            .all(|(_, function_name)| function_name != "storage_deposit"));
+    }
+
+    #[test]
+    fn test_order_counts_follow_transitions() {
+        let mut contract = setup_contract();
+        for id in 1..=4 {
+            create_order(&mut contract, id, ONE_NEAR);
+        }
+        assert_eq!(
+            contract.get_order_counts(),
+            OrderCounts {
+                active_count: 4,
+                ..OrderCounts::default()
+            }
+        );
+
+        complete_order(&mut contract, 1);
+        fail_completion(&mut contract, 2);
+        refund_order(&mut contract, 3);
+        contract.expire_htlc(order_hash_for(4));
+        assert_eq!(
+            contract.get_order_counts(),
+            OrderCounts {
+                active_count: 1,
+                completed_count: 1,
+                refunded_count: 1,
+                expired_count: 1,
+                ..OrderCounts::default()
+            }
+        );
+
+        refund_order(&mut contract, 4);
+        purge_order(&mut contract, 1);
+        assert_eq!(
+            contract.get_order_counts(),
+            OrderCounts {
+                active_count: 1,
+                refunded_count: 2,
+                ..OrderCounts::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_push_on_complete_pays_recipient() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        complete_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(2), ONE_NEAR)]);
+        assert_eq!(
+            contract.get_claimable(accounts(2), "NEAR".to_string()),
+            U128(0)
+        );
+    }
+
+    #[test]
+    fn test_pull_mode_completion_credits_claim() {
+        let mut contract = setup_contract();
+        let mut order = TestOrder::new(1, ONE_NEAR);
+        order.push_on_complete = Some(false);
+        create_test_order(&mut contract, order);
+
+        complete_order(&mut contract, 1);
+        assert_eq!(order_state(&contract, 1), Some(HTLCState::Completed));
+        assert!(created_transfers().is_empty());
+        assert_eq!(
+            contract.get_claimable(accounts(2), "NEAR".to_string()),
+            U128(ONE_NEAR)
+        );
+        assert_eq!(contract.get_solvency().claimable_native, U128(ONE_NEAR));
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.claim("NEAR".to_string());
+        assert_eq!(created_transfers(), vec![(accounts(2), ONE_NEAR)]);
+        assert_eq!(
+            contract.get_claimable(accounts(2), "NEAR".to_string()),
+            U128(0)
+        );
+    }
+
+    #[test]
+    fn test_failed_claim_restores_balance() {
+        let mut contract = setup_contract();
+        let mut order = TestOrder::new(1, ONE_NEAR);
+        order.push_on_complete = Some(false);
+        create_test_order(&mut contract, order);
+        complete_order(&mut contract, 1);
+        set_context(accounts(2), START + HOUR, 0);
+        contract.claim("NEAR".to_string());
+
+        set_promise_result(PromiseResult::Failed);
+        assert!(!contract.on_claim_complete(accounts(2), "NEAR".to_string(), U128(ONE_NEAR)));
+        assert_eq!(
+            contract.get_claimable(accounts(2), "NEAR".to_string()),
+            U128(ONE_NEAR)
+        );
+    }
+
+    #[test]
+    fn test_claim_native_in_any_casing() {
+        let mut contract = setup_contract();
+        let mut order = TestOrder::new(1, ONE_NEAR);
+        order.push_on_complete = Some(false);
+        create_test_order(&mut contract, order);
+        complete_order(&mut contract, 1);
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.claim("near".to_string());
+        assert_eq!(created_transfers(), vec![(accounts(2), ONE_NEAR)]);
+        assert_eq!(contract.get_solvency().claimable_native, U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Nothing to claim")]
+    fn test_claim_without_balance() {
+        let mut contract = setup_contract();
+        set_context(accounts(2), START, 0);
+        contract.claim("NEAR".to_string());
+    }
+
+    #[test]
+    fn test_complete_within_min_payout_and_deadline() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        set_fee(&mut contract, 100);
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc(
+            order_hash_for(1),
+            secret_for(1),
+            Some(U128(ONE_NEAR * 99 / 100)),
+            Some(START + HOUR),
+        );
+        assert_eq!(
+            created_transfers(),
+            vec![(accounts(2), ONE_NEAR * 99 / 100)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "E_PAYOUT_BELOW_MINIMUM: Payout below minimum")]
+    fn test_complete_rejects_payout_below_min() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        set_fee(&mut contract, 100);
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc(order_hash_for(1), secret_for(1), Some(U128(ONE_NEAR)), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "E_DEADLINE_PASSED: Completion deadline passed")]
+    fn test_complete_rejects_past_deadline() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+
+        set_context(accounts(2), START + HOUR, 0);
+        contract.complete_htlc(
+            order_hash_for(1),
+            secret_for(1),
+            None,
+            Some(START + HOUR - 1),
+        );
+    }
+
+    fn set_storage_reserve(contract: &mut NEAREscrow, storage_reserve: Balance) {
+        set_context(accounts(0), START, 0);
+        contract.set_storage_reserve(U128(storage_reserve));
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount exceeds free balance")]
+    fn test_emergency_withdraw_keeps_storage_reserve() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, 10 * ONE_NEAR);
+        set_storage_reserve(&mut contract, 2 * ONE_NEAR);
+
+        set_balance_context(accounts(0), 20 * ONE_NEAR);
+        contract.emergency_withdraw(U128(5 * ONE_NEAR));
+    }
+
+    #[test]
+    fn test_emergency_withdraw_above_storage_reserve() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, 10 * ONE_NEAR);
+        set_storage_reserve(&mut contract, 2 * ONE_NEAR);
+
+        set_balance_context(accounts(0), 20 * ONE_NEAR);
+        contract.emergency_withdraw(U128(3 * ONE_NEAR));
+        assert_eq!(created_transfers(), vec![(accounts(0), 3 * ONE_NEAR)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount exceeds free balance")]
+    fn test_fee_withdrawal_keeps_storage_reserve() {
+        let mut contract = setup_contract();
+        set_fee(&mut contract, 100);
+        create_order(&mut contract, 1, 10 * ONE_NEAR);
+        complete_order(&mut contract, 1);
+        set_storage_reserve(&mut contract, 10 * ONE_NEAR);
+
+        set_balance_context(accounts(0), 10 * ONE_NEAR);
+        contract.withdraw_fees("NEAR".to_string(), U128(ONE_NEAR / 10), accounts(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Amount exceeds free balance")]
+    fn test_force_refund_keeps_storage_reserve() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, 10 * ONE_NEAR);
+        set_storage_reserve(&mut contract, 5 * ONE_NEAR);
+
         let mut context = get_context(accounts(0));
+        context
+            .block_timestamp(RESCUE_AT)
+            .account_balance(16 * ONE_NEAR);
         testing_env!(context.build());
+        contract.owner_force_refund(order_hash_for(1), accounts(4));
+    }
 
-        let contract = NEAREscrow::new(accounts(0));
-        let secret = Base64VecU8(b"test_secret".to_vec());
-        let hash = Sha256::digest(&secret.0);
-        let hash_lock = Base64VecU8(hash.to_vec());
+    #[test]
+    fn test_storage_reserve_in_config() {
+        let mut contract = setup_contract();
+        set_storage_reserve(&mut contract, ONE_NEAR);
+        assert_eq!(contract.get_storage_reserve(), U128(ONE_NEAR));
+        assert_eq!(contract.get_config().storage_reserve, U128(ONE_NEAR));
+    }
+
+    /// Assert `f` panics with a message starting with `code`
+    fn assert_error_code(code: ErrorCode, f: impl FnOnce()) {
+        let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+            .expect_err("Expected a panic");
+        let message = payload
+            .downcast::<String>()
+            .map_or_else(|_| String::new(), |message| *message);
+        assert!(
+            message.starts_with(&format!("{}: ", code.as_str())),
+            "Unexpected panic message: {}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_error_code_unsupported_chain() {
+        let mut contract = setup_contract();
+        assert_error_code(ErrorCode::UnsupportedChain, || {
+            create_test_order(
+                &mut contract,
+                TestOrder {
+                    src_chain: "bitcoin".to_string(),
+                    ..TestOrder::new(1, ONE_NEAR)
+                },
+            );
+        });
+    }
+
+    #[test]
+    fn test_error_code_invalid_amount() {
+        let mut contract = setup_contract();
+        assert_error_code(ErrorCode::InvalidAmount, || {
+            create_test_order(
+                &mut contract,
+                TestOrder {
+                    safety_deposit: Some(U128(ONE_NEAR)),
+                    ..TestOrder::new(1, ONE_NEAR)
+                },
+            );
+        });
+    }
+
+    #[test]
+    fn test_error_code_invalid_timelock() {
+        let mut contract = setup_contract();
+        assert_error_code(ErrorCode::InvalidTimelock, || {
+            create_test_order(
+                &mut contract,
+                TestOrder {
+                    public_window_start: Some(START + 3 * HOUR),
+                    ..TestOrder::new(1, ONE_NEAR)
+                },
+            );
+        });
+    }
+
+    #[test]
+    fn test_error_code_order_exists() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        assert_error_code(ErrorCode::OrderExists, || {
+            create_order(&mut contract, 1, ONE_NEAR);
+        });
+    }
+
+    #[test]
+    fn test_error_code_invalid_hash_lock() {
+        let mut contract = setup_contract();
+        assert_error_code(ErrorCode::InvalidHashLock, || {
+            create_test_order(
+                &mut contract,
+                TestOrder {
+                    merkle_root: Some(Base64VecU8(vec![0; 8])),
+                    ..TestOrder::new(1, ONE_NEAR)
+                },
+            );
+        });
+    }
+
+    #[test]
+    fn test_error_code_insufficient_storage() {
+        let mut contract = setup_contract();
+        set_context(accounts(3), START, ONE_NEAR);
+        assert_error_code(ErrorCode::InsufficientStorage, || {
+            contract.create_htlc_batch(vec![batch_args(1, ONE_NEAR)]);
+        });
+    }
+
+    #[test]
+    fn test_error_code_order_not_found() {
+        let mut contract = setup_contract();
+        assert_error_code(ErrorCode::OrderNotFound, || {
+            complete_order(&mut contract, 1);
+        });
+        assert_error_code(ErrorCode::OrderNotFound, || {
+            refund_order(&mut contract, 1);
+        });
+    }
+
+    #[test]
+    fn test_error_code_order_not_active() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        complete_order(&mut contract, 1);
+        assert_error_code(ErrorCode::OrderNotActive, || {
+            complete_order(&mut contract, 1);
+        });
+        assert_error_code(ErrorCode::OrderNotActive, || {
+            refund_order(&mut contract, 1);
+        });
+    }
+
+    #[test]
+    fn test_error_code_order_expired() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        set_context(accounts(2), START + 3 * HOUR, 0);
+        assert_error_code(ErrorCode::OrderExpired, || {
+            contract.complete_htlc(order_hash_for(1), secret_for(1), None, None);
+        });
+    }
+
+    #[test]
+    fn test_error_code_order_not_expired() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        set_context(accounts(1), START + HOUR, 0);
+        assert_error_code(ErrorCode::OrderNotExpired, || {
+            contract.refund_htlc(order_hash_for(1));
+        });
+    }
+
+    #[test]
+    fn test_error_code_invalid_secret() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        set_context(accounts(2), START + HOUR, 0);
+        assert_error_code(ErrorCode::InvalidSecret, || {
+            contract.complete_htlc(order_hash_for(1), secret_for(2), None, None);
+        });
+    }
+
+    #[test]
+    fn test_error_code_partial_fill_required() {
+        let mut contract = setup_contract();
+        create_test_order(
+            &mut contract,
+            TestOrder {
+                merkle_root: Some(Base64VecU8(vec![0; 32])),
+                parts: Some(2),
+                ..TestOrder::new(1, ONE_NEAR)
+            },
+        );
+        assert_error_code(ErrorCode::PartialFillRequired, || {
+            complete_order(&mut contract, 1);
+        });
+    }
+
+    #[test]
+    fn test_error_code_partial_fill_out_of_order() {
+        let mut contract = setup_contract();
+        let (_, proof_1) = create_merkle_order(&mut contract);
+        set_context(accounts(2), START + HOUR, 0);
+        assert_error_code(ErrorCode::InvalidLeafIndex, || {
+            contract.complete_htlc_partial(
+                order_hash_for(1),
+                secret_for(2),
+                U128(ONE_NEAR),
+                vec![proof_1],
+                1,
+                None,
+            );
+        });
+    }
+
+    #[test]
+    fn test_error_code_fund_active_order() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        set_context(accounts(1), START, ONE_NEAR);
+        assert_error_code(ErrorCode::OrderNotActive, || {
+            contract.fund_htlc(order_hash_for(1));
+        });
+    }
+
+    #[test]
+    fn test_error_code_payout_below_minimum() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        set_context(accounts(2), START + HOUR, 0);
+        assert_error_code(ErrorCode::PayoutBelowMinimum, || {
+            contract.complete_htlc(
+                order_hash_for(1),
+                secret_for(1),
+                Some(U128(2 * ONE_NEAR)),
+                None,
+            );
+        });
+    }
+
+    fn auction_order(amount: Balance) -> TestOrder {
+        TestOrder {
+            auction: Some(AuctionConfig {
+                start_amount: U128(2 * ONE_NEAR),
+                floor_amount: U128(ONE_NEAR),
+                start_time: START - HOUR,
+                duration: 2 * HOUR,
+            }),
+            ..TestOrder::new(1, amount)
+        }
+    }
+
+    #[test]
+    fn test_auction_price_decays_to_floor() {
+        let mut contract = setup_contract();
+        create_test_order(&mut contract, auction_order(3 * ONE_NEAR / 2));
+
+        assert_eq!(
+            contract.get_auction_price(order_hash_for(1)),
+            Some(U128(3 * ONE_NEAR / 2))
+        );
+        set_context(accounts(2), START + HOUR, 0);
+        assert_eq!(
+            contract.get_auction_price(order_hash_for(1)),
+            Some(U128(ONE_NEAR))
+        );
+        set_context(accounts(2), START + 2 * HOUR, 0);
+        assert_eq!(
+            contract.get_auction_price(order_hash_for(1)),
+            Some(U128(ONE_NEAR))
+        );
+
+        complete_order(&mut contract, 1);
+        assert_eq!(created_transfers(), vec![(accounts(2), 3 * ONE_NEAR / 2)]);
+    }
 
-        assert!(contract.verify_secret(secret, hash_lock));
+    #[test]
+    #[should_panic(expected = "E_INVALID_AMOUNT: Amount below auction price")]
+    fn test_auction_rejects_amount_below_price() {
+        let mut contract = setup_contract();
+        create_test_order(&mut contract, auction_order(ONE_NEAR));
+    }
+
+    #[test]
+    #[should_panic(expected = "E_INVALID_AUCTION: Invalid auction")]
+    fn test_auction_floor_above_start() {
+        let mut contract = setup_contract();
+        let mut order = auction_order(2 * ONE_NEAR);
+        order.auction = Some(AuctionConfig {
+            start_amount: U128(ONE_NEAR),
+            floor_amount: U128(2 * ONE_NEAR),
+            start_time: START,
+            duration: HOUR,
+        });
+        create_test_order(&mut contract, order);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_order_without_auction_has_no_price() {
+        let mut contract = setup_contract();
+        create_order(&mut contract, 1, ONE_NEAR);
+        assert_eq!(contract.get_auction_price(order_hash_for(1)), None);
+    }
+}
@@ -0,0 +1,47 @@
+//! NEP-330 source metadata, letting tooling identify the deployed version
+//! and the standards it implements
+use crate::*;
+
+pub const CONTRACT_SOURCE_LINK: &str = "https://github.com/WilliamUW/ETHGlobalUnite";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Standard {
+    pub standard: String,
+    pub version: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractSourceMetadata {
+    pub version: Option<String>,
+    pub link: Option<String>,
+    pub standards: Vec<Standard>,
+}
+
+impl Standard {
+    fn new(standard: &str, version: &str) -> Self {
+        Self {
+            standard: standard.to_string(),
+            version: version.to_string(),
+        }
+    }
+}
+
+#[near_bindgen]
+impl NEAREscrow {
+    /// NEP-330 contract source metadata
+    pub fn contract_source_metadata(&self) -> ContractSourceMetadata {
+        ContractSourceMetadata {
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            link: Some(CONTRACT_SOURCE_LINK.to_string()),
+            standards: vec![
+                Standard::new("nep330", "1.1.0"),
+                Standard::new("nep297", "1.0.0"),
+                Standard::new("nep141", "1.0.0"),
+                Standard::new("nep171", "1.0.0"),
+                Standard::new(EVENT_STANDARD, EVENT_STANDARD_VERSION),
+            ],
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use crate::*;
+
+/// Swap order layout of the initial release
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldSwapOrder {
+    pub order_hash: Base64VecU8,
+    pub src_maker: String,
+    pub src_chain: String,
+    pub src_token: String,
+    pub src_amount: U128,
+    pub dst_recipient: AccountId,
+    pub dst_token: String,
+    pub dst_amount: U128,
+    pub hash_lock: Base64VecU8,
+    pub timelock: Timestamp,
+    pub state: HTLCState,
+    pub created_at: Timestamp,
+    pub resolver: AccountId,
+}
+
+/// Contract state layout of the initial release
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct OldNEAREscrow {
+    pub owner: AccountId,
+    pub swap_orders: UnorderedMap<Base64VecU8, OldSwapOrder>,
+    pub deposits: LookupMap<Base64VecU8, Balance>,
+    pub supported_chains: LookupMap<String, bool>,
+    pub min_timelock: Timestamp,
+    pub max_timelock: Timestamp,
+}
+
+/// The initial release only escrowed attached NEAR, whatever `dst_token`
+/// said, so every old order becomes a native order keyed under "NEAR".
+impl From<OldSwapOrder> for SwapOrder {
+    fn from(old: OldSwapOrder) -> Self {
+        Self {
+            order_hash: old.order_hash,
+            src_maker: old.src_maker,
+            src_chain: old.src_chain,
+            src_token: old.src_token,
+            src_amount: old.src_amount,
+            dst_recipient: old.dst_recipient,
+            dst_token: "NEAR".to_string(),
+            dst_amount: old.dst_amount,
+            hash_lock: old.hash_lock,
+            hash_algorithm: HashAlgorithm::Sha256,
+            timelock: old.timelock,
+            complete_by: None,
+            state: old.state,
+            created_at: old.created_at,
+            resolver: old.resolver,
+            refund_bridge: None,
+            auto_complete_secret: None,
+            src_timelock: None,
+            revealed_secret: None,
+            safety_deposit: U128(0),
+            public_window_start: None,
+            timelocks: None,
+            merkle_root: None,
+            parts_filled: 0,
+            filled_amount: U128(0),
+            storage_bytes: 0,
+            order_id: 0,
+            dst_asset: DstAsset::Native,
+            memo: None,
+            refund_to: None,
+            relayer_fee: U128(0),
+            allow_recipient_override: false,
+            src_block_height: 0,
+            completion_attempts: 0,
+            alt_hash_locks: Vec::new(),
+            matched_hash_lock: None,
+            auto_register_recipient: false,
+            push_on_complete: true,
+            auction: None,
+            parts: 0,
+        }
+    }
+}
+
+#[near_bindgen]
+impl NEAREscrow {
+    /// Upgrade state written by the initial release: rewrites every order in
+    /// the current layout, rebuilds the active indexes and gives settings
+    /// added since then their `new` defaults. Run once, right after
+    /// deploying the new code.
+    ///
+    /// The old chain map can't be enumerated, so the chains to carry over
+    /// are listed in `supported_chains`; each keeps its enabled flag and
+    /// gets a zeroed config, and unknown names are skipped.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate(supported_chains: Vec<String>) -> Self {
+        let mut old: OldNEAREscrow = env::state_read().expect("Failed to read old state");
+
+        let orders: Vec<SwapOrder> = old.swap_orders.values().map(SwapOrder::from).collect();
+        old.swap_orders.clear();
+
+        let mut contract = Self::new(old.owner);
+        contract.deposits = old.deposits;
+        for chain in supported_chains {
+            if let Some(enabled) = old.supported_chains.remove(&chain) {
+                contract.supported_chains.insert(
+                    &chain,
+                    &ChainConfig {
+                        chain_id: 0,
+                        min_confirmations: 0,
+                        enabled,
+                    },
+                );
+            }
+        }
+        contract.min_timelock = old.min_timelock;
+        contract.max_timelock = old.max_timelock;
+
+        for mut order in orders {
+            contract.assign_order_id(&mut order);
+            contract.swap_orders.insert(&order.order_hash, &order);
+            contract.used_order_hashes.insert(&order.order_hash);
+            contract.index_order(&order);
+            contract.order_counts.add(&order.state);
+            contract.total_orders_created += 1;
+            let mut stats = contract
+                .resolver_stats
+                .get(&order.resolver)
+                .unwrap_or_default();
+            stats.orders_created += 1;
+            match order.state {
+                HTLCState::Completed => {
+                    contract.total_completed += 1;
+                    contract.add_volume(&order.dst_token, order.dst_amount.0 as i128);
+                    stats.completed += 1;
+                    stats.total_volume = U128(stats.total_volume.0 + order.dst_amount.0);
+                }
+                HTLCState::Refunded => {
+                    contract.total_refunded += 1;
+                    stats.refunded += 1;
+                }
+                _ => {}
+            }
+            contract.resolver_stats.insert(&order.resolver, &stats);
+            if order.state == HTLCState::Active {
+                contract.activate_order(&order.order_hash, order.dst_amount.0);
+                contract.lock(&order.token_id(), order.dst_amount.0);
+            }
+        }
+        contract
+    }
+}
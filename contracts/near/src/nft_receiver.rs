@@ -0,0 +1,85 @@
+use crate::*;
+use near_sdk::PromiseOrValue;
+
+#[near_bindgen]
+impl NEAREscrow {
+    /// NEP-171 receiver: lock the transferred NFT into a new HTLC, with the
+    /// order parameters in `msg` as for `ft_on_transfer`. The calling NFT
+    /// contract becomes the order's `dst_token` and `previous_owner_id` its
+    /// resolver, who gets the NFT back on refund. Returns whether the NFT
+    /// should be returned to its previous owner.
+    #[allow(unused_variables)]
+    pub fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: String,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        let message: FtHtlcMessage = match serde_json::from_str(&msg) {
+            Ok(message) => message,
+            Err(_) => {
+                env::log_str("Malformed HTLC message, returning NFT");
+                return PromiseOrValue::Value(true);
+            }
+        };
+
+        // As for tokens, an NFT contract named like the "NEAR" sentinel is
+        // too easily mistaken for native NEAR
+        if TokenId::is_native_name(env::predecessor_account_id().as_str()) {
+            env::log_str("Ambiguous NFT contract, returning NFT");
+            return PromiseOrValue::Value(true);
+        }
+
+        if !self.is_chain_supported(message.src_chain.clone()) {
+            env::log_str("Unsupported source chain, returning NFT");
+            return PromiseOrValue::Value(true);
+        }
+
+        let contract = env::predecessor_account_id();
+        self.internal_create_htlc(SwapOrder {
+            order_hash: message.order_hash,
+            src_maker: message.src_maker,
+            src_chain: message.src_chain,
+            src_token: message.src_token.unwrap_or_default(),
+            src_amount: message.src_amount.unwrap_or(U128(0)),
+            dst_recipient: message.dst_recipient,
+            dst_token: contract.to_string(),
+            dst_amount: U128(1),
+            hash_lock: message.hash_lock,
+            hash_algorithm: message.hash_algorithm.unwrap_or_default(),
+            timelock: message.timelock,
+            complete_by: message.complete_by,
+            state: HTLCState::Active,
+            created_at: env::block_timestamp(),
+            resolver: previous_owner_id,
+            refund_bridge: message.refund_bridge,
+            auto_complete_secret: message.auto_complete_secret,
+            src_timelock: message.src_timelock,
+            revealed_secret: None,
+            safety_deposit: U128(0),
+            public_window_start: None,
+            timelocks: None,
+            merkle_root: None,
+            parts_filled: 0,
+            filled_amount: U128(0),
+            storage_bytes: 0,
+            order_id: 0,
+            dst_asset: DstAsset::Nft { contract, token_id },
+            memo: message.memo,
+            refund_to: message.refund_to,
+            relayer_fee: message.relayer_fee.unwrap_or(U128(0)),
+            allow_recipient_override: message.allow_recipient_override.unwrap_or(false),
+            src_block_height: message.src_block_height.unwrap_or(0),
+            completion_attempts: 0,
+            alt_hash_locks: message.alt_hash_locks.unwrap_or_default(),
+            matched_hash_lock: None,
+            auto_register_recipient: false,
+            push_on_complete: true,
+            auction: None,
+            parts: 0,
+        });
+
+        PromiseOrValue::Value(false)
+    }
+}
@@ -0,0 +1,227 @@
+use crate::*;
+
+impl HashAlgorithm {
+    /// Root of a Merkle tree given a leaf, its sibling path and position.
+    /// Each level hashes `left || right`, with the bit of `index` at that
+    /// level telling whether the running node is the right child.
+    pub fn merkle_root(&self, leaf: Vec<u8>, proof: &[Base64VecU8], index: u32) -> Vec<u8> {
+        let mut node = leaf;
+        let mut index = index;
+        for sibling in proof {
+            let mut pair = Vec::with_capacity(node.len() + sibling.0.len());
+            if index & 1 == 0 {
+                pair.extend_from_slice(&node);
+                pair.extend_from_slice(&sibling.0);
+            } else {
+                pair.extend_from_slice(&sibling.0);
+                pair.extend_from_slice(&node);
+            }
+            node = self.digest(&pair);
+            index /= 2;
+        }
+        node
+    }
+}
+
+/// Part of a Merkle order being filled through `complete_htlc_partial`
+pub(crate) struct PartialFill {
+    pub fill_amount: Balance,
+    pub merkle_proof: Vec<Base64VecU8>,
+    pub leaf_index: u32,
+}
+
+#[near_bindgen]
+impl NEAREscrow {
+    /// Fill part of a Merkle order by revealing the secret at `leaf_index`.
+    /// Parts are filled in leaf order, and leaf `i` may bring the filled
+    /// total up to `i + 1` of the order's `parts` equal slices. Each fill
+    /// pays out like `complete_htlc`, relayer fee, pull mode and
+    /// `min_payout` included; the order is completed once it is filled.
+    pub fn complete_htlc_partial(
+        &mut self,
+        order_hash: Base64VecU8,
+        secret: Base64VecU8,
+        fill_amount: U128,
+        merkle_proof: Vec<Base64VecU8>,
+        leaf_index: u32,
+        min_payout: Option<U128>,
+    ) -> PromiseOrValue<bool> {
+        if let Some(order) = self.swap_orders.get(&order_hash) {
+            Self::assert_exclusive_access(&order);
+        }
+
+        let fill = PartialFill {
+            fill_amount: fill_amount.0,
+            merkle_proof,
+            leaf_index,
+        };
+        self.internal_complete_and_pay(order_hash, secret, None, min_payout, 0, Some(fill))
+    }
+
+    /// Whether the secret at `leaf_index` of a Merkle order has been used.
+    /// Leaves are consumed in order, so these are the first `parts_filled`.
+    pub fn is_leaf_used(&self, order_hash: Base64VecU8, leaf_index: u32) -> bool {
+        self.swap_orders
+            .get(&order_hash)
+            .is_some_and(|order| leaf_index < order.parts_filled)
+    }
+
+    /// Part of an order's deposit not yet filled, refunded to the resolver if
+    /// the order expires. Zero once the order is settled.
+    pub fn get_unfilled_amount(&self, order_hash: Base64VecU8) -> U128 {
+        U128(self.deposits.get(&order_hash).unwrap_or(0))
+    }
+
+    /// Callback for `complete_htlc_partial`: pay the executor its fee, and
+    /// the safety deposit on the final fill, or return a failed fill to the
+    /// deposit and take back the protocol `fee` accrued for it. A failed
+    /// fill of the latest leaf releases that leaf so it can be filled
+    /// again. If a later leaf was filled meanwhile, leaves stay consumed
+    /// and the amount is left to later leaves or the refund.
+    #[private]
+    pub fn on_partial_fill_transfer(
+        &mut self,
+        order_hash: Base64VecU8,
+        amount: U128,
+        executor: AccountId,
+        executor_fee: U128,
+        fee: U128,
+        leaf_index: u32,
+    ) -> bool {
+        let mut swap_order = self
+            .swap_orders
+            .get(&order_hash)
+            .expect("Order not found");
+
+        let succeeded = Self::transfer_succeeded();
+        if succeeded {
+            if executor_fee.0 > 0 {
+                self.pay_executor_fee(&swap_order, executor.clone(), executor_fee.0);
+            }
+            if swap_order.state == HTLCState::Completed {
+                self.pay_safety_deposit(&order_hash, executor);
+            }
+            return true;
+        }
+
+        let remaining = self.deposits.get(&order_hash).unwrap_or(0) + amount.0;
+        swap_order.filled_amount = U128(swap_order.filled_amount.0 - amount.0);
+        if swap_order.parts_filled == leaf_index + 1 {
+            swap_order.parts_filled = leaf_index;
+        }
+        self.add_volume(&swap_order.dst_token, -(amount.0 as i128));
+        swap_order.completion_attempts = swap_order.completion_attempts.saturating_add(1);
+        let completed = swap_order.state == HTLCState::Completed;
+        self.update_resolver_stats(&swap_order.resolver, |stats| {
+            stats.completed -= completed as u64;
+            stats.total_volume = U128(stats.total_volume.0 - amount.0);
+        });
+        if completed {
+            self.set_state(&mut swap_order, HTLCState::Active);
+            self.total_completed -= 1;
+            self.activate_order(&order_hash, remaining);
+        }
+        self.swap_orders.insert(&order_hash, &swap_order);
+        self.deposits.insert(&order_hash, &remaining);
+        self.lock(&swap_order.token_id(), amount.0);
+        self.unaccrue_fee(&swap_order.dst_token, fee.0);
+
+        self.emit_event(HtlcEvent::HtlcCompletionFailed(vec![
+            HtlcCompletionFailedData { order_hash, amount },
+        ]));
+        false
+    }
+}
+
+impl NEAREscrow {
+    /// Validate a fill of a Merkle order's next part and record it,
+    /// returning the order and the amount released
+    pub(crate) fn internal_fill_part(
+        &mut self,
+        order_hash: &Base64VecU8,
+        secret: &Base64VecU8,
+        fill: PartialFill,
+    ) -> (SwapOrder, Balance) {
+        let mut swap_order = self
+            .swap_orders
+            .get(order_hash)
+            .unwrap_or_else(|| fail(ErrorCode::OrderNotFound, "Order not found"));
+        let merkle_root = swap_order.merkle_root.clone().unwrap_or_else(|| {
+            fail(
+                ErrorCode::PartialFillUnsupported,
+                "Order does not support partial fills",
+            )
+        });
+        Self::assert_open_for_completion(&swap_order);
+        self.assert_source_confirmed(&swap_order);
+        self.assert_recipient_allowed(&swap_order);
+
+        let leaf_index = fill.leaf_index;
+        require(
+            leaf_index == swap_order.parts_filled,
+            ErrorCode::InvalidLeafIndex,
+            "Unexpected leaf index",
+        );
+        require(
+            leaf_index < swap_order.parts,
+            ErrorCode::InvalidLeafIndex,
+            "Leaf index out of range",
+        );
+        self.assert_secret_length(secret);
+        let leaf = swap_order.hash_algorithm.digest(&secret.0);
+        require(
+            swap_order
+                .hash_algorithm
+                .merkle_root(leaf, &fill.merkle_proof, leaf_index)
+                == merkle_root.0,
+            ErrorCode::InvalidSecret,
+            "Invalid merkle proof",
+        );
+
+        let remaining = self
+            .deposits
+            .get(order_hash)
+            .unwrap_or_else(|| fail(ErrorCode::OrderNotFound, "Deposit not found"));
+        let fill_amount = fill.fill_amount;
+        require(
+            fill_amount > 0 && fill_amount <= remaining,
+            ErrorCode::InvalidAmount,
+            "Invalid fill amount",
+        );
+        let total = swap_order.filled_amount.0 + remaining;
+        if let Some(auction) = &swap_order.auction {
+            Self::assert_auction_price(auction, total);
+        }
+        // Floor of total * (leaf_index + 1) / parts, without overflowing
+        let parts = swap_order.parts as u128;
+        let slices = leaf_index as u128 + 1;
+        let share = total / parts * slices + total % parts * slices / parts;
+        require(
+            swap_order.filled_amount.0 + fill_amount <= share,
+            ErrorCode::InvalidAmount,
+            "Fill exceeds the leaf's share",
+        );
+
+        swap_order.parts_filled += 1;
+        swap_order.filled_amount = U128(swap_order.filled_amount.0 + fill_amount);
+        swap_order.revealed_secret = Some(secret.clone());
+        self.unlock(&swap_order.token_id(), fill_amount);
+        self.add_volume(&swap_order.dst_token, fill_amount as i128);
+        let completed = fill_amount == remaining;
+        self.update_resolver_stats(&swap_order.resolver, |stats| {
+            stats.completed += completed as u64;
+            stats.total_volume = U128(stats.total_volume.0 + fill_amount);
+        });
+        if completed {
+            self.set_state(&mut swap_order, HTLCState::Completed);
+            self.total_completed += 1;
+            self.deposits.remove(order_hash);
+            self.deactivate_order(order_hash);
+        } else {
+            self.deposits.insert(order_hash, &(remaining - fill_amount));
+        }
+        self.swap_orders.insert(order_hash, &swap_order);
+
+        (swap_order, fill_amount)
+    }
+}
@@ -0,0 +1,252 @@
+//! NEP-145 storage management: resolvers prepay the storage their orders
+//! occupy, charged on creation and credited back as order data is freed
+use crate::*;
+use near_sdk::{assert_one_yocto, StorageUsage};
+
+/// Bytes reserved for a storage registration record (account id key,
+/// balance value and per-record overhead)
+pub const STORAGE_REGISTRATION_BYTES: StorageUsage = 200;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+#[near_bindgen]
+impl NEAREscrow {
+    /// Deposit NEAR to pay for the storage of `account_id`'s orders
+    /// (the caller by default). The registration minimum is kept out of the
+    /// available balance. With `registration_only` anything beyond the
+    /// minimum is refunded.
+    #[payable]
+    pub fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let registration_only = registration_only.unwrap_or(false);
+
+        let balance = match self.storage_balances.get(&account_id) {
+            Some(balance) if registration_only => {
+                if amount > 0 {
+                    Promise::new(env::predecessor_account_id()).transfer(amount);
+                }
+                balance
+            }
+            Some(balance) => StorageBalance {
+                total: U128(balance.total.0 + amount),
+                available: U128(balance.available.0 + amount),
+            },
+            None => {
+                let min = Self::storage_registration_cost();
+                assert!(
+                    amount >= min,
+                    "Insufficient deposit for storage registration"
+                );
+                if registration_only {
+                    if amount > min {
+                        Promise::new(env::predecessor_account_id()).transfer(amount - min);
+                    }
+                    StorageBalance {
+                        total: U128(min),
+                        available: U128(0),
+                    }
+                } else {
+                    StorageBalance {
+                        total: U128(amount),
+                        available: U128(amount - min),
+                    }
+                }
+            }
+        };
+
+        self.save_storage_balance(&account_id, &balance);
+        balance
+    }
+
+    /// Withdraw unused storage balance (all of it by default). Requires
+    /// exactly 1 yoctoNEAR attached.
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let mut balance = self
+            .storage_balances
+            .get(&account_id)
+            .expect("Account not registered for storage");
+        let amount = amount.map_or(balance.available.0, |amount| amount.0);
+        assert!(
+            amount <= balance.available.0,
+            "Insufficient storage balance"
+        );
+
+        balance.total = U128(balance.total.0 - amount);
+        balance.available = U128(balance.available.0 - amount);
+        self.save_storage_balance(&account_id, &balance);
+        if amount > 0 {
+            Promise::new(account_id).transfer(amount);
+        }
+        balance
+    }
+
+    /// Close the caller's storage registration and refund what isn't used
+    /// by orders. Fails while orders still use storage unless `force` is
+    /// set, which forfeits that part. Requires exactly 1 yoctoNEAR attached.
+    #[payable]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let balance = match self.storage_balances.get(&account_id) {
+            Some(balance) => balance,
+            None => return false,
+        };
+
+        let unused = balance.available.0 + Self::storage_registration_cost();
+        assert!(
+            unused >= balance.total.0 || force.unwrap_or(false),
+            "Storage still in use by orders"
+        );
+
+        self.total_storage_available -= balance.available.0;
+        self.storage_balances.remove(&account_id);
+        Promise::new(account_id).transfer(unused.min(balance.total.0));
+        true
+    }
+
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(Self::storage_registration_cost()),
+            max: None,
+        }
+    }
+
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.storage_balances.get(&account_id)
+    }
+}
+
+impl NEAREscrow {
+    fn storage_registration_cost() -> Balance {
+        STORAGE_REGISTRATION_BYTES as Balance * env::storage_byte_cost()
+    }
+
+    /// Store a storage balance, keeping `total_storage_available` in sync
+    fn save_storage_balance(&mut self, account_id: &AccountId, balance: &StorageBalance) {
+        let previous = self
+            .storage_balances
+            .insert(account_id, balance)
+            .map_or(0, |previous| previous.available.0);
+        self.total_storage_available =
+            self.total_storage_available - previous + balance.available.0;
+    }
+
+    /// Charge the storage added since `initial_storage` to `account_id`,
+    /// returning the bytes charged
+    pub(crate) fn charge_storage(
+        &mut self,
+        account_id: &AccountId,
+        initial_storage: StorageUsage,
+    ) -> StorageUsage {
+        let used = env::storage_usage().saturating_sub(initial_storage);
+        if used == 0 {
+            return 0;
+        }
+
+        let cost = used as Balance * env::storage_byte_cost();
+        let mut balance = self
+            .storage_balances
+            .get(account_id)
+            .unwrap_or_else(|| {
+                fail(
+                    ErrorCode::InsufficientStorage,
+                    "Account not registered for storage",
+                )
+            });
+        require(
+            cost <= balance.available.0,
+            ErrorCode::InsufficientStorage,
+            "Insufficient storage balance",
+        );
+        balance.available = U128(balance.available.0 - cost);
+        self.save_storage_balance(account_id, &balance);
+        used
+    }
+
+    /// Credit the storage an order freed since `initial_storage` back to its
+    /// resolver, up to what it was charged. The caller stores the order.
+    pub(crate) fn release_order_storage(
+        &mut self,
+        swap_order: &mut SwapOrder,
+        initial_storage: StorageUsage,
+    ) {
+        let freed = initial_storage
+            .saturating_sub(env::storage_usage())
+            .min(swap_order.storage_bytes);
+        swap_order.storage_bytes -= freed;
+        self.release_storage(&swap_order.resolver, freed);
+    }
+
+    /// Move an order's storage charge of `bytes` from one account to another
+    pub(crate) fn transfer_storage_charge(
+        &mut self,
+        from: &AccountId,
+        to: &AccountId,
+        bytes: StorageUsage,
+    ) {
+        if bytes == 0 {
+            return;
+        }
+
+        let cost = bytes as Balance * env::storage_byte_cost();
+        let mut balance = self
+            .storage_balances
+            .get(to)
+            .expect("Account not registered for storage");
+        assert!(cost <= balance.available.0, "Insufficient storage balance");
+        balance.available = U128(balance.available.0 - cost);
+        self.save_storage_balance(to, &balance);
+        self.release_storage(from, bytes);
+    }
+
+    /// Take `amount` out of `account_id`'s available storage balance,
+    /// returning false (and taking nothing) if it isn't there
+    pub(crate) fn take_storage_balance(&mut self, account_id: &AccountId, amount: Balance) -> bool {
+        match self.storage_balances.get(account_id) {
+            Some(mut balance) if balance.available.0 >= amount => {
+                balance.total = U128(balance.total.0 - amount);
+                balance.available = U128(balance.available.0 - amount);
+                self.save_storage_balance(account_id, &balance);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Credit `bytes` of storage back to `account_id`, if still registered
+    pub(crate) fn release_storage(&mut self, account_id: &AccountId, bytes: StorageUsage) {
+        if bytes == 0 {
+            return;
+        }
+
+        if let Some(mut balance) = self.storage_balances.get(account_id) {
+            let credit = bytes as Balance * env::storage_byte_cost();
+            let max_available = balance
+                .total
+                .0
+                .saturating_sub(Self::storage_registration_cost());
+            balance.available = U128((balance.available.0 + credit).min(max_available));
+            self.save_storage_balance(account_id, &balance);
+        }
+    }
+}